@@ -1,25 +1,38 @@
 use crate::{context::Context, error_status::ErrorStatus};
 use axum::{
     body::StreamBody,
-    extract::{BodyStream, Path},
+    extract::{BodyStream, Path, Query},
     headers::ContentLength,
     http::{
         header::{
-            CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
-            LAST_MODIFIED,
+            ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+            CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+            X_CONTENT_TYPE_OPTIONS,
         },
-        HeaderMap, HeaderValue, Method,
+        HeaderMap, HeaderValue, Method, StatusCode,
     },
-    response::{IntoResponse, Response},
+    response::{IntoResponse, Redirect, Response},
     Extension, Json, TypedHeader,
 };
 use chrono::{DateTime, Utc};
 use cloud_database::Claims;
 use futures::{future, StreamExt};
-use jwst::{error, BlobStorage};
+use jwst::{error, read_blob_ranges, BlobRanges, BlobStorage, BlobUsage, JwstError};
 use jwst_logger::{info, instrument, tracing};
+use jwst_storage::BlobGcOptions;
 use mime::APPLICATION_OCTET_STREAM;
-use std::sync::Arc;
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+pub struct GcQuery {
+    /// Only report the unreferenced blobs, nothing is deleted
+    #[serde(default)]
+    dry_run: bool,
+    /// Seconds a blob must stay unreferenced before it is deleted
+    grace_period: Option<u64>,
+}
 
 impl Context {
     #[instrument(skip(self, method, headers))]
@@ -51,12 +64,18 @@ impl Context {
             }
         }
 
-        let mut header = HeaderMap::with_capacity(5);
+        let mut header = HeaderMap::with_capacity(8);
         header.insert(ETAG, HeaderValue::from_str(&id).unwrap());
         header.insert(
             CONTENT_TYPE,
-            HeaderValue::from_static(APPLICATION_OCTET_STREAM.essence_str()),
+            HeaderValue::from_str(&meta.content_type).unwrap_or(HeaderValue::from_static(
+                APPLICATION_OCTET_STREAM.essence_str(),
+            )),
         );
+        if let Ok(disposition) = HeaderValue::from_str(&meta.content_disposition(&id)) {
+            header.insert(CONTENT_DISPOSITION, disposition);
+        }
+        header.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
         header.insert(
             LAST_MODIFIED,
             HeaderValue::from_str(&DateTime::<Utc>::from_utc(meta.last_modified, Utc).to_rfc2822())
@@ -70,20 +89,109 @@ impl Context {
             CACHE_CONTROL,
             HeaderValue::from_str("public, immutable, max-age=31536000").unwrap(),
         );
+        header.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
 
         if method == Method::HEAD {
             return header.into_response();
         };
 
+        // let the client download the blob from the object storage directly
+        if let Ok(Some(url)) = self
+            .storage
+            .blobs()
+            .get_blob_url(workspace.clone(), id.clone())
+            .await
+        {
+            return Redirect::temporary(&url).into_response();
+        }
+
+        let ranges = BlobRanges::from_headers(
+            headers.get(RANGE).and_then(|h| h.to_str().ok()),
+            headers
+                .get(IF_RANGE)
+                .map(|h| h.to_str().unwrap_or_default()),
+            &id,
+            &meta,
+        );
+        match ranges {
+            BlobRanges::Full => {}
+            BlobRanges::Unsatisfiable => {
+                return (
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(
+                        CONTENT_RANGE,
+                        BlobRanges::unsatisfiable_content_range(meta.size),
+                    )],
+                )
+                    .into_response()
+            }
+            BlobRanges::Partial(ranges) => {
+                let Ok(ranges) =
+                    read_blob_ranges(self.storage.blobs(), workspace, id, &meta, ranges).await
+                else {
+                    return ErrorStatus::NotFound.into_response();
+                };
+                if let Some(content_type) = ranges.content_type {
+                    header.insert(CONTENT_TYPE, HeaderValue::from_str(&content_type).unwrap());
+                }
+                if let Some(content_range) = ranges.content_range {
+                    header.insert(
+                        CONTENT_RANGE,
+                        HeaderValue::from_str(&content_range).unwrap(),
+                    );
+                }
+                header.insert(CONTENT_LENGTH, HeaderValue::from(ranges.content_length));
+                return (
+                    StatusCode::PARTIAL_CONTENT,
+                    header,
+                    StreamBody::new(ranges.body),
+                )
+                    .into_response();
+            }
+        }
+
         let Ok(file) = self.storage.blobs().get_blob(workspace, id).await else {
             return ErrorStatus::NotFound.into_response();
         };
         (header, StreamBody::new(file)).into_response()
     }
 
+    async fn blob_usage(&self, workspace: Option<String>) -> Result<BlobUsage, Response> {
+        self.storage
+            .blobs()
+            .get_usage(workspace)
+            .await
+            .map_err(|e| {
+                error!("Failed to get blob usage: {}", e);
+                ErrorStatus::InternalServerError.into_response()
+            })
+    }
+
+    /// With a `quota` the uploads to the workspace are serialized, so that the usage
+    /// only changes by the uploaded blob. A new blob that takes the workspace over the
+    /// quota is deleted again, while blobs that already exist can always be uploaded.
     #[instrument(skip(self, stream))]
-    async fn upload_blob(&self, stream: BodyStream, workspace: Option<String>) -> Response {
+    async fn upload_blob(
+        &self,
+        stream: BodyStream,
+        workspace: Option<String>,
+        content_type: Option<String>,
+        quota: Option<u64>,
+    ) -> Response {
         info!("upload_blob enter");
+        let (_guard, before) = match quota {
+            Some(_) => {
+                let guard = self
+                    .lock_blob_uploads(workspace.as_deref().unwrap_or_default())
+                    .await;
+                match self.blob_usage(workspace.clone()).await {
+                    Ok(usage) => (Some(guard), Some(usage)),
+                    Err(response) => return response,
+                }
+            }
+            None => (None, None),
+        };
+
         // TODO: cancel
         let mut has_error = false;
         let stream = stream
@@ -96,15 +204,24 @@ impl Context {
         if let Ok(id) = self
             .storage
             .blobs()
-            .put_blob(workspace.clone(), stream)
+            .put_blob(workspace.clone(), stream, content_type)
             .await
         {
             if has_error {
                 let _ = self.storage.blobs().delete_blob(workspace, id).await;
-                ErrorStatus::InternalServerError.into_response()
-            } else {
-                id.into_response()
+                return ErrorStatus::InternalServerError.into_response();
+            }
+            if let (Some(quota), Some(before)) = (quota, before) {
+                match self.blob_usage(workspace.clone()).await {
+                    Ok(after) if after.size > quota && after.count > before.count => {
+                        let _ = self.storage.blobs().delete_blob(workspace, id).await;
+                        return ErrorStatus::StorageQuotaExceeded.into_response();
+                    }
+                    Ok(_) => (),
+                    Err(response) => return response,
+                }
             }
+            id.into_response()
         } else {
             ErrorStatus::InternalServerError.into_response()
         }
@@ -144,8 +261,10 @@ impl Context {
     ),
     responses(
         (status = 200, description = "Successfully get blob",body=BodyStream),
+        (status = 206, description = "Successfully get the requested ranges of blob",body=BodyStream),
         (status = 304, description = "The file is not modified"),
         (status = 404, description = "The file does not exist"),
+        (status = 416, description = "The requested ranges are outside of the file"),
     )
 )]
 #[instrument(skip(ctx, method, headers))]
@@ -159,27 +278,38 @@ pub async fn get_blob(
     ctx.get_blob(None, id, method, headers).await
 }
 
+/// The content type declared by the upload, it is only used
+/// if the type cannot be detected from the content.
+fn declared_content_type(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
 ///  Upload `blob`.
 /// - Return 200 and `hash`.
-/// - Return 413 upload file size exceeds 10MB.
+/// - Return 413 upload file size exceeds the upload limit.
 #[utoipa::path(put, tag = "Blob", context_path = "/api", path = "/blob",
-request_body(content=BodyStream, description="file size needs to be less than 10MB", content_type="application/octet-stream"),
+request_body(content=BodyStream, description="file size needs to be within the upload limit", content_type="application/octet-stream"),
     responses(
         (status = 200, description = "Successfully upload blob",body=String),
-        (status = 413, description = "Upload file size exceeds 10MB"),
+        (status = 413, description = "Upload file size exceeds the upload limit"),
     ))]
-#[instrument(skip(ctx, length, stream))]
+#[instrument(skip(ctx, length, headers, stream))]
 pub async fn upload_blob(
     Extension(ctx): Extension<Arc<Context>>,
     TypedHeader(length): TypedHeader<ContentLength>,
+    headers: HeaderMap,
     stream: BodyStream,
 ) -> Response {
     info!("upload_blob enter");
-    if length.0 > 10 * 1024 * 1024 {
+    if length.0 > ctx.blob_upload_limit {
         return ErrorStatus::PayloadTooLarge.into_response();
     }
 
-    ctx.upload_blob(stream, None).await
+    ctx.upload_blob(stream, None, declared_content_type(&headers), None)
+        .await
 }
 
 ///  Get `blob` by workspace_id and hash.
@@ -197,8 +327,10 @@ pub async fn upload_blob(
     ),
     responses(
         (status = 200, description = "Successfully get blob",body=BodyStream),
+        (status = 206, description = "Successfully get the requested ranges of blob",body=BodyStream),
         (status = 304, description = "The file is not modified"),
         (status = 404, description = "The file or workspace does not exist"),
+        (status = 416, description = "The requested ranges are outside of the file"),
     )
 )]
 #[instrument(skip(ctx, method, headers))]
@@ -227,7 +359,7 @@ pub async fn get_blob_in_workspace(
 /// - Return 200 and `hash`.
 /// - Return 403 sorry, you do not have permission.
 /// - Return 404 the workspace does not exist.
-/// - Return 413 upload file size exceeds 10MB.
+/// - Return 413 upload file size exceeds the upload limit or the workspace storage quota.
 /// - Return 500 internal server error.
 #[utoipa::path(
     put,
@@ -237,25 +369,26 @@ pub async fn get_blob_in_workspace(
     params(
         ("workspace_id", description = "id of workspace"),
     ),
-    request_body(content=BodyStream, description="file size needs to be less than 10MB", content_type="application/octet-stream"),
+    request_body(content=BodyStream, description="file size needs to be within the upload limit", content_type="application/octet-stream"),
     responses(
         (status = 200, description = "Successfully upload blob",body=String),
         (status = 403, description = "Sorry, you do not have permission."),
         (status = 404, description = "The workspace does not exist"),
-        (status = 413, description = "Upload file size exceeds 10MB"),
+        (status = 413, description = "Upload file size exceeds the upload limit or the workspace storage quota"),
         (status = 500, description = "Internal server error"),
     )
 )]
-#[instrument(skip(ctx, claims, length, stream), fields(user_id = %claims.user.id))]
+#[instrument(skip(ctx, claims, length, headers, stream), fields(user_id = %claims.user.id))]
 pub async fn upload_blob_in_workspace(
     Extension(ctx): Extension<Arc<Context>>,
     Extension(claims): Extension<Arc<Claims>>,
     Path(workspace_id): Path<String>,
     TypedHeader(length): TypedHeader<ContentLength>,
+    headers: HeaderMap,
     stream: BodyStream,
 ) -> Response {
     info!("upload_blob_in_workspace enter");
-    if length.0 > 10 * 1024 * 1024 {
+    if length.0 > ctx.blob_upload_limit {
         return ErrorStatus::PayloadTooLarge.into_response();
     }
 
@@ -272,7 +405,75 @@ pub async fn upload_blob_in_workspace(
         }
     }
 
-    ctx.upload_blob(stream, Some(workspace_id)).await
+    ctx.upload_blob(
+        stream,
+        Some(workspace_id),
+        declared_content_type(&headers),
+        ctx.blob_quota,
+    )
+    .await
+}
+
+/// Delete the `blob`s that no block of the workspace refers to any more.
+/// - Return 200 and the report, blobs are only deleted once they stayed
+///   unreferenced for the grace period.
+/// - Return 403 sorry, you do not have permission.
+/// - Return 404 the workspace does not exist.
+/// - Return 500 internal server error.
+#[utoipa::path(
+    post,
+    tag = "Blob",
+    context_path = "/api/workspace",
+    path = "/{workspace_id}/gc",
+    params(
+        ("workspace_id", description = "id of workspace"),
+        GcQuery,
+    ),
+    responses(
+        (status = 200, description = "Successfully collect blobs", body = BlobGcReport),
+        (status = 403, description = "Sorry, you do not have permission."),
+        (status = 404, description = "The workspace does not exist"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(ctx, claims, query), fields(user_id = %claims.user.id))]
+pub async fn gc_workspace_blobs(
+    Extension(ctx): Extension<Arc<Context>>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(workspace_id): Path<String>,
+    Query(query): Query<GcQuery>,
+) -> Response {
+    info!("gc_workspace_blobs enter");
+    match ctx
+        .db
+        .get_permission(claims.user.id.clone(), workspace_id.clone())
+        .await
+    {
+        Ok(Some(p)) if p.can_admin() => (),
+        Ok(_) => return ErrorStatus::Forbidden.into_response(),
+        Err(e) => {
+            error!("Failed to get permission: {:?}", e);
+            return ErrorStatus::InternalServerError.into_response();
+        }
+    }
+
+    let options = BlobGcOptions {
+        grace_period: query
+            .grace_period
+            .map(Duration::from_secs)
+            .unwrap_or(ctx.blob_gc.grace_period),
+        dry_run: query.dry_run,
+    };
+    match ctx.storage.gc_blobs(&workspace_id, options).await {
+        Ok(report) => Json(report).into_response(),
+        Err(JwstError::WorkspaceNotFound(_)) => {
+            ErrorStatus::NotFoundWorkspace(workspace_id).into_response()
+        }
+        Err(e) => {
+            error!("Failed to gc blobs: {}", e);
+            ErrorStatus::InternalServerError.into_response()
+        }
+    }
 }
 
 /// Create `Workspace` .
@@ -8,17 +8,20 @@ pub use ws::*;
 use crate::{context::Context, error_status::ErrorStatus, layer::make_firebase_auth_layer};
 use axum::{
     extract::{Path, Query},
-    http::{StatusCode,header::CONTENT_TYPE, HeaderMap},
-    response::{IntoResponse, Response},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{Html, IntoResponse, Response},
     routing::{delete, get, post, put, Router},
     Extension, Json,
 };
 use chrono::{Duration, Utc};
 use cloud_database::{
-    Claims, MakeToken, RefreshToken, UpdateWorkspace, User, UserQuery, UserToken,
+    Claims, MakeToken, RefreshToken, UpdateWorkspace, User, UserQuery, UserSearchInput, UserToken,
     WorkspaceSearchInput,
 };
-use jwst::{error, BlobStorage, JwstError};
+use jwst::{error, BlobStorage, JwstError, SearchOptions};
 use jwst_logger::{instrument, info, tracing};
 use lib0::any::Any;
 use std::sync::Arc;
@@ -33,6 +36,8 @@ use utoipa::OpenApi;
         update_workspace,
         delete_workspace,
         search_workspace,
+        search_user_workspaces,
+        get_backlinks,
         query_user,
         make_token,
         get_doc,
@@ -40,6 +45,7 @@ use utoipa::OpenApi;
         health_check,
         blobs::get_blob_in_workspace,
         blobs::upload_blob_in_workspace,
+        blobs::gc_workspace_blobs,
         blobs::get_blob,
         blobs::upload_blob,
         blobs::create_workspace,
@@ -98,7 +104,10 @@ pub fn make_rest_route(ctx: Arc<Context>) -> Router {
                 )
                 .route("/workspace/:id/doc", get(get_doc))
                 .route("/workspace/:id/search", post(search_workspace))
+                .route("/search", post(search_user_workspaces))
+                .route("/workspace/:id/backlinks/:target", get(get_backlinks))
                 .route("/workspace/:id/blob", put(blobs::upload_blob_in_workspace))
+                .route("/workspace/:id/gc", post(blobs::gc_workspace_blobs))
                 .route("/permission/:id", delete(permissions::remove_user))
                 .layer(make_firebase_auth_layer(ctx.key.jwt_decode.clone())),
         )
@@ -546,8 +555,10 @@ pub async fn get_doc(
 }
 
 
-/// Get a exists `page` json by page id
-/// - Return `page` json.
+/// Get a exists `page` by page id
+/// - Return `page` json if request content type is json.
+/// - Return `page` html if request accepts `text/html`.
+/// - Otherwise return `page` markdown.
 #[utoipa::path(
     get,
     tag = "Workspace",
@@ -587,6 +598,20 @@ pub async fn get_public_page(
                 } else {
                     ErrorStatus::NotFound.into_response()
                 }
+            } else if headers
+                .get(ACCEPT)
+                .and_then(|c| c.to_str().ok())
+                .map(|s| s.contains("text/html"))
+                .unwrap_or(false)
+            {
+                if let Some(html) = workspace.with_trx(|t| {
+                    t.get_exists_space(page_id)
+                        .and_then(|page| page.to_html(&t.trx))
+                }) {
+                    Html(html).into_response()
+                } else {
+                    ErrorStatus::NotFound.into_response()
+                }
             } else if let Some(markdown) = workspace.with_trx(|t| {
                 t.get_exists_space(page_id)
                     .and_then(|page| page.to_markdown(&t.trx))
@@ -667,6 +692,8 @@ async fn get_workspace_doc(ctx: Arc<Context>, workspace_id: String) -> Response
     path = "/{workspace_id}/search",
     request_body(content = WorkspaceSearchInput, description = "Request body for search workspace",content_type = "application/json",example = json!({
         "query": "string",
+        "offset": 0,
+        "limit": 10,
     }
     )),
     params(
@@ -674,10 +701,15 @@ async fn get_workspace_doc(ctx: Arc<Context>, workspace_id: String) -> Response
     ),
     responses(
         (status = 200, description = "Workspace's data", body = SearchResults,
-        example=json!([{
-         "block_id": "xxxx",
-         "score": "f32",
-        }]
+        example=json!({
+         "total": 1,
+         "offset": 0,
+         "items": [{
+          "block_id": "xxxx",
+          "score": "f32",
+          "snippets": {"text": "matched <b>text</b>"},
+         }],
+        }
         )),
         (status = 400, description = "Request parameter error."),
         (status = 401, description = "Unauthorized."),
@@ -710,10 +742,182 @@ pub async fn search_workspace(
         }
     };
 
-    let search_results = match ctx.search_workspace(workspace_id, &payload.query).await {
+    let mut options = SearchOptions {
+        offset: payload.offset,
+        ..Default::default()
+    };
+    if let Some(limit) = payload.limit {
+        options.limit = limit;
+    }
+    if !options.in_bounds() {
+        return ErrorStatus::BadRequest.into_response();
+    }
+
+    let search_results = match ctx
+        .search_workspace(workspace_id, &payload.query, options)
+        .await
+    {
         Ok(results) => results,
         Err(err) => return err.to_string().into_response(),
     };
 
     Json(search_results).into_response()
 }
+
+/// search in all workspaces that the user can read
+/// - Return the matched blocks of all workspaces, sorted by score
+/// - Return 400 if `offset + limit` is beyond 100
+/// - Return 403 if the user cannot read any of the requested `workspaces`
+/// - Workspaces that cannot be searched are listed in `failed`
+#[utoipa::path(
+    post,
+    tag = "Workspace",
+    context_path = "/api",
+    path = "/search",
+    request_body(content = UserSearchInput, description = "Request body for search workspaces",content_type = "application/json",example = json!({
+        "query": "string",
+        "offset": 0,
+        "limit": 10,
+        "workspaces": ["xxxx"],
+    }
+    )),
+    responses(
+        (status = 200, description = "Matched blocks", body = UserSearchResults,
+        example=json!({
+         "total": 1,
+         "offset": 0,
+         "items": [{
+          "workspace_id": "xxxx",
+          "space_id": "xxxx",
+          "block_id": "xxxx",
+          "score": "f32",
+          "snippets": {"text": "matched <b>text</b>"},
+         }],
+         "failed": [],
+        }
+        )),
+        (status = 400, description = "Request parameter error."),
+        (status = 401, description = "Unauthorized."),
+        (status = 403, description = "Sorry, you do not have permission."),
+        (status = 500, description = "Server error, please try again later.")
+    )
+)]
+#[instrument(
+    skip(ctx, claims),
+    fields(
+        user_id = %claims.user.id,
+    )
+)]
+pub async fn search_user_workspaces(
+    Extension(ctx): Extension<Arc<Context>>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<UserSearchInput>,
+) -> Response {
+    info!("search_user_workspaces enter");
+    let requested = payload.workspaces.is_some();
+    let workspace_ids = match payload.workspaces {
+        Some(workspace_ids) => workspace_ids,
+        None => match ctx.db.get_user_workspaces(claims.user.id.clone()).await {
+            Ok(workspaces) => workspaces.into_iter().map(|ws| ws.id).collect(),
+            Err(e) => {
+                error!("Failed to get workspaces: {:?}", e);
+                return ErrorStatus::InternalServerError.into_response();
+            }
+        },
+    };
+
+    let mut readable = Vec::with_capacity(workspace_ids.len());
+    for workspace_id in workspace_ids {
+        match ctx
+            .db
+            .can_read_workspace(claims.user.id.clone(), workspace_id.clone())
+            .await
+        {
+            Ok(true) => readable.push(workspace_id),
+            Ok(false) if requested => return ErrorStatus::Forbidden.into_response(),
+            Ok(false) => (),
+            Err(e) => {
+                error!("Failed to get permission: {:?}", e);
+                return ErrorStatus::InternalServerError.into_response();
+            }
+        }
+    }
+
+    match ctx
+        .search_workspaces(
+            readable,
+            &payload.query,
+            payload.offset,
+            payload.limit.unwrap_or(SearchOptions::default().limit),
+        )
+        .await
+    {
+        Some(search_results) => Json(search_results).into_response(),
+        None => ErrorStatus::BadRequest.into_response(),
+    }
+}
+
+/// Get the blocks referring to a block, page or blob of the workspace
+/// - Return 200 ok and the references, empty if nothing refers to the target.
+/// - Return 403 Forbidden if you do not have permission.
+/// - Return 404 Not Found if `Workspace` is not exists.
+/// - Return 500 Internal Server Error if database error.
+#[utoipa::path(
+    get,
+    tag = "Workspace",
+    context_path = "/api/workspace",
+    path = "/{workspace_id}/backlinks/{target}",
+    params(
+        ("workspace_id", description = "workspace id"),
+        ("target", description = "id of the referenced block, page or blob"),
+    ),
+    responses(
+        (status = 200, description = "Blocks referring to the target", body = [Reference],
+        example=json!([{
+         "space_id": "xxxx",
+         "block_id": "xxxx",
+         "kind": "link",
+         "prop": "text",
+         "target": "xxxx",
+        }]
+        )),
+        (status = 401, description = "Unauthorized."),
+        (status = 403, description = "Sorry, you do not have permission."),
+        (status = 404, description = "Workspace not found."),
+        (status = 500, description = "Server error, please try again later.")
+    )
+)]
+#[instrument(
+    skip(ctx, claims),
+    fields(
+        user_id = %claims.user.id,
+    )
+)]
+pub async fn get_backlinks(
+    Extension(ctx): Extension<Arc<Context>>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path((workspace_id, target)): Path<(String, String)>,
+) -> Response {
+    info!("get_backlinks enter");
+    match ctx
+        .db
+        .can_read_workspace(claims.user.id.clone(), workspace_id.clone())
+        .await
+    {
+        Ok(true) => (),
+        Ok(false) => return ErrorStatus::Forbidden.into_response(),
+        Err(e) => {
+            error!("Failed to get permission: {:?}", e);
+            return ErrorStatus::InternalServerError.into_response();
+        }
+    }
+
+    match ctx.storage.get_workspace(workspace_id).await {
+        Ok(workspace) => Json(workspace.backlinks(target)).into_response(),
+        Err(JwstError::WorkspaceNotFound(_)) => ErrorStatus::NotFound.into_response(),
+        Err(e) => {
+            error!("Failed to get workspace: {:?}", e);
+            ErrorStatus::InternalServerError.into_response()
+        }
+    }
+}
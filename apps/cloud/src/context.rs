@@ -1,11 +1,12 @@
 use cloud_components::{FirebaseContext, KeyContext, MailContext};
-use cloud_database::CloudDatabase;
-use jwst::SearchResults;
+use cloud_database::{CloudDatabase, UserSearchResult, UserSearchResults};
+use futures::future::join_all;
+use jwst::{SearchOptions, SearchResults};
 use jwst_logger::{error, warn};
 use jwst_rpc::{BroadcastChannels, BroadcastType, RpcContextImpl};
-use jwst_storage::JwstStorage;
-use std::collections::HashMap;
-use tokio::sync::{Mutex, RwLock};
+use jwst_storage::{BlobGcOptions, BlobStorageType, JwstStorage, S3Options};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
 
 use crate::api::UserChannel;
 
@@ -17,8 +18,17 @@ pub struct Context {
     pub storage: JwstStorage,
     pub user_channel: UserChannel,
     pub channel: BroadcastChannels,
+    pub blob_gc: BlobGcOptions,
+    /// Total size in bytes the blobs of a workspace may use, unlimited if not set
+    pub blob_quota: Option<u64>,
+    /// Size in bytes of the largest blob that can be uploaded
+    pub blob_upload_limit: u64,
+    blob_upload_locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
 }
 
+/// Blobs are streamed into the storage, so the limit is not bound by memory
+const DEFAULT_BLOB_UPLOAD_LIMIT: u64 = 100 * 1024 * 1024;
+
 impl Context {
     pub async fn new() -> Context {
         let database_url = dotenvy::var("DATABASE_URL");
@@ -36,11 +46,23 @@ impl Context {
             )
             .await
             .expect("Cannot create cloud database"),
-            storage: JwstStorage::new(
+            storage: JwstStorage::new_with_options(
                 database_url
                     .map(|db| format!("{db}_binary"))
                     .as_deref()
                     .unwrap_or("sqlite::memory:?cache=shared"),
+                dotenvy::var("SEARCH_INDEX_DIR").ok().map(PathBuf::from),
+                dotenvy::var("BLOB_S3_BUCKET")
+                    .ok()
+                    .filter(|bucket| !bucket.is_empty())
+                    .map(|bucket| BlobStorageType::S3(s3_options(bucket)))
+                    .or_else(|| {
+                        dotenvy::var("BLOB_STORAGE_DIR")
+                            .ok()
+                            .filter(|dir| !dir.is_empty())
+                            .map(|dir| BlobStorageType::Local(PathBuf::from(dir)))
+                    })
+                    .unwrap_or_default(),
             )
             .await
             .expect("Cannot create storage"),
@@ -61,19 +83,48 @@ impl Context {
             // =========== sync channel ===========
             channel: RwLock::new(HashMap::new()),
             user_channel: UserChannel::new(),
+            // =========== blob gc ===========
+            blob_gc: BlobGcOptions {
+                grace_period: dotenvy::var("BLOB_GC_GRACE_PERIOD")
+                    .ok()
+                    .and_then(|secs| secs.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(BlobGcOptions::default().grace_period),
+                dry_run: false,
+            },
+            blob_quota: dotenvy::var("BLOB_WORKSPACE_QUOTA")
+                .ok()
+                .and_then(|bytes| bytes.parse().ok()),
+            blob_upload_limit: dotenvy::var("BLOB_UPLOAD_LIMIT")
+                .ok()
+                .and_then(|bytes| bytes.parse().ok())
+                .unwrap_or(DEFAULT_BLOB_UPLOAD_LIMIT),
+            blob_upload_locks: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Serialize the blob uploads to the workspace, the uploads wait until the guard is dropped.
+    pub async fn lock_blob_uploads(&self, workspace_id: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.blob_upload_locks.lock().await;
+            // drop the locks nobody holds or waits for
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(workspace_id.into()).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+
     pub async fn search_workspace(
         &self,
         workspace_id: String,
         query_string: &str,
+        options: SearchOptions,
     ) -> Result<SearchResults, Box<dyn std::error::Error>> {
         let workspace_id = workspace_id.to_string();
 
         match self.storage.get_workspace(workspace_id.clone()).await {
             Ok(workspace) => {
-                let search_results = workspace.search(query_string)?;
+                let search_results = workspace.search_with_options(query_string, options)?;
                 Ok(search_results)
             }
             Err(e) => {
@@ -83,6 +134,55 @@ impl Context {
         }
     }
 
+    /// Search the workspaces at once and merge the results by score.
+    ///
+    /// The first `offset + limit` results of each workspace are merged,
+    /// so the page is the same as if the blocks of all workspaces were searched at once.
+    /// Returns None if `offset + limit` is beyond [SearchOptions::MAX_LIMIT].
+    pub async fn search_workspaces(
+        &self,
+        workspace_ids: Vec<String>,
+        query_string: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Option<UserSearchResults> {
+        let options = SearchOptions {
+            limit: offset
+                .checked_add(limit)
+                .filter(|window| *window <= SearchOptions::MAX_LIMIT)?,
+            ..Default::default()
+        };
+
+        let searches = workspace_ids.into_iter().map(|workspace_id| {
+            let options = options.clone();
+            async move {
+                let results = self
+                    .search_workspace(workspace_id.clone(), query_string, options)
+                    .await
+                    .map_err(|e| e.to_string());
+                (workspace_id, results)
+            }
+        });
+
+        let mut failed = vec![];
+        let searched = join_all(searches)
+            .await
+            .into_iter()
+            .filter_map(|(workspace_id, results)| match results {
+                Ok(results) => Some((workspace_id, results)),
+                Err(e) => {
+                    error!("cannot search workspace {}: {}", workspace_id, e);
+                    failed.push(workspace_id);
+                    None
+                }
+            })
+            .collect();
+
+        let mut results = merge_search_results(searched, offset, limit);
+        results.failed = failed;
+        Some(results)
+    }
+
     // TODO: this should be moved to another module
     pub async fn close_websocket(&self, workspace: String, user: String) {
         let mut closed = vec![];
@@ -115,6 +215,63 @@ impl Context {
     }
 }
 
+/// Merge the results of the workspaces by score and take the page,
+/// results with the same score keep the order of the workspaces.
+fn merge_search_results(
+    searched: Vec<(String, SearchResults)>,
+    offset: usize,
+    limit: usize,
+) -> UserSearchResults {
+    let mut results = UserSearchResults {
+        offset,
+        ..Default::default()
+    };
+
+    for (workspace_id, workspace_results) in searched {
+        results.total += workspace_results.total;
+        results
+            .items
+            .extend(workspace_results.items.into_iter().map(|item| {
+                let prefix = format!("{}:", item.space_id);
+                let block_id = match item.block_id.strip_prefix(&prefix) {
+                    Some(block_id) => block_id.to_owned(),
+                    None => item.block_id.clone(),
+                };
+                UserSearchResult {
+                    workspace_id: workspace_id.clone(),
+                    space_id: item.space_id,
+                    block_id,
+                    score: item.score,
+                    snippets: item.snippets,
+                }
+            }));
+    }
+
+    results.items.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results.items = results.items.into_iter().skip(offset).take(limit).collect();
+
+    results
+}
+
+/// The object storage of the blobs, configured by the `BLOB_S3_*` variables
+fn s3_options(bucket: String) -> S3Options {
+    let var = |name: &str, default: &str| {
+        dotenvy::var(name)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| default.into())
+    };
+    S3Options {
+        endpoint: var("BLOB_S3_ENDPOINT", "https://s3.amazonaws.com"),
+        bucket,
+        region: var("BLOB_S3_REGION", "us-east-1"),
+        access_key: var("BLOB_S3_ACCESS_KEY", ""),
+        secret_key: var("BLOB_S3_SECRET_KEY", ""),
+        prefix: var("BLOB_S3_PREFIX", ""),
+        path_style: var("BLOB_S3_PATH_STYLE", "false") == "true",
+    }
+}
+
 impl RpcContextImpl<'_> for Context {
     fn get_storage(&self) -> &JwstStorage {
         &self.storage
@@ -124,3 +281,52 @@ impl RpcContextImpl<'_> for Context {
         &self.channel
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jwst::SearchResult;
+
+    fn workspace_results(total: usize, items: &[(&str, f32)]) -> SearchResults {
+        SearchResults {
+            total,
+            offset: 0,
+            items: items
+                .iter()
+                .map(|(block_id, score)| SearchResult {
+                    block_id: format!("space:{block_id}"),
+                    space_id: "space".into(),
+                    score: *score,
+                    snippets: HashMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn merge_search_results_by_score() {
+        let searched = vec![
+            (
+                "ws1".to_string(),
+                workspace_results(3, &[("a", 3.0), ("b", 1.0), ("c", 0.5)]),
+            ),
+            (
+                "ws2".to_string(),
+                workspace_results(2, &[("d", 2.0), ("e", 1.0)]),
+            ),
+        ];
+
+        let results = merge_search_results(searched, 1, 3);
+        assert_eq!(results.total, 5);
+        assert_eq!(results.offset, 1);
+        assert_eq!(
+            results
+                .items
+                .iter()
+                .map(|item| (item.workspace_id.as_str(), item.block_id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("ws2", "d"), ("ws1", "b"), ("ws2", "e")]
+        );
+        assert_eq!(results.items[0].space_id, "space");
+    }
+}
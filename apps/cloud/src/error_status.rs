@@ -13,6 +13,7 @@ pub enum ErrorStatus {
     NotFoundInvitation,
     InternalServerError,
     PayloadTooLarge,
+    StorageQuotaExceeded,
     BadRequest,
     Forbidden,
     Unauthorized,
@@ -58,7 +59,11 @@ impl IntoResponse for ErrorStatus {
             ),
             ErrorStatus::PayloadTooLarge => error_response(
                 StatusCode::PAYLOAD_TOO_LARGE,
-                "Upload file size exceeds 10MB",
+                "Upload file size exceeds the limit.",
+            ),
+            ErrorStatus::StorageQuotaExceeded => error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Workspace storage quota exceeded.",
             ),
             ErrorStatus::BadRequest => {
                 error_response(StatusCode::BAD_REQUEST, "Request parameter error.")
@@ -7,7 +7,7 @@ mod utils;
 
 use axum::{http::Method, Extension, Router, Server};
 use jwst_logger::{error, info, info_span, init_logger};
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tower_http::cors::{Any, CorsLayer};
 
 #[global_allocator]
@@ -35,6 +35,22 @@ async fn main() {
 
     let context = Arc::new(context::Context::new().await);
 
+    // collect the unreferenced blobs of all workspaces periodically, in seconds
+    if let Some(interval) = dotenvy::var("BLOB_GC_INTERVAL")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+    {
+        info!("gc blobs every {} seconds", interval);
+        let context = context.clone();
+        tokio::spawn(async move {
+            context
+                .storage
+                .gc_blobs_every(Duration::from_secs(interval), context.blob_gc)
+                .await
+        });
+    }
+
     let app = layer::make_tracing_layer(files::static_files(
         Router::new()
             .nest(
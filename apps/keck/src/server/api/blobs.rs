@@ -1,9 +1,19 @@
 use super::*;
 
-use axum::{body::StreamBody, extract::BodyStream, response::Response};
+use axum::{
+    body::StreamBody,
+    extract::{BodyStream, Query},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+        HeaderMap, HeaderValue,
+    },
+    response::Response,
+};
 use futures::{future, StreamExt};
-use jwst::BlobStorage;
-use utoipa::ToSchema;
+use jwst::{BlobStorage, JwstError};
+use jwst_storage::BlobGcOptions;
+use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Serialize, ToSchema)]
 struct BlobStatus {
@@ -11,6 +21,98 @@ struct BlobStatus {
     exists: bool,
 }
 
+#[derive(Deserialize, IntoParams)]
+pub struct GcQuery {
+    /// Only report the unreferenced blobs, nothing is deleted
+    #[serde(default)]
+    dry_run: bool,
+    /// Seconds a blob must stay unreferenced before it is deleted
+    grace_period: Option<u64>,
+}
+
+impl GcQuery {
+    fn options(&self, default: BlobGcOptions) -> BlobGcOptions {
+        BlobGcOptions {
+            grace_period: self
+                .grace_period
+                .map(Duration::from_secs)
+                .unwrap_or(default.grace_period),
+            dry_run: self.dry_run,
+        }
+    }
+}
+
+/// List the `Blob`s of a `Workspace`, the most recently uploaded first
+/// - Return 200 and the id, size and upload time of the `Blob`s.
+#[utoipa::path(
+    get,
+    tag = "Blobs",
+    context_path = "/api/blobs",
+    path = "/{workspace}",
+    params(
+        ("workspace", description = "workspace id"),
+        Pagination
+    ),
+    responses(
+        (status = 200, description = "List blobs", body = PageData<[BlobInfo]>),
+        (status = 500, description = "Failed to query blobs"),
+    )
+)]
+pub async fn list_blobs(
+    Extension(context): Extension<Arc<Context>>,
+    Path(workspace): Path<String>,
+    Query(pagination): Query<Pagination>,
+) -> Response {
+    let Pagination { offset, limit } = pagination;
+    info!("list_blobs: {}", workspace);
+    let blobs = context.storage.blobs();
+    match (
+        blobs.get_usage(Some(workspace.clone())).await,
+        blobs
+            .list_blobs(Some(workspace), offset as u64, limit as u64)
+            .await,
+    ) {
+        (Ok(usage), Ok(data)) => Json(PageData {
+            total: usage.count as usize,
+            data,
+        })
+        .into_response(),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("failed to list blobs: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Get the storage used by the `Blob`s of a `Workspace`
+/// - Return 200 and the number and total size of the `Blob`s.
+#[utoipa::path(
+    get,
+    tag = "Blobs",
+    context_path = "/api/usage",
+    path = "/{workspace}",
+    params(
+        ("workspace", description = "workspace id"),
+    ),
+    responses(
+        (status = 200, description = "Blob usage", body = BlobUsage),
+        (status = 500, description = "Failed to query blobs"),
+    )
+)]
+pub async fn get_blob_usage(
+    Extension(context): Extension<Arc<Context>>,
+    Path(workspace): Path<String>,
+) -> Response {
+    info!("get_blob_usage: {}", workspace);
+    match context.storage.blobs().get_usage(Some(workspace)).await {
+        Ok(usage) => Json(usage).into_response(),
+        Err(e) => {
+            error!("failed to get blob usage: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
 /// Check a `Blob` is exists by id
 /// - Return 200 if `Blob` is exists.
 /// - Return 404 Not Found if `Workspace` or `Blob` not exists.
@@ -75,16 +177,26 @@ pub async fn get_blob(
 ) -> Response {
     let (workspace, hash) = params;
     info!("get_blob: {}, {}", workspace, hash);
-    if let Ok(blob) = context
-        .storage
-        .blobs()
-        .get_blob(Some(workspace), hash)
-        .await
-    {
-        StreamBody::new(blob).into_response()
-    } else {
-        StatusCode::NOT_FOUND.into_response()
+    let blobs = context.storage.blobs();
+    let (Ok(metadata), Ok(blob)) = (
+        blobs
+            .get_metadata(Some(workspace.clone()), hash.clone())
+            .await,
+        blobs.get_blob(Some(workspace), hash.clone()).await,
+    ) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut header = HeaderMap::with_capacity(3);
+    if let Ok(content_type) = HeaderValue::from_str(&metadata.content_type) {
+        header.insert(CONTENT_TYPE, content_type);
+    }
+    if let Ok(disposition) = HeaderValue::from_str(&metadata.content_disposition(&hash)) {
+        header.insert(CONTENT_DISPOSITION, disposition);
     }
+    header.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+
+    (header, StreamBody::new(blob)).into_response()
 }
 
 /// Save `Blob` if not exists
@@ -110,10 +222,15 @@ pub async fn get_blob(
 pub async fn set_blob(
     Extension(context): Extension<Arc<Context>>,
     Path(params): Path<(String, String)>,
+    headers: HeaderMap,
     body: BodyStream,
 ) -> Response {
     let (workspace, hash) = params;
     info!("set_blob: {}, {}", workspace, hash);
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
 
     let mut has_error = false;
     let body = body
@@ -126,7 +243,7 @@ pub async fn set_blob(
     if let Ok(id) = context
         .storage
         .blobs()
-        .put_blob(Some(workspace.clone()), body)
+        .put_blob(Some(workspace.clone()), body, content_type)
         .await
     {
         if has_error {
@@ -192,12 +309,93 @@ pub async fn delete_blob(
     }
 }
 
-pub fn blobs_apis(router: Router) -> Router {
-    router.route(
-        "/blobs/:workspace/:blob",
-        head(check_blob)
-            .get(get_blob)
-            .post(set_blob)
-            .delete(delete_blob),
+/// Delete the `Blob`s that no block of the `Workspace` refers to any more
+/// - Return 200 and the report, blobs are only deleted once they stayed
+///   unreferenced for the grace period.
+/// - Return 404 Not Found if `Workspace` not exists.
+#[utoipa::path(
+    post,
+    tag = "Blobs",
+    context_path = "/api/gc",
+    path = "/{workspace}",
+    params(
+        ("workspace", description = "workspace id"),
+        GcQuery,
+    ),
+    responses(
+        (status = 200, description = "Blobs were collected", body = BlobGcReport),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Failed to collect blobs"),
+    )
+)]
+pub async fn gc_blobs(
+    Extension(context): Extension<Arc<Context>>,
+    Path(workspace): Path<String>,
+    Query(query): Query<GcQuery>,
+) -> Response {
+    info!("gc_blobs: {}, dry run: {}", workspace, query.dry_run);
+
+    match context
+        .storage
+        .gc_blobs(&workspace, query.options(context.blob_gc))
+        .await
+    {
+        Ok(report) => Json(report).into_response(),
+        Err(JwstError::WorkspaceNotFound(_)) => (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({workspace:?}) not found"),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("failed to gc blobs of {}: {}", workspace, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Delete the unreferenced `Blob`s of all `Workspace`s
+/// - Return 200 and the report of each workspace that has blobs.
+#[utoipa::path(
+    post,
+    tag = "Blobs",
+    context_path = "/api",
+    path = "/gc",
+    params(GcQuery),
+    responses(
+        (status = 200, description = "Blobs were collected", body = [BlobGcReport]),
+        (status = 500, description = "Failed to collect blobs"),
     )
+)]
+pub async fn gc_all_blobs(
+    Extension(context): Extension<Arc<Context>>,
+    Query(query): Query<GcQuery>,
+) -> Response {
+    info!("gc_all_blobs: dry run: {}", query.dry_run);
+
+    match context
+        .storage
+        .gc_all_blobs(query.options(context.blob_gc))
+        .await
+    {
+        Ok(reports) => Json(reports).into_response(),
+        Err(e) => {
+            error!("failed to gc blobs: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub fn blobs_apis(router: Router) -> Router {
+    router
+        .route("/blobs/:workspace", get(list_blobs))
+        .route(
+            "/blobs/:workspace/:blob",
+            head(check_blob)
+                .get(get_blob)
+                .post(set_blob)
+                .delete(delete_blob),
+        )
+        .route("/usage/:workspace", get(get_blob_usage))
+        .route("/gc", post(gc_all_blobs))
+        .route("/gc/:workspace", post(gc_blobs))
 }
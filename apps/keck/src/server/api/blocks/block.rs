@@ -1,6 +1,8 @@
 use super::*;
 use axum::{extract::Query, response::Response};
-use jwst::DocStorage;
+use jwst::{
+    BlockIdStrategy, DocStorage, FlavourSchema, JwstError, SchemaRegistry, WorkspaceTransaction,
+};
 use lib0::any::Any;
 use serde_json::Value as JsonValue;
 
@@ -38,9 +40,85 @@ pub async fn get_block(
     }
 }
 
+/// Convert the json props of a request, the flavour is skipped because it is not a prop.
+fn parse_props(payload: &JsonValue) -> Result<Vec<(String, Any)>, Response> {
+    let mut props = vec![];
+    if let Some(block_content) = payload.as_object() {
+        for (key, value) in block_content.iter() {
+            if key == "flavour" {
+                continue;
+            }
+            match serde_json::from_value::<Any>(value.clone()) {
+                Ok(value) => props.push((key.clone(), value)),
+                Err(e) => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("value at {key} cannot be stored: {e}"),
+                    )
+                        .into_response())
+                }
+            }
+        }
+    }
+
+    Ok(props)
+}
+
+/// Set the props of a block after checking them against the schema of its flavour.
+/// A missing block is created with the given flavour and the default props of the flavour,
+/// an existing block only has the props of the request checked.
+fn set_props(
+    schemas: &SchemaRegistry,
+    t: &mut WorkspaceTransaction,
+    block_id: &str,
+    flavour: &str,
+    props: Vec<(String, Any)>,
+) -> Result<jwst::Block, Response> {
+    let blocks = t.get_blocks();
+
+    // validate before touching the block, so that a bad request changes nothing
+    let (flavour, props, checked) = match blocks.get(&t.trx, block_id) {
+        Some(block) => {
+            let flavour = block.flavor(&t.trx);
+            let checked = schemas.validate_update(&flavour, &props.iter().cloned().collect());
+            (flavour, props, checked)
+        }
+        None => {
+            let mut content = schemas
+                .get(flavour)
+                .map(|schema| schema.defaults())
+                .unwrap_or_default();
+            for (key, value) in props {
+                if matches!(value, Any::Null | Any::Undefined) {
+                    content.remove(&key);
+                } else {
+                    content.insert(key, value);
+                }
+            }
+            let checked = schemas.validate(flavour, &content);
+            (flavour.to_owned(), content.into_iter().collect(), checked)
+        }
+    };
+    if let Err(violations) = checked {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(violations)).into_response());
+    }
+
+    let block = blocks.create(&mut t.trx, block_id, flavour);
+    for (key, value) in props {
+        if let Err(e) = block.try_set(&mut t.trx, &key, value) {
+            return Err((StatusCode::BAD_REQUEST, e.to_string()).into_response());
+        }
+    }
+
+    Ok(block)
+}
+
 /// Create or set `Block` with id
 /// - Return 200 and `Block`'s data if `Block`'s content set successful.
+/// - Return 400 Bad Request if some value of the content cannot be stored.
 /// - Return 404 Not Found if `Workspace` not exists.
+/// - Return 422 Unprocessable Entity and the violations if the content does not match the
+///   schema of the `Block`'s flavour.
 #[utoipa::path(
     post,
     tag = "Blocks",
@@ -57,7 +135,9 @@ pub async fn get_block(
     ),
     responses(
         (status = 200, description = "Block created and content was set", body = Block),
+        (status = 400, description = "Block content cannot be stored"),
         (status = 404, description = "Workspace not found"),
+        (status = 422, description = "Block content does not match the schema", body = [SchemaViolation]),
     )
 )]
 pub async fn set_block(
@@ -68,30 +148,31 @@ pub async fn set_block(
     let (ws_id, block_id) = params;
     info!("set_block: {}, {}", ws_id, block_id);
     if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
+        let props = match parse_props(&payload) {
+            Ok(props) => props,
+            Err(response) => return response,
+        };
+
         let mut update = None;
+        let schemas = context.schemas.read().await;
 
         // set block content
-        let block = workspace.with_trx(|mut t| {
-            let block = t.get_blocks().create(&mut t.trx, &block_id, "text");
-
-            // set block content
-            if let Some(block_content) = payload.as_object() {
-                let mut changed = false;
-                for (key, value) in block_content.iter() {
-                    changed = true;
-                    if let Ok(value) = serde_json::from_value::<Any>(value.clone()) {
-                        block.set(&mut t.trx, key, value);
-                    }
-                }
+        let result = workspace.with_trx(|mut t| {
+            let changed = !props.is_empty();
+            let block = set_props(&schemas, &mut t, &block_id, "text", props)?;
 
-                if changed {
-                    update = Some(t.trx.encode_update_v1());
-                }
+            if changed {
+                update = Some(t.trx.encode_update_v1());
             }
 
-            block
+            Ok::<_, Response>(block)
         });
 
+        let block = match result {
+            Ok(block) => block,
+            Err(response) => return response,
+        };
+
         if let Some(update) = update {
             if let Err(e) = context.storage.docs().write_update(ws_id, &update).await {
                 error!("db write error: {}", e.to_string());
@@ -107,7 +188,10 @@ pub async fn set_block(
 
 /// Create or set `Block` with id and flavour
 /// - Return 200 and `Block`'s data if `Block`'s created successful.
+/// - Return 400 Bad Request if some value of the content cannot be stored.
 /// - Return 404 Not Found if `Workspace` not exists.
+/// - Return 422 Unprocessable Entity and the violations if the content does not match the
+///   schema of the flavour.
 #[utoipa::path(
     patch,
     tag = "Blocks",
@@ -124,7 +208,9 @@ pub async fn set_block(
     ),
     responses(
         (status = 200, description = "Block created", body = Block),
+        (status = 400, description = "Block content cannot be stored"),
         (status = 404, description = "Workspace not found"),
+        (status = 422, description = "Block content does not match the schema", body = [SchemaViolation]),
     )
 )]
 pub async fn set_block_with_flavour(
@@ -154,13 +240,25 @@ pub async fn set_block_with_flavour(
         ws_id, block_id, flavour
     );
 
+    let props = match parse_props(&payload) {
+        Ok(props) => props,
+        Err(response) => return response,
+    };
+
     let mut update = None;
     if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
-        let block = workspace.with_trx(|mut t| {
-            let created_block = t.get_blocks().create(&mut t.trx, block_id, flavour);
+        let schemas = context.schemas.read().await;
+        let result = workspace.with_trx(|mut t| {
+            let created_block = set_props(&schemas, &mut t, &block_id, flavour, props)?;
             update = Some(t.trx.encode_update_v1());
-            created_block
+            Ok::<_, Response>(created_block)
         });
+
+        let block = match result {
+            Ok(block) => block,
+            Err(response) => return response,
+        };
+
         if let Some(update) = update {
             if let Err(e) = context.storage.docs().write_update(ws_id, &update).await {
                 error!("db write error: {}", e.to_string());
@@ -177,6 +275,55 @@ pub async fn set_block_with_flavour(
     }
 }
 
+/// Get the schemas of all block flavours
+/// - Return 200 and the schemas sorted by flavour.
+#[utoipa::path(
+    get,
+    tag = "Blocks",
+    context_path = "/api",
+    path = "/schema",
+    responses(
+        (status = 200, description = "Get the schemas of block flavours", body = [FlavourSchema]),
+    )
+)]
+pub async fn get_block_schemas(Extension(context): Extension<Arc<Context>>) -> Response {
+    Json(context.schemas.read().await.schemas()).into_response()
+}
+
+/// Register the schema of a block flavour
+/// - Return 200 and the schema, a schema with the same flavour is replaced.
+///
+/// Registered schemas are only kept in memory and apply to every workspace of the server,
+/// they are lost on restart and have to be registered again.
+/// - Return 400 Bad Request if the flavour is empty.
+#[utoipa::path(
+    put,
+    tag = "Blocks",
+    context_path = "/api",
+    path = "/schema",
+    request_body(
+        content = FlavourSchema,
+        description = "Schema of the flavour",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Schema registered", body = FlavourSchema),
+        (status = 400, description = "Flavour is empty"),
+    )
+)]
+pub async fn set_block_schema(
+    Extension(context): Extension<Arc<Context>>,
+    Json(schema): Json<FlavourSchema>,
+) -> Response {
+    info!("set_block_schema: {}", schema.flavour);
+    if schema.flavour.is_empty() {
+        return (StatusCode::BAD_REQUEST, "flavour is empty").into_response();
+    }
+
+    context.schemas.write().await.register(schema.clone());
+    Json(schema).into_response()
+}
+
 /// Get exists `Blocks` in certain `Workspace` by flavour
 /// - Return 200 Ok and `Blocks`'s data if `Blocks` is exists.
 /// - Return 404 Not Found if `Workspace` not exists or 500 Internal Server Error when transaction init fails.
@@ -259,6 +406,40 @@ pub async fn get_block_history(
     }
 }
 
+/// Get the blocks referring to a `Block`, a page or a blob
+/// - Return 200 and the references if `Workspace` exists, the id does not need to be a `Block`.
+/// - Return 404 Not Found if `Workspace` not exists.
+#[utoipa::path(
+    get,
+    tag = "Blocks",
+    context_path = "/api/block",
+    path = "/{workspace}/{block}/backlinks",
+    params(
+        ("workspace", description = "workspace id"),
+        ("block", description = "id of the referenced block, page or blob"),
+    ),
+    responses(
+        (status = 200, description = "Get blocks referring to the target", body = [Reference]),
+        (status = 404, description = "Workspace not found"),
+    )
+)]
+pub async fn get_block_backlinks(
+    Extension(context): Extension<Arc<Context>>,
+    Path(params): Path<(String, String)>,
+) -> Response {
+    let (ws_id, block) = params;
+    info!("get_block_backlinks: {}, {}", ws_id, block);
+    if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
+        Json(workspace.backlinks(block)).into_response()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response()
+    }
+}
+
 /// Delete block
 /// - Return 204 No Content if delete successful.
 /// - Return 404 Not Found if `Workspace` or `Block` not exists.
@@ -487,3 +668,181 @@ pub async fn remove_block_children(
         StatusCode::NOT_FOUND.into_response()
     }
 }
+
+enum Transfer {
+    Clone(BlockIdStrategy),
+    Move,
+}
+
+/// Clone or move a block with its descendants into a space, then append it to a parent.
+async fn transfer_block(
+    context: Arc<Context>,
+    ws_id: String,
+    block_id: String,
+    (from_space, to_space): (String, String),
+    parent: Option<String>,
+    transfer: Transfer,
+) -> Response {
+    let Ok(workspace) = context.storage.get_workspace(&ws_id).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response();
+    };
+
+    let result = workspace.with_trx(|mut t| {
+        let source = t.get_space(&from_space);
+        let target = t.get_space(&to_space);
+        let Some(block) = source.get(&t.trx, &block_id) else {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Block({block_id:?}) not found in Space({from_space:?})"),
+            )
+                .into_response());
+        };
+        let parent = match parent {
+            Some(parent) => match target.get(&t.trx, &parent) {
+                Some(parent) => Some(parent),
+                None => {
+                    return Err((
+                        StatusCode::NOT_FOUND,
+                        format!("Block({parent:?}) not found in Space({to_space:?})"),
+                    )
+                        .into_response())
+                }
+            },
+            None => None,
+        };
+        // checked before any change, a block can't be moved into its own subtree
+        if let (Transfer::Move, Some(parent)) = (&transfer, &parent) {
+            let mut ancestor = (from_space == to_space).then(|| parent.block_id());
+            let mut visited = std::collections::HashSet::new();
+            while let Some(id) = ancestor.filter(|id| visited.insert(id.clone())) {
+                if id == block_id {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("Block({block_id:?}) can't be moved into its own subtree"),
+                    )
+                        .into_response());
+                }
+                ancestor = target.get(&t.trx, &id).and_then(|b| b.parent(&t.trx));
+            }
+        }
+
+        let block = match transfer {
+            Transfer::Clone(strategy) => block.clone_into(&mut t.trx, &target, strategy),
+            Transfer::Move => block.move_to(&mut t.trx, &target),
+        }
+        .map_err(|e| match e {
+            JwstError::BlockExists(..) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+            e => {
+                error!("failed to transfer block: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        })?;
+        if let Some(parent) = parent {
+            parent.push_children(&mut t.trx, &block);
+        }
+
+        Ok((block, t.trx.encode_update_v1()))
+    });
+
+    match result {
+        Ok((block, update)) => {
+            if let Err(e) = context.storage.docs().write_update(ws_id, &update).await {
+                error!("db write error: {}", e.to_string());
+            }
+            Json(block).into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+/// Copy a `Block` with its descendants
+/// - Return 200 and the copied `Block`'s data.
+/// - Return 404 Not Found if `Workspace`, `Block` or parent not exists.
+/// - Return 409 Conflict if a copied id already exists in the target space.
+#[utoipa::path(
+    post,
+    tag = "Blocks",
+    context_path = "/api/block",
+    path = "/{workspace}/{block}/clone",
+    params(
+        ("workspace", description = "workspace id"),
+        ("block", description = "block id"),
+    ),
+    request_body(
+        content = CloneBlock,
+        description = "json",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Block copied", body = Block),
+        (status = 404, description = "Workspace, block or parent not found"),
+        (status = 409, description = "Block id conflicts in the target space"),
+        (status = 500, description = "Failed to copy block")
+    )
+)]
+pub async fn clone_block(
+    Extension(context): Extension<Arc<Context>>,
+    Path(params): Path<(String, String)>,
+    Json(payload): Json<CloneBlock>,
+) -> Response {
+    let (ws_id, block) = params;
+    info!("clone_block: {}, {}", ws_id, block);
+    transfer_block(
+        context,
+        ws_id,
+        block,
+        (payload.from_space, payload.to_space),
+        payload.parent,
+        Transfer::Clone(payload.ids),
+    )
+    .await
+}
+
+/// Move a `Block` with its descendants to another space, the block ids are kept
+/// - Return 200 and the moved `Block`'s data.
+/// - Return 400 Bad Request if the parent is the `Block` or one of its descendants.
+/// - Return 404 Not Found if `Workspace`, `Block` or parent not exists.
+/// - Return 409 Conflict if a moved id already exists in the target space.
+#[utoipa::path(
+    post,
+    tag = "Blocks",
+    context_path = "/api/block",
+    path = "/{workspace}/{block}/move",
+    params(
+        ("workspace", description = "workspace id"),
+        ("block", description = "block id"),
+    ),
+    request_body(
+        content = MoveBlock,
+        description = "json",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Block moved", body = Block),
+        (status = 400, description = "Parent is in the subtree of the block"),
+        (status = 404, description = "Workspace, block or parent not found"),
+        (status = 409, description = "Block id conflicts in the target space"),
+        (status = 500, description = "Failed to move block")
+    )
+)]
+pub async fn move_block(
+    Extension(context): Extension<Arc<Context>>,
+    Path(params): Path<(String, String)>,
+    Json(payload): Json<MoveBlock>,
+) -> Response {
+    let (ws_id, block) = params;
+    info!("move_block: {}, {}", ws_id, block);
+    transfer_block(
+        context,
+        ws_id,
+        block,
+        (payload.from_space, payload.to_space),
+        payload.parent,
+        Transfer::Move,
+    )
+    .await
+}
@@ -3,17 +3,19 @@ mod schema;
 mod workspace;
 
 pub use block::{
-    delete_block, get_block, get_block_history, insert_block_children, remove_block_children,
-    set_block,
+    clone_block, delete_block, get_block, get_block_backlinks, get_block_history,
+    get_block_schemas, insert_block_children, move_block, remove_block_children, set_block,
+    set_block_schema,
 };
 pub use workspace::{
-    delete_workspace, get_workspace, history_workspace, history_workspace_clients, set_workspace,
+    create_snapshot, delete_workspace, diff_snapshot, get_workspace, history_workspace,
+    history_workspace_clients, import_markdown, list_snapshots, restore_snapshot, set_workspace,
     workspace_client,
 };
 
 use super::*;
 use jwst_static::with_api_doc_v2;
-use schema::InsertChildren;
+use schema::{CloneBlock, InsertChildren, MoveBlock, Snapshot};
 use utoipa::OpenApi;
 
 #[derive(OpenApi)]
@@ -26,23 +28,40 @@ use utoipa::OpenApi;
         workspace::history_workspace_clients,
         workspace::history_workspace,
         workspace::get_workspace_block,
+        workspace::import_markdown,
+        workspace::list_snapshots,
+        workspace::create_snapshot,
+        workspace::restore_snapshot,
+        workspace::diff_snapshot,
         workspace::workspace_search,
+        workspace::workspace_suggest,
+        workspace::get_search_analyzer,
+        workspace::set_search_analyzer,
         block::get_block,
         block::set_block_with_flavour,
         block::get_block_by_flavour,
         block::set_block,
         block::get_block_history,
+        block::get_block_backlinks,
         block::get_block_children,
         block::delete_block,
         block::insert_block_children,
         block::remove_block_children,
+        block::get_block_schemas,
+        block::set_block_schema,
+        block::clone_block,
+        block::move_block,
     ),
     components(
         schemas(
-            schema::InsertChildren,
+            schema::InsertChildren, schema::Snapshot, schema::CloneBlock, schema::MoveBlock,
             schema::Workspace, schema::Block, schema::BlockRawHistory,
             jwst::BlockHistory, jwst::HistoryOperation, jwst::RawHistory,
-            jwst::SearchResults, jwst::SearchResult
+            jwst::SearchResults, jwst::SearchResult, jwst::SearchOrder, jwst::SearchAnalyzer,
+            jwst::SearchMode, jwst::Suggestion, jwst::Reference, jwst::ReferenceKind,
+            jwst::FlavourSchema, jwst::PropSchema, jwst::PropType, jwst::SchemaViolation,
+            jwst::WorkspaceDiff, jwst::SpaceDiff, jwst::BlockSummary, jwst::BlockDiff,
+            jwst::PropChange, jwst::ChildrenChange, jwst::BlockIdStrategy
         )
     ),
     tags(
@@ -68,13 +87,20 @@ fn doc_apis(router: Router) -> Router {
 fn block_apis(router: Router) -> Router {
     let block_operation = Router::new()
         .route("/history", get(block::get_block_history))
+        .route("/backlinks", get(block::get_block_backlinks))
         .route(
             "/children",
             get(block::get_block_children).post(block::insert_block_children),
         )
-        .route("/children/:children", delete(block::remove_block_children));
+        .route("/children/:children", delete(block::remove_block_children))
+        .route("/clone", post(block::clone_block))
+        .route("/move", post(block::move_block));
 
     doc_apis(router)
+        .route(
+            "/schema",
+            get(block::get_block_schemas).put(block::set_block_schema),
+        )
         .nest("/block/:workspace/:block/", block_operation)
         .route(
             "/block/:workspace/:block",
@@ -110,11 +136,35 @@ fn workspace_apis(router: Router) -> Router {
             "/block/:workspace/blocks",
             get(workspace::get_workspace_block),
         )
+        .route(
+            "/block/:workspace/markdown/:space",
+            post(workspace::import_markdown),
+        )
+        .route(
+            "/block/:workspace/snapshot",
+            get(workspace::list_snapshots).post(workspace::create_snapshot),
+        )
+        .route(
+            "/block/:workspace/snapshot/:snapshot/restore",
+            post(workspace::restore_snapshot),
+        )
+        .route(
+            "/block/:workspace/snapshot/:snapshot/diff",
+            get(workspace::diff_snapshot),
+        )
         .route("/search/:workspace", get(workspace::workspace_search))
+        .route(
+            "/search/:workspace/suggest",
+            get(workspace::workspace_suggest),
+        )
         .route(
             "/search/:workspace/index",
             get(workspace::get_search_index).post(workspace::set_search_index),
         )
+        .route(
+            "/search/:workspace/analyzer",
+            get(workspace::get_search_analyzer).post(workspace::set_search_analyzer),
+        )
 }
 
 pub fn blocks_apis(router: Router) -> Router {
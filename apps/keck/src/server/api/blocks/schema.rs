@@ -1,6 +1,8 @@
 pub use std::collections::HashMap;
 
-use serde::Deserialize;
+use jwst::BlockIdStrategy;
+use jwst_storage::SnapshotMetadata;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 #[derive(Default, Deserialize, PartialEq, Debug, ToSchema)]
@@ -39,3 +41,54 @@ pub enum InsertChildren {
     InsertAfter { id: String, after: String },
     InsertAt { id: String, pos: u32 },
 }
+
+fn default_space() -> String {
+    "blocks".into()
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(example = json!({"to_space": "page1", "parent": "jwstRf4rMzua7E", "ids": "random"}))]
+pub struct CloneBlock {
+    /// Space of the block, `blocks` by default
+    #[serde(default = "default_space")]
+    pub(super) from_space: String,
+    /// Space to copy the block into, `blocks` by default
+    #[serde(default = "default_space")]
+    pub(super) to_space: String,
+    /// Block of the target space that the copy is appended to
+    pub(super) parent: Option<String>,
+    /// How the copied blocks get their ids, random ids by default
+    #[serde(default)]
+    pub(super) ids: BlockIdStrategy,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(example = json!({"to_space": "page1", "parent": "jwstRf4rMzua7E"}))]
+pub struct MoveBlock {
+    /// Space of the block, `blocks` by default
+    #[serde(default = "default_space")]
+    pub(super) from_space: String,
+    /// Space to move the block into
+    pub(super) to_space: String,
+    /// Block of the target space that the block is appended to
+    pub(super) parent: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(example = json!({"id": 1, "name": "before import", "timestamp": 946684800000_i64}))]
+pub struct Snapshot {
+    id: i32,
+    name: String,
+    /// Unix timestamp in milliseconds
+    timestamp: i64,
+}
+
+impl From<SnapshotMetadata> for Snapshot {
+    fn from(snapshot: SnapshotMetadata) -> Self {
+        Self {
+            id: snapshot.id,
+            name: snapshot.name,
+            timestamp: snapshot.timestamp.timestamp_millis(),
+        }
+    }
+}
@@ -4,7 +4,10 @@ use axum::{
     http::header,
     response::Response,
 };
-use jwst::{parse_history, parse_history_client, DocStorage};
+use jwst::{
+    parse_history, parse_history_client, DocStorage, SearchAnalyzer, SearchMode, SearchOptions,
+    SearchOrder, WorkspaceDiff,
+};
 use utoipa::IntoParams;
 
 /// Get a exists `Workspace` by id
@@ -138,8 +141,31 @@ pub async fn workspace_client(
 // See doc for using utoipa search queries example here: https://github.com/juhaku/utoipa/blob/6c7f6a2d/examples/todo-axum/src/main.rs#L124-L130
 #[derive(Deserialize, IntoParams)]
 pub struct BlockSearchQuery {
-    /// Search by title and text.
+    /// Search by title and text, match all blocks if empty.
+    #[serde(default)]
     query: String,
+    /// Number of results to skip, 0 by default and at most 10000.
+    #[serde(default)]
+    offset: usize,
+    /// Max number of results, 10 by default and at most 100.
+    limit: Option<usize>,
+    /// Only match the blocks of this flavour.
+    flavour: Option<String>,
+    /// Only match the blocks in this space.
+    space: Option<String>,
+    /// Only match the blocks created at or after this unix timestamp in milliseconds.
+    created_after: Option<u64>,
+    /// Only match the blocks created at or before this unix timestamp in milliseconds.
+    created_before: Option<u64>,
+    /// Only match the blocks updated at or after this unix timestamp in milliseconds.
+    updated_after: Option<u64>,
+    /// Only match the blocks updated at or before this unix timestamp in milliseconds.
+    updated_before: Option<u64>,
+    /// Sort by relevance or recency, relevance by default.
+    order: Option<SearchOrder>,
+    /// Read the query as tantivy query syntax or as plain words that tolerate typos,
+    /// syntax by default.
+    mode: Option<SearchMode>,
 }
 
 /// Search workspace blocks of server
@@ -156,17 +182,44 @@ pub struct BlockSearchQuery {
     ),
     responses(
         (status = 200, description = "Search results", body = SearchResults),
+        (status = 400, description = "Limit or offset is too large"),
     )
 )]
 pub async fn workspace_search(
     Extension(context): Extension<Arc<Context>>,
     Path(ws_id): Path<String>,
-    query: Query<BlockSearchQuery>,
+    Query(query): Query<BlockSearchQuery>,
 ) -> Response {
     let query_text = &query.query;
     info!("workspace_search: {ws_id:?} query = {query_text:?}");
+    let mut options = SearchOptions {
+        offset: query.offset,
+        flavour: query.flavour,
+        space_id: query.space,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        updated_after: query.updated_after,
+        updated_before: query.updated_before,
+        order: query.order.unwrap_or_default(),
+        mode: query.mode.unwrap_or_default(),
+        ..Default::default()
+    };
+    if let Some(limit) = query.limit {
+        options.limit = limit;
+    }
+    if !options.in_bounds() {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "limit must be at most {} and offset at most {}",
+                SearchOptions::MAX_LIMIT,
+                SearchOptions::MAX_OFFSET
+            ),
+        )
+            .into_response();
+    }
     if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
-        match workspace.search(query_text) {
+        match workspace.search_with_options(query_text, options) {
             Ok(list) => {
                 debug!("workspace_search: {ws_id:?} query = {query_text:?}; {list:#?}");
                 Json(list).into_response()
@@ -185,6 +238,57 @@ pub async fn workspace_search(
     }
 }
 
+/// Block title suggest query
+#[derive(Deserialize, IntoParams)]
+pub struct SuggestQuery {
+    /// The typed text, the last word is matched as a prefix.
+    #[serde(default)]
+    query: String,
+    /// Max number of suggestions, 5 by default.
+    limit: Option<usize>,
+}
+
+/// Suggest block titles of workspace as the user types
+///
+/// Nothing is suggested if `title` is not in the search index fields.
+#[utoipa::path(
+    get,
+    tag = "Workspace",
+    context_path = "/api/search",
+    path = "/{workspace}/suggest",
+    params(
+        ("workspace", description = "workspace id"),
+        SuggestQuery,
+    ),
+    responses(
+        (status = 200, description = "Suggested titles", body = [Suggestion]),
+        (status = 404, description = "Workspace not found")
+    )
+)]
+pub async fn workspace_suggest(
+    Extension(context): Extension<Arc<Context>>,
+    Path(ws_id): Path<String>,
+    Query(query): Query<SuggestQuery>,
+) -> Response {
+    let query_text = &query.query;
+    info!("workspace_suggest: {ws_id:?} query = {query_text:?}");
+    if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
+        match workspace.suggest(query_text, query.limit.unwrap_or(5)) {
+            Ok(list) => Json(list).into_response(),
+            Err(err) => {
+                error!("Internal server error calling workspace_suggest: {err:?}");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response()
+    }
+}
+
 #[utoipa::path(
     get,
     tag = "Workspace",
@@ -251,6 +355,367 @@ pub async fn set_search_index(
     }
 }
 
+#[utoipa::path(
+    get,
+    tag = "Workspace",
+    context_path = "/api/search",
+    path = "/{workspace}/analyzer",
+    params(
+        ("workspace", description = "workspace id"),
+    ),
+    responses(
+        (status = 200, description = "result", body = SearchAnalyzer),
+        (status = 404, description = "Workspace not found")
+    )
+)]
+pub async fn get_search_analyzer(
+    Extension(context): Extension<Arc<Context>>,
+    Path(ws_id): Path<String>,
+) -> Response {
+    info!("get_search_analyzer: {ws_id:?}");
+
+    if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
+        Json(workspace.metadata().search_analyzer).into_response()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response()
+    }
+}
+
+/// Change how the text of `Workspace` is tokenized for search
+/// - Return 200 Ok, the blocks are indexed again with the new analyzer on the next search.
+/// - Return 404 Not Found if `Workspace` not exists.
+#[utoipa::path(
+    post,
+    tag = "Workspace",
+    context_path = "/api/search",
+    path = "/{workspace}/analyzer",
+    params(
+        ("workspace", description = "workspace id"),
+    ),
+    request_body(
+        content = SearchAnalyzer,
+        description = "The analyzer, e.g. \"english\"",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "success"),
+        (status = 404, description = "Workspace not found")
+    )
+)]
+pub async fn set_search_analyzer(
+    Extension(context): Extension<Arc<Context>>,
+    Path(ws_id): Path<String>,
+    Json(analyzer): Json<SearchAnalyzer>,
+) -> Response {
+    info!("set_search_analyzer: {ws_id:?} analyzer = {analyzer:?}");
+
+    if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
+        workspace.set_search_analyzer(analyzer);
+        StatusCode::OK.into_response()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response()
+    }
+}
+
+/// Import markdown into a `Space` of `Workspace`
+///
+/// Headings, quotes, lists, todos, fenced code, dividers and images are converted to the
+/// corresponding blocks and appended to the page of the `Space`.
+/// - Return 200 Ok and the `Space`'s blocks if import successful.
+/// - Return 404 Not Found if `Workspace` not exists.
+#[utoipa::path(
+    post,
+    tag = "Workspace",
+    context_path = "/api/block",
+    path = "/{workspace}/markdown/{space}",
+    params(
+        ("workspace", description = "workspace id"),
+        ("space", description = "space id, will be created if not exists"),
+    ),
+    request_body(
+        content = String,
+        description = "markdown",
+        content_type = "text/markdown"
+    ),
+    responses(
+        (status = 200, description = "Markdown imported"),
+        (status = 404, description = "Workspace not found")
+    )
+)]
+pub async fn import_markdown(
+    Extension(context): Extension<Arc<Context>>,
+    Path(params): Path<(String, String)>,
+    markdown: String,
+) -> Response {
+    let (ws_id, space_id) = params;
+    info!("import_markdown: {}, {}", ws_id, space_id);
+    if let Ok(workspace) = context.storage.get_workspace(&ws_id).await {
+        let update = workspace.with_trx(|mut t| {
+            let space = t.get_space(&space_id);
+            space.from_markdown(&mut t.trx, &markdown);
+            t.trx.encode_update_v1()
+        });
+
+        if let Err(e) = context.storage.docs().write_update(ws_id, &update).await {
+            error!("db write error: {}", e.to_string());
+        }
+
+        match workspace.with_trx(|t| t.get_exists_space(&space_id)) {
+            Some(space) => Json(space).into_response(),
+            None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response()
+    }
+}
+
+/// Get the snapshots of `Workspace`
+/// - Return 200 Ok and the snapshots in creation order.
+/// - Return 404 Not Found if `Workspace` not exists.
+#[utoipa::path(
+    get,
+    tag = "Workspace",
+    context_path = "/api/block",
+    path = "/{workspace}/snapshot",
+    params(
+        ("workspace", description = "workspace id"),
+    ),
+    responses(
+        (status = 200, description = "Get snapshots", body = [Snapshot]),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Failed to list snapshots")
+    )
+)]
+pub async fn list_snapshots(
+    Extension(context): Extension<Arc<Context>>,
+    Path(ws_id): Path<String>,
+) -> Response {
+    info!("list_snapshots: {}", ws_id);
+    if let Err(e) = context.storage.get_workspace(&ws_id).await {
+        warn!("workspace {} not found: {}", ws_id, e);
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response();
+    }
+
+    match context.storage.docs().snapshots(ws_id).await {
+        Ok(snapshots) => Json(
+            snapshots
+                .into_iter()
+                .map(Snapshot::from)
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => {
+            error!("failed to list snapshots: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Create a snapshot of `Workspace`'s current state
+/// - Return 200 Ok and the created snapshot.
+/// - Return 404 Not Found if `Workspace` not exists.
+#[utoipa::path(
+    post,
+    tag = "Workspace",
+    context_path = "/api/block",
+    path = "/{workspace}/snapshot",
+    params(
+        ("workspace", description = "workspace id"),
+    ),
+    request_body(
+        content = String,
+        description = "snapshot name",
+        content_type = "text/plain"
+    ),
+    responses(
+        (status = 200, description = "Snapshot created", body = Snapshot),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Failed to create snapshot")
+    )
+)]
+pub async fn create_snapshot(
+    Extension(context): Extension<Arc<Context>>,
+    Path(ws_id): Path<String>,
+    name: String,
+) -> Response {
+    info!("create_snapshot: {}, {}", ws_id, name);
+    if let Err(e) = context.storage.get_workspace(&ws_id).await {
+        warn!("workspace {} not found: {}", ws_id, e);
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response();
+    }
+
+    match context.storage.docs().create_snapshot(ws_id, name).await {
+        Ok(snapshot) => Json(Snapshot::from(snapshot)).into_response(),
+        Err(e) => {
+            error!("failed to create snapshot: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Restore `Workspace` to a snapshot
+///
+/// The restore is written as a new update, connected clients receive it like any other change.
+/// - Return 200 Ok if restore successful.
+/// - Return 404 Not Found if `Workspace` or snapshot not exists.
+#[utoipa::path(
+    post,
+    tag = "Workspace",
+    context_path = "/api/block",
+    path = "/{workspace}/snapshot/{snapshot}/restore",
+    params(
+        ("workspace", description = "workspace id"),
+        ("snapshot", description = "snapshot id"),
+    ),
+    responses(
+        (status = 200, description = "Workspace restored"),
+        (status = 404, description = "Workspace or snapshot not found"),
+        (status = 500, description = "Failed to restore snapshot")
+    )
+)]
+pub async fn restore_snapshot(
+    Extension(context): Extension<Arc<Context>>,
+    Path(params): Path<(String, i32)>,
+) -> Response {
+    let (ws_id, snapshot) = params;
+    info!("restore_snapshot: {}, {}", ws_id, snapshot);
+    if let Err(e) = context.storage.get_workspace(&ws_id).await {
+        warn!("workspace {} not found: {}", ws_id, e);
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Workspace({ws_id:?}) not found"),
+        )
+            .into_response();
+    }
+
+    match context
+        .storage
+        .docs()
+        .restore_snapshot(ws_id.clone(), snapshot)
+        .await
+    {
+        Ok(true) => StatusCode::OK.into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            format!("Snapshot({snapshot}) of Workspace({ws_id:?}) not found"),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("failed to restore snapshot: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Block diff query
+#[derive(Deserialize, IntoParams)]
+pub struct SnapshotDiffQuery {
+    /// Snapshot id to compare with, the current state of workspace if not set.
+    to: Option<i32>,
+    /// Only return the changes of this space.
+    space: Option<String>,
+}
+
+/// Get the block changes of `Workspace` since a snapshot
+///
+/// Only snapshots can be diffed, the content deleted since a client state vector is garbage
+/// collected so the blocks at that state can't be rebuilt.
+/// - Return 200 Ok and the added, removed and changed blocks of each space.
+/// - Return 404 Not Found if `Workspace` or snapshot not exists.
+#[utoipa::path(
+    get,
+    tag = "Workspace",
+    context_path = "/api/block",
+    path = "/{workspace}/snapshot/{snapshot}/diff",
+    params(
+        ("workspace", description = "workspace id"),
+        ("snapshot", description = "snapshot id"),
+        SnapshotDiffQuery,
+    ),
+    responses(
+        (status = 200, description = "Block changes", body = WorkspaceDiff),
+        (status = 404, description = "Workspace or snapshot not found"),
+        (status = 500, description = "Failed to diff snapshot")
+    )
+)]
+pub async fn diff_snapshot(
+    Extension(context): Extension<Arc<Context>>,
+    Path(params): Path<(String, i32)>,
+    Query(query): Query<SnapshotDiffQuery>,
+) -> Response {
+    let (ws_id, snapshot) = params;
+    info!("diff_snapshot: {}, {}, {:?}", ws_id, snapshot, query.to);
+    let workspace = match context.storage.get_workspace(&ws_id).await {
+        Ok(workspace) => workspace,
+        Err(e) => {
+            warn!("workspace {} not found: {}", ws_id, e);
+            return (
+                StatusCode::NOT_FOUND,
+                format!("Workspace({ws_id:?}) not found"),
+            )
+                .into_response();
+        }
+    };
+
+    let docs = context.storage.docs();
+    let mut snapshots = vec![];
+    for id in std::iter::once(snapshot).chain(query.to) {
+        match docs.snapshot(ws_id.clone(), id).await {
+            Ok(Some(snapshot)) => snapshots.push(snapshot),
+            Ok(None) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    format!("Snapshot({id}) of Workspace({ws_id:?}) not found"),
+                )
+                    .into_response()
+            }
+            Err(e) => {
+                error!("failed to get snapshot: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+
+    let diff = match snapshots.as_slice() {
+        [from, to] => from.diff(to),
+        [from] => workspace.diff_blocks(from),
+        _ => unreachable!(),
+    };
+
+    match diff {
+        Ok(mut diff) => {
+            if let Some(space) = query.space {
+                diff.spaces.retain(|diff| diff.space_id == space);
+            }
+            Json(diff).into_response()
+        }
+        Err(e) => {
+            error!("failed to diff snapshot: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
 /// Get `Block` in `Workspace`
 /// - Return 200 and `Block`'s ID.
 /// - Return 404 Not Found if `Workspace` or `Block` not exists.
@@ -419,5 +884,10 @@ mod test {
             resp.json::<schema::Workspace>().await,
             schema::Workspace::default()
         );
+
+        let resp = client.get("/search/test?limit=1000").send().await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = client.get("/search/test?offset=100000").send().await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 }
@@ -10,11 +10,12 @@ use axum::{
     extract::{Json, Path},
     http::StatusCode,
     response::IntoResponse,
-    routing::{delete, get, head},
+    routing::{delete, get, head, post},
 };
+use jwst::SchemaRegistry;
 use jwst_rpc::{BroadcastChannels, RpcContextImpl};
-use jwst_storage::JwstStorage;
-use std::collections::HashMap;
+use jwst_storage::{BlobGcOptions, BlobStorageType, JwstStorage, S3Options};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 use tokio::sync::RwLock;
 
 #[derive(Deserialize)]
@@ -36,32 +37,96 @@ pub struct PageData<T> {
     data: T,
 }
 
+/// Blobs are streamed into the storage, so the limit is not bound by memory
+const DEFAULT_BLOB_UPLOAD_LIMIT: u64 = 100 * 1024 * 1024;
+
 pub struct Context {
     pub channel: BroadcastChannels,
     pub storage: JwstStorage,
+    /// Flavours and props checked by the block apis, more flavours can be registered
+    /// through the schema api, they are kept in memory only and lost on restart
+    pub schemas: RwLock<SchemaRegistry>,
+    /// Default options of the blob garbage collection
+    pub blob_gc: BlobGcOptions,
+    /// Size in bytes of the largest blob that can be uploaded
+    pub blob_upload_limit: u64,
 }
 
 impl Context {
     pub async fn new(storage: Option<JwstStorage>) -> Self {
+        let index_dir = dotenvy::var("SEARCH_INDEX_DIR").ok().map(PathBuf::from);
+        if let Some(index_dir) = &index_dir {
+            info!("persist search indexes in: {}", index_dir.display());
+        }
+        let blob_storage = match (
+            dotenvy::var("BLOB_S3_BUCKET"),
+            dotenvy::var("BLOB_STORAGE_DIR"),
+        ) {
+            (Ok(bucket), _) if !bucket.is_empty() => {
+                info!("store blobs in s3 bucket: {}", bucket);
+                BlobStorageType::S3(s3_options(bucket))
+            }
+            (_, Ok(blob_dir)) if !blob_dir.is_empty() => {
+                info!("store blobs in: {}", blob_dir);
+                BlobStorageType::Local(PathBuf::from(blob_dir))
+            }
+            _ => BlobStorageType::Database,
+        };
         let storage = if let Some(storage) = storage {
             info!("use external storage instance: {}", storage.database());
             Ok(storage)
         } else if let Ok(database_url) = dotenvy::var("DATABASE_URL") {
             info!("use external database: {}", database_url);
-            JwstStorage::new(&database_url).await
+            JwstStorage::new_with_options(&database_url, index_dir, blob_storage).await
         } else {
             info!("use sqlite database: jwst.db");
-            JwstStorage::new_with_sqlite("jwst").await
+            JwstStorage::new_with_sqlite("jwst", index_dir, blob_storage).await
         }
         .expect("Cannot create database");
 
+        // seconds a blob must stay unreferenced before it is deleted
+        let blob_gc = BlobGcOptions {
+            grace_period: dotenvy::var("BLOB_GC_GRACE_PERIOD")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(BlobGcOptions::default().grace_period),
+            dry_run: false,
+        };
+        let blob_upload_limit = dotenvy::var("BLOB_UPLOAD_LIMIT")
+            .ok()
+            .and_then(|bytes| bytes.parse().ok())
+            .unwrap_or(DEFAULT_BLOB_UPLOAD_LIMIT);
+
         Context {
             channel: RwLock::new(HashMap::new()),
             storage,
+            schemas: RwLock::new(SchemaRegistry::default()),
+            blob_gc,
+            blob_upload_limit,
         }
     }
 }
 
+/// The object storage of the blobs, configured by the `BLOB_S3_*` variables
+fn s3_options(bucket: String) -> S3Options {
+    let var = |name: &str, default: &str| {
+        dotenvy::var(name)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| default.into())
+    };
+    S3Options {
+        endpoint: var("BLOB_S3_ENDPOINT", "https://s3.amazonaws.com"),
+        bucket,
+        region: var("BLOB_S3_REGION", "us-east-1"),
+        access_key: var("BLOB_S3_ACCESS_KEY", ""),
+        secret_key: var("BLOB_S3_SECRET_KEY", ""),
+        prefix: var("BLOB_S3_PREFIX", ""),
+        path_style: var("BLOB_S3_PATH_STYLE", "false") == "true",
+    }
+}
+
 impl RpcContextImpl<'_> for Context {
     fn get_storage(&self) -> &JwstStorage {
         &self.storage
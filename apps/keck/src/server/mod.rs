@@ -4,7 +4,7 @@ mod sync;
 mod utils;
 
 use axum::{http::Method, response::Redirect, Extension, Router, Server};
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::signal;
 use tower_http::cors::{Any, CorsLayer};
 
@@ -61,6 +61,22 @@ pub async fn start_server() {
 
     let context = Arc::new(Context::new(None).await);
 
+    // collect the unreferenced blobs of all workspaces periodically, in seconds
+    if let Some(interval) = dotenvy::var("BLOB_GC_INTERVAL")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+    {
+        info!("gc blobs every {} seconds", interval);
+        let context = context.clone();
+        tokio::spawn(async move {
+            context
+                .storage
+                .gc_blobs_every(Duration::from_secs(interval), context.blob_gc)
+                .await
+        });
+    }
+
     let app = files::static_files(sync::sync_handler(api::api_handler(Router::new())))
         .layer(cors)
         .layer(Extension(context.clone()));
@@ -7,16 +7,17 @@ use axum::{
     headers::ContentLength,
     http::{
         header::{
-            CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
-            LAST_MODIFIED,
+            ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+            CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+            X_CONTENT_TYPE_OPTIONS,
         },
         HeaderMap, HeaderValue, StatusCode,
     },
-    response::{IntoResponse, Response},
+    response::{IntoResponse, Redirect, Response},
     Json, TypedHeader,
 };
 use futures::{future, StreamExt};
-use jwst::BlobStorage;
+use jwst::{read_blob_ranges, BlobRanges, BlobStorage};
 use time::{format_description::well_known::Rfc2822, OffsetDateTime};
 
 #[derive(Serialize)]
@@ -53,12 +54,17 @@ impl Context {
             }
         }
 
-        let mut header = HeaderMap::with_capacity(5);
+        let mut header = HeaderMap::with_capacity(8);
         header.insert(ETAG, HeaderValue::from_str(&id).unwrap());
         header.insert(
             CONTENT_TYPE,
-            HeaderValue::from_static("application/octet-stream"),
+            HeaderValue::from_str(&meta.content_type)
+                .unwrap_or(HeaderValue::from_static("application/octet-stream")),
         );
+        if let Ok(disposition) = HeaderValue::from_str(&meta.content_disposition(&id)) {
+            header.insert(CONTENT_DISPOSITION, disposition);
+        }
+        header.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
         header.insert(
             LAST_MODIFIED,
             HeaderValue::from_str(
@@ -77,11 +83,67 @@ impl Context {
             CACHE_CONTROL,
             HeaderValue::from_str("public, immutable, max-age=31536000").unwrap(),
         );
+        header.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
 
         if method == Method::HEAD {
             return header.into_response();
         };
 
+        // let the client download the blob from the object storage directly
+        if let Ok(Some(url)) = self
+            .storage
+            .blobs()
+            .get_blob_url(workspace.clone(), id.clone())
+            .await
+        {
+            return Redirect::temporary(&url).into_response();
+        }
+
+        let ranges = BlobRanges::from_headers(
+            headers.get(RANGE).and_then(|h| h.to_str().ok()),
+            headers
+                .get(IF_RANGE)
+                .map(|h| h.to_str().unwrap_or_default()),
+            &id,
+            &meta,
+        );
+        match ranges {
+            BlobRanges::Full => {}
+            BlobRanges::Unsatisfiable => {
+                return (
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(
+                        CONTENT_RANGE,
+                        BlobRanges::unsatisfiable_content_range(meta.size),
+                    )],
+                )
+                    .into_response()
+            }
+            BlobRanges::Partial(ranges) => {
+                let Ok(ranges) =
+                    read_blob_ranges(self.storage.blobs(), workspace, id, &meta, ranges).await
+                else {
+                    return StatusCode::NOT_FOUND.into_response();
+                };
+                if let Some(content_type) = ranges.content_type {
+                    header.insert(CONTENT_TYPE, HeaderValue::from_str(&content_type).unwrap());
+                }
+                if let Some(content_range) = ranges.content_range {
+                    header.insert(
+                        CONTENT_RANGE,
+                        HeaderValue::from_str(&content_range).unwrap(),
+                    );
+                }
+                header.insert(CONTENT_LENGTH, HeaderValue::from(ranges.content_length));
+                return (
+                    StatusCode::PARTIAL_CONTENT,
+                    header,
+                    StreamBody::new(ranges.body),
+                )
+                    .into_response();
+            }
+        }
+
         let Ok(file) = self.storage.blobs().get_blob(workspace, id).await else {
             return StatusCode::NOT_FOUND.into_response()
         };
@@ -89,7 +151,12 @@ impl Context {
         (header, StreamBody::new(file)).into_response()
     }
 
-    async fn upload_blob(&self, stream: BodyStream, workspace: Option<String>) -> Response {
+    async fn upload_blob(
+        &self,
+        stream: BodyStream,
+        workspace: Option<String>,
+        content_type: Option<String>,
+    ) -> Response {
         // TODO: cancel
         let mut has_error = false;
         let stream = stream
@@ -102,7 +169,7 @@ impl Context {
         if let Ok(id) = self
             .storage
             .blobs()
-            .put_blob(workspace.clone(), stream)
+            .put_blob(workspace.clone(), stream, content_type)
             .await
         {
             if has_error {
@@ -130,11 +197,106 @@ pub async fn upload_blob_in_workspace(
     Extension(ctx): Extension<Arc<Context>>,
     Path(workspace_id): Path<String>,
     TypedHeader(length): TypedHeader<ContentLength>,
+    headers: HeaderMap,
     stream: BodyStream,
 ) -> Response {
-    if length.0 > 10 * 1024 * 1024 {
+    if length.0 > ctx.blob_upload_limit {
         return StatusCode::PAYLOAD_TOO_LARGE.into_response();
     }
 
-    ctx.upload_blob(stream, Some(workspace_id)).await
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    ctx.upload_blob(stream, Some(workspace_id), content_type)
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Bytes, routing::get};
+    use axum_test_helper::TestClient;
+    use futures::stream::iter;
+    use jwst_storage::JwstStorage;
+
+    #[tokio::test]
+    async fn get_blob_ranges() {
+        let storage = JwstStorage::new("sqlite::memory:").await.unwrap();
+        let context = Arc::new(Context::new(Some(storage)).await);
+        let id = context
+            .storage
+            .blobs()
+            .put_blob(
+                Some("test".into()),
+                iter(vec![Bytes::from("0123456789")]),
+                Some("text/plain".into()),
+            )
+            .await
+            .unwrap();
+
+        let app = Router::new()
+            .route("/blob/:workspace/:name", get(get_blob_in_workspace))
+            .layer(Extension(context));
+        let client = TestClient::new(app);
+        let url = format!("/blob/test/{id}");
+
+        let resp = client.get(&url).header(RANGE, "bytes=2-4").send().await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[CONTENT_RANGE], "bytes 2-4/10");
+        assert_eq!(resp.headers()[CONTENT_LENGTH], "3");
+        assert_eq!(resp.text().await, "234");
+
+        // overlapping ranges are served as one
+        let resp = client.get(&url).header(RANGE, "bytes=0-3,2-5").send().await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[CONTENT_RANGE], "bytes 0-5/10");
+        assert_eq!(resp.text().await, "012345");
+
+        let resp = client.get(&url).header(RANGE, "bytes=10-").send().await;
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(resp.headers()[CONTENT_RANGE], "bytes */10");
+
+        // several ranges are the parts of a multipart body
+        let resp = client.get(&url).header(RANGE, "bytes=0-1,5-6").send().await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let boundary = resp.headers()[CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap()
+            .to_string();
+        let length: usize = resp.headers()[CONTENT_LENGTH]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let body = resp.text().await;
+        assert_eq!(
+            body,
+            format!(
+                "--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+                 --{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 5-6/10\r\n\r\n56\r\n\
+                 --{boundary}--\r\n"
+            )
+        );
+        assert_eq!(body.len(), length);
+
+        // the ranges are only served for the version of the blob the client has
+        let resp = client
+            .get(&url)
+            .header(RANGE, "bytes=2-4")
+            .header(IF_RANGE, "\"other\"")
+            .send()
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.text().await, "0123456789");
+        let resp = client
+            .get(&url)
+            .header(RANGE, "bytes=2-4")
+            .header(IF_RANGE, format!("\"{id}\""))
+            .send()
+            .await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    }
 }
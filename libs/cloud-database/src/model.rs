@@ -9,6 +9,7 @@ use sea_orm::{FromQueryResult, TryGetable};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use sqlx::{self, types::chrono::NaiveDateTime, FromRow, Type};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GoogleClaims {
@@ -187,6 +188,11 @@ pub struct CreateWorkspace {
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WorkspaceSearchInput {
     pub query: String,
+    /// Number of results to skip, at most 10000
+    #[serde(default)]
+    pub offset: usize,
+    /// Max number of results, 10 if not set and at most 100
+    pub limit: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -199,6 +205,40 @@ pub struct WorkspaceSearchResult {
     pub block_id: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UserSearchInput {
+    pub query: String,
+    /// Number of results to skip
+    #[serde(default)]
+    pub offset: usize,
+    /// Max number of results, 10 if not set, `offset + limit` is at most 100
+    pub limit: Option<usize>,
+    /// Only search in these workspaces, all workspaces of the user are searched if not set
+    pub workspaces: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct UserSearchResults {
+    /// Number of all matched blocks in the searched workspaces
+    pub total: usize,
+    pub offset: usize,
+    pub items: Vec<UserSearchResult>,
+    /// Workspaces that could not be searched, their blocks are missing from the items
+    #[serde(default)]
+    pub failed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UserSearchResult {
+    pub workspace_id: String,
+    pub space_id: String,
+    pub block_id: String,
+    pub score: f32,
+    /// Highlighted fragments of the matched fields keyed by field name
+    #[serde(default)]
+    pub snippets: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UpdateWorkspace {
     pub public: bool,
@@ -3,6 +3,7 @@ package com.toeverything.jwst
 import com.toeverything.jwst.lib.JwstStorage
 import java.util.*
 import com.toeverything.jwst.lib.Block as JwstBlock
+import com.toeverything.jwst.lib.UndoManager as JwstUndoManager
 import com.toeverything.jwst.lib.Workspace as JwstWorkspace
 import com.toeverything.jwst.lib.WorkspaceTransaction as JwstWorkspaceTransaction
 import com.toeverything.jwst.lib.VecOfStrings
@@ -71,6 +72,46 @@ class Workspace(workspace: JwstWorkspace) {
         }
         return this.workspace.setSearchIndex(indexFields)
     }
+
+    fun undoManager(spaceId: String = "blocks"): UndoManager {
+        return UndoManager(JwstUndoManager(this.workspace, spaceId))
+    }
+}
+
+class UndoManager constructor(private var manager: JwstUndoManager) {
+    companion object {
+        init {
+            System.loadLibrary("jwst")
+        }
+    }
+
+    fun undo(): Boolean {
+        return this.manager.undo()
+    }
+
+    fun redo(): Boolean {
+        return this.manager.redo()
+    }
+
+    fun canUndo(): Boolean {
+        return this.manager.canUndo()
+    }
+
+    fun canRedo(): Boolean {
+        return this.manager.canRedo()
+    }
+
+    fun stopCapturing() {
+        this.manager.stopCapturing()
+    }
+
+    fun clear() {
+        this.manager.clear()
+    }
+
+    fun observeStackChanged(callback: (undo: Boolean, pushed: Boolean) -> Unit) {
+        this.manager.observeStackChanged { undo, pushed -> callback(undo, pushed) }
+    }
 }
 
 class WorkspaceTransaction constructor(internal var trx: JwstWorkspaceTransaction) {
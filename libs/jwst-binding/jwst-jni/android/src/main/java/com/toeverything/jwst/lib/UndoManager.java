@@ -0,0 +1,74 @@
+// Automatically generated by flapigen
+package com.toeverything.jwst.lib;
+import androidx.annotation.NonNull;
+import androidx.annotation.Nullable;
+
+public final class UndoManager {
+
+    public UndoManager(@NonNull Workspace workspace) {
+        long a0 = workspace.mNativeObj;
+        mNativeObj = init(a0);
+        JNIReachabilityFence.reachabilityFence1(workspace);
+    }
+    private static native long init(long workspace);
+
+    public final boolean undo() {
+        boolean ret = do_undo(mNativeObj);
+
+        return ret;
+    }
+    private static native boolean do_undo(long self);
+
+    public final boolean redo() {
+        boolean ret = do_redo(mNativeObj);
+
+        return ret;
+    }
+    private static native boolean do_redo(long self);
+
+    public final boolean canUndo() {
+        boolean ret = do_canUndo(mNativeObj);
+
+        return ret;
+    }
+    private static native boolean do_canUndo(long self);
+
+    public final boolean canRedo() {
+        boolean ret = do_canRedo(mNativeObj);
+
+        return ret;
+    }
+    private static native boolean do_canRedo(long self);
+
+    public final void stopCapturing() {
+        do_stopCapturing(mNativeObj);
+    }
+    private static native void do_stopCapturing(long self);
+
+    public final void clear() {
+        do_clear(mNativeObj);
+    }
+    private static native void do_clear(long self);
+
+    public synchronized void delete() {
+        if (mNativeObj != 0) {
+            do_delete(mNativeObj);
+            mNativeObj = 0;
+       }
+    }
+    @Override
+    protected void finalize() throws Throwable {
+        try {
+            delete();
+        }
+        finally {
+             super.finalize();
+        }
+    }
+    private static native void do_delete(long me);
+    /*package*/ UndoManager(InternalPointerMarker marker, long ptr) {
+        assert marker == InternalPointerMarker.RAW_PTR;
+        this.mNativeObj = ptr;
+    }
+    /*package*/ long mNativeObj;
+}
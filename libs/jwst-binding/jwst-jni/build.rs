@@ -43,6 +43,12 @@ r#"foreign_callback!(
         onTrx = OnWorkspaceTransaction::on_trx(& self , trx : WorkspaceTransaction);
     }
 );"#,
+r#"foreign_callback!(
+    callback OnUndoStackChanged {
+        self_type OnUndoStackChanged;
+        onChanged = OnUndoStackChanged::on_changed(& self , undo : bool , pushed : bool);
+    }
+);"#,
 r#"
 pub type VecOfStrings = Vec<String>;
 foreign_class!(
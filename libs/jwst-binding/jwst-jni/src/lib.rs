@@ -2,16 +2,18 @@ mod block;
 mod java_glue;
 mod storage;
 mod transaction;
+mod undo;
 mod workspace;
 
 pub use crate::java_glue::*;
 
 use block::Block;
 use jwst::{
-    Block as JwstBlock, Workspace as JwstWorkspace,
+    Block as JwstBlock, UndoManager as JwstUndoManager, Workspace as JwstWorkspace,
     WorkspaceTransaction as JwstWorkspaceTransaction,
 };
 use rifgen::rifgen_attr::*;
 use storage::JwstStorage;
 use transaction::{OnWorkspaceTransaction, WorkspaceTransaction};
+use undo::{OnUndoStackChanged, UndoManager};
 use workspace::Workspace;
@@ -0,0 +1,74 @@
+use super::{generate_interface, JwstUndoManager, Workspace};
+use jwst::{error, UndoOptions, UndoStack, UndoStackEvent};
+
+pub trait OnUndoStackChanged {
+    /// `undo` is false for the redo stack, `pushed` is false when a step was popped.
+    fn on_changed(&self, undo: bool, pushed: bool);
+}
+
+pub struct UndoManager(pub(crate) JwstUndoManager);
+
+impl UndoManager {
+    /// Track the changes of the space made by [Workspace::with_trx],
+    /// the changes synced from other clients are not undone.
+    #[generate_interface(constructor)]
+    pub fn new(workspace: &Workspace, space_id: String) -> UndoManager {
+        let workspace = &workspace.workspace;
+        Self(workspace.undo_manager(
+            space_id,
+            UndoOptions {
+                origins: vec![workspace.local_origin()],
+                ..Default::default()
+            },
+        ))
+    }
+
+    #[generate_interface]
+    pub fn undo(&mut self) -> bool {
+        self.0.undo().unwrap_or_else(|e| {
+            error!("failed to undo: {}", e);
+            false
+        })
+    }
+
+    #[generate_interface]
+    pub fn redo(&mut self) -> bool {
+        self.0.redo().unwrap_or_else(|e| {
+            error!("failed to redo: {}", e);
+            false
+        })
+    }
+
+    #[generate_interface]
+    pub fn can_undo(&self) -> bool {
+        self.0.can_undo()
+    }
+
+    #[generate_interface]
+    pub fn can_redo(&self) -> bool {
+        self.0.can_redo()
+    }
+
+    #[generate_interface]
+    pub fn stop_capturing(&mut self) {
+        self.0.stop_capturing()
+    }
+
+    #[generate_interface]
+    pub fn clear(&mut self) {
+        if let Err(e) = self.0.clear() {
+            error!("failed to clear undo: {}", e);
+        }
+    }
+
+    #[generate_interface]
+    pub fn observe_stack_changed(&mut self, callback: Box<dyn OnUndoStackChanged>) {
+        self.0.observe_stack_changed(move |event| {
+            let (stack, pushed) = match event {
+                UndoStackEvent::Added(stack) => (stack, true),
+                UndoStackEvent::Popped(stack) => (stack, false),
+            };
+            callback.on_changed(stack == UndoStack::Undo, pushed)
+        })
+    }
+}
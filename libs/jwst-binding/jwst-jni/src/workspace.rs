@@ -35,10 +35,11 @@ impl Workspace {
         trx.0.get_blocks().exists(&trx.0.trx, block_id)
     }
 
+    /// The changes are made as this client, see [super::UndoManager].
     #[generate_interface]
     pub fn with_trx(&self, on_trx: Box<dyn OnWorkspaceTransaction>) -> bool {
         self.workspace
-            .try_with_trx(|trx| on_trx.on_trx(WorkspaceTransaction(trx)))
+            .try_with_local_trx(|trx| on_trx.on_trx(WorkspaceTransaction(trx)))
             .is_some()
     }
 
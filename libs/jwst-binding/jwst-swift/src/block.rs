@@ -1,5 +1,5 @@
 use super::DynamicValue;
-use jwst::{Block as JwstBlock, Workspace};
+use jwst::{Block as JwstBlock, Workspace, WorkspaceTransaction};
 use lib0::any::Any;
 
 pub struct Block {
@@ -12,6 +12,12 @@ impl Block {
         Self { workspace, block }
     }
 
+    /// The changes are made as this client, see [crate::UndoManager].
+    fn with_local_trx<T>(&self, f: impl FnOnce(WorkspaceTransaction) -> T) -> T {
+        self.workspace
+            .with_trx_origin(&self.workspace.local_origin(), f)
+    }
+
     pub fn get(&self, key: String) -> Option<DynamicValue> {
         self.workspace
             .with_trx(|trx| self.block.get(&trx.trx, &key).map(DynamicValue::new))
@@ -22,34 +28,32 @@ impl Block {
     }
 
     pub fn push_children(&self, block: &Block) {
-        self.workspace
-            .with_trx(|mut trx| self.block.push_children(&mut trx.trx, &block.block));
+        self.with_local_trx(|mut trx| self.block.push_children(&mut trx.trx, &block.block));
     }
 
     pub fn insert_children_at(&self, block: &Block, pos: u32) {
-        self.workspace.with_trx(|mut trx| {
+        self.with_local_trx(|mut trx| {
             self.block
                 .insert_children_at(&mut trx.trx, &block.block, pos)
         });
     }
 
     pub fn insert_children_before(&self, block: &Block, reference: &str) {
-        self.workspace.with_trx(|mut trx| {
+        self.with_local_trx(|mut trx| {
             self.block
                 .insert_children_before(&mut trx.trx, &block.block, reference)
         });
     }
 
     pub fn insert_children_after(&self, block: &Block, reference: &str) {
-        self.workspace.with_trx(|mut trx| {
+        self.with_local_trx(|mut trx| {
             self.block
                 .insert_children_after(&mut trx.trx, &block.block, reference)
         });
     }
 
     pub fn remove_children(&self, block: &Block) {
-        self.workspace
-            .with_trx(|mut trx| self.block.remove_children(&mut trx.trx, &block.block));
+        self.with_local_trx(|mut trx| self.block.remove_children(&mut trx.trx, &block.block));
     }
 
     pub fn exists_children(&self, block_id: &str) -> i32 {
@@ -88,28 +92,23 @@ impl Block {
     }
 
     pub fn set_bool(&self, key: String, value: bool) {
-        self.workspace
-            .with_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
+        self.with_local_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
     }
 
     pub fn set_string(&self, key: String, value: String) {
-        self.workspace
-            .with_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
+        self.with_local_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
     }
 
     pub fn set_float(&self, key: String, value: f64) {
-        self.workspace
-            .with_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
+        self.with_local_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
     }
 
     pub fn set_integer(&self, key: String, value: i64) {
-        self.workspace
-            .with_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
+        self.with_local_trx(|mut trx| self.block.set(&mut trx.trx, &key, value));
     }
 
     pub fn set_null(&self, key: String) {
-        self.workspace
-            .with_trx(|mut trx| self.block.set(&mut trx.trx, &key, Any::Null));
+        self.with_local_trx(|mut trx| self.block.set(&mut trx.trx, &key, Any::Null));
     }
 
     pub fn is_bool(&self, key: String) -> bool {
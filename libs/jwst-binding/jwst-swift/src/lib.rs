@@ -1,12 +1,14 @@
 mod block;
 mod dynamic_value;
 mod storage;
+mod undo;
 mod workspace;
 
 pub use block::Block;
 pub use dynamic_value::{DynamicValue, DynamicValueMap};
 use jwst::JwstError;
 pub use storage::Storage;
+pub use undo::UndoManager;
 pub use workspace::Workspace;
 
 type JwstWorkSpaceResult = Result<Workspace, JwstError>;
@@ -111,6 +113,33 @@ mod ffi {
         fn get_search_index(self: &Workspace) -> Vec<String>;
 
         fn set_search_index(self: &Workspace, fields: Vec<String>) -> bool;
+
+        fn undo_manager(self: &Workspace, space_id: String) -> UndoManager;
+    }
+
+    extern "Rust" {
+        type UndoManager;
+
+        fn undo(self: &mut UndoManager) -> bool;
+
+        fn redo(self: &mut UndoManager) -> bool;
+
+        fn can_undo(self: &UndoManager) -> bool;
+
+        fn can_redo(self: &UndoManager) -> bool;
+
+        fn stop_capturing(self: &mut UndoManager);
+
+        fn clear(self: &mut UndoManager);
+
+        fn observe_stack_changed(self: &mut UndoManager, observer: UndoStackObserver);
+    }
+
+    extern "Swift" {
+        type UndoStackObserver;
+
+        /// `undo` is false for the redo stack, `pushed` is false when a step was popped.
+        fn on_changed(self: &UndoStackObserver, undo: bool, pushed: bool);
     }
 
     extern "Rust" {
@@ -0,0 +1,50 @@
+use super::ffi::UndoStackObserver;
+use jwst::{error, UndoManager as JwstUndoManager, UndoStack, UndoStackEvent};
+
+pub struct UndoManager {
+    pub(crate) manager: JwstUndoManager,
+}
+
+impl UndoManager {
+    pub fn undo(self: &mut UndoManager) -> bool {
+        self.manager.undo().unwrap_or_else(|e| {
+            error!("failed to undo: {}", e);
+            false
+        })
+    }
+
+    pub fn redo(self: &mut UndoManager) -> bool {
+        self.manager.redo().unwrap_or_else(|e| {
+            error!("failed to redo: {}", e);
+            false
+        })
+    }
+
+    pub fn can_undo(self: &UndoManager) -> bool {
+        self.manager.can_undo()
+    }
+
+    pub fn can_redo(self: &UndoManager) -> bool {
+        self.manager.can_redo()
+    }
+
+    pub fn stop_capturing(self: &mut UndoManager) {
+        self.manager.stop_capturing()
+    }
+
+    pub fn clear(self: &mut UndoManager) {
+        if let Err(e) = self.manager.clear() {
+            error!("failed to clear undo: {}", e);
+        }
+    }
+
+    pub fn observe_stack_changed(self: &mut UndoManager, observer: UndoStackObserver) {
+        self.manager.observe_stack_changed(move |event| {
+            let (stack, pushed) = match event {
+                UndoStackEvent::Added(stack) => (stack, true),
+                UndoStackEvent::Popped(stack) => (stack, false),
+            };
+            observer.on_changed(stack == UndoStack::Undo, pushed)
+        })
+    }
+}
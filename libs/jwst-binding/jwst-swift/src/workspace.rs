@@ -1,5 +1,5 @@
-use super::Block;
-use jwst::Workspace as JwstWorkspace;
+use super::{Block, UndoManager};
+use jwst::{UndoOptions, Workspace as JwstWorkspace};
 use yrs::UpdateSubscription;
 
 pub struct Workspace {
@@ -37,7 +37,8 @@ impl Workspace {
 
     pub fn create(&self, block_id: String, flavor: String) -> Block {
         let workspace = self.workspace.clone();
-        self.workspace.with_trx(|mut trx| {
+        let origin = self.workspace.local_origin();
+        self.workspace.with_trx_origin(&origin, |mut trx| {
             let block = Block::new(
                 workspace,
                 trx.get_blocks().create(&mut trx.trx, block_id, flavor),
@@ -69,4 +70,18 @@ impl Workspace {
     pub fn set_search_index(self: &Workspace, fields: Vec<String>) -> bool {
         self.workspace.set_search_index(fields)
     }
+
+    /// Track the changes of the space made through this binding for undo and redo,
+    /// the changes synced from other clients are not undone.
+    pub fn undo_manager(self: &Workspace, space_id: String) -> UndoManager {
+        UndoManager {
+            manager: self.workspace.undo_manager(
+                space_id,
+                UndoOptions {
+                    origins: vec![self.workspace.local_origin()],
+                    ..Default::default()
+                },
+            ),
+        }
+    }
 }
@@ -9,9 +9,10 @@ pub struct Model {
     pub workspace: String,
     #[sea_orm(primary_key, auto_increment = false)]
     pub hash: String,
-    pub blob: Vec<u8>,
     pub length: i64,
     pub timestamp: DateTimeWithTimeZone,
+    pub unreferenced_since: Option<DateTimeWithTimeZone>,
+    pub content_type: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
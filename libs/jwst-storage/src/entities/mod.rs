@@ -2,5 +2,7 @@
 
 pub mod prelude;
 
+pub mod blob_chunks;
 pub mod blobs;
 pub mod docs;
+pub mod snapshots;
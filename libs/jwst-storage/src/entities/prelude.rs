@@ -1,4 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.6
 
+pub use super::blob_chunks::Entity as BlobChunks;
 pub use super::blobs::Entity as Blobs;
 pub use super::docs::Entity as Docs;
+pub use super::snapshots::Entity as Snapshots;
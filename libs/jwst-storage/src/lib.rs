@@ -16,11 +16,11 @@ use jwst::{DocStorage, JwstError, JwstResult, Workspace};
 use jwst_logger::{debug, error, info, trace, warn};
 use path_ext::PathExt;
 use sea_orm::{prelude::*, ConnectOptions, Database, DbErr, FromQueryResult, QuerySelect, Set};
-use std::{io::Cursor, num::NonZeroU32, path::PathBuf, sync::Arc, time::Duration};
+use std::{num::NonZeroU32, path::PathBuf, sync::Arc, time::Duration};
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use url::Url;
 
-pub use storage::JwstStorage;
+pub use storage::{BlobGcOptions, BlobStorageType, JwstStorage, S3Options, SnapshotMetadata};
 
 pub struct Bucket {
     bucket: Arc<RateLimiter<NotKeyed, InMemoryState, QuantaClock, NoOpMiddleware<QuantaInstant>>>,
@@ -2,6 +2,10 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_initial_blob_table;
 mod m20220101_000002_initial_doc_table;
+mod m20230321_000001_snapshot_table;
+mod m20230410_000001_blob_unreferenced;
+mod m20230418_000001_blob_chunks;
+mod m20230420_000001_blob_content_type;
 mod schema;
 
 pub struct Migrator;
@@ -12,6 +16,10 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20220101_000001_initial_blob_table::Migration),
             Box::new(m20220101_000002_initial_doc_table::Migration),
+            Box::new(m20230321_000001_snapshot_table::Migration),
+            Box::new(m20230410_000001_blob_unreferenced::Migration),
+            Box::new(m20230418_000001_blob_chunks::Migration),
+            Box::new(m20230420_000001_blob_content_type::Migration),
         ]
     }
 }
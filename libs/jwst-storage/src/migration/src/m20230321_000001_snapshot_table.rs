@@ -0,0 +1,60 @@
+use super::schema::Snapshots;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230321_000001_snapshot_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Snapshots::Table)
+                    .col(
+                        ColumnDef::new(Snapshots::Id)
+                            .integer()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Snapshots::Workspace).string().not_null())
+                    .col(ColumnDef::new(Snapshots::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(Snapshots::Timestamp)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Snapshots::StateVector).binary().not_null())
+                    .col(ColumnDef::new(Snapshots::Blob).binary().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("snapshots_list")
+                    .table(Snapshots::Table)
+                    .col(Snapshots::Workspace)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("snapshots_list").to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Snapshots::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
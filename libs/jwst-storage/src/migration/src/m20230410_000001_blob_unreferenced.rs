@@ -0,0 +1,40 @@
+use super::schema::Blobs;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230410_000001_blob_unreferenced"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // When the garbage collection first found the blob unreferenced, null if it is referenced.
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blobs::Table)
+                    .add_column(
+                        ColumnDef::new(Blobs::UnreferencedSince)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blobs::Table)
+                    .drop_column(Blobs::UnreferencedSince)
+                    .to_owned(),
+            )
+            .await
+    }
+}
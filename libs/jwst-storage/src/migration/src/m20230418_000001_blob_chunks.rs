@@ -0,0 +1,136 @@
+use super::schema::{BlobChunks, Blobs};
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::query::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230418_000001_blob_chunks"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // The content of the blobs is stored in chunks, so that it can be streamed
+    // instead of reading and writing the whole blob at once.
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BlobChunks::Table)
+                    .col(ColumnDef::new(BlobChunks::Workspace).string().not_null())
+                    .col(ColumnDef::new(BlobChunks::Hash).string().not_null())
+                    .col(ColumnDef::new(BlobChunks::Idx).integer().not_null())
+                    .col(
+                        ColumnDef::new(BlobChunks::Chunk)
+                            .blob(BlobSize::Medium)
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(BlobChunks::Workspace)
+                            .col(BlobChunks::Hash)
+                            .col(BlobChunks::Idx),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // the existing blobs become their only chunk
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(BlobChunks::Table)
+                    .columns([
+                        BlobChunks::Workspace,
+                        BlobChunks::Hash,
+                        BlobChunks::Idx,
+                        BlobChunks::Chunk,
+                    ])
+                    .select_from(
+                        Query::select()
+                            .column(Blobs::Workspace)
+                            .column(Blobs::Hash)
+                            .expr(Expr::val(0))
+                            .column(Blobs::Blob)
+                            .from(Blobs::Table)
+                            .to_owned(),
+                    )
+                    .map_err(|e| DbErr::Migration(e.to_string()))?
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blobs::Table)
+                    .drop_column(Blobs::Blob)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    // The chunks of each blob are concatenated in order into the blob column again.
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blobs::Table)
+                    .add_column(ColumnDef::new(Blobs::Blob).binary().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        let builder = db.get_database_backend();
+
+        let trx = db.begin().await?;
+
+        let stmt = Query::select()
+            .column(Blobs::Workspace)
+            .column(Blobs::Hash)
+            .from(Blobs::Table)
+            .to_owned();
+        let rows = trx.query_all(builder.build(&stmt)).await?;
+
+        for row in rows.into_iter() {
+            let workspace = row.try_get::<String>("", "workspace")?;
+            let hash = row.try_get::<String>("", "hash")?;
+
+            // read one blob at a time, so that only a single blob is held in memory
+            let stmt = Query::select()
+                .column(BlobChunks::Chunk)
+                .from(BlobChunks::Table)
+                .cond_where(
+                    Cond::all()
+                        .add(Expr::col(BlobChunks::Workspace).eq(workspace.clone()))
+                        .add(Expr::col(BlobChunks::Hash).eq(hash.clone())),
+                )
+                .order_by(BlobChunks::Idx, Order::Asc)
+                .to_owned();
+            let mut blob = vec![];
+            for chunk in trx.query_all(builder.build(&stmt)).await? {
+                blob.extend(chunk.try_get::<Vec<u8>>("", "chunk")?);
+            }
+
+            let stmt = Query::update()
+                .table(Blobs::Table)
+                .values(vec![(Blobs::Blob, blob.into())])
+                .cond_where(
+                    Cond::all()
+                        .add(Expr::col(Blobs::Workspace).eq(workspace))
+                        .add(Expr::col(Blobs::Hash).eq(hash)),
+                )
+                .to_owned();
+            trx.execute(builder.build(&stmt)).await?;
+        }
+
+        trx.commit().await?;
+
+        manager
+            .drop_table(Table::drop().table(BlobChunks::Table).to_owned())
+            .await
+    }
+}
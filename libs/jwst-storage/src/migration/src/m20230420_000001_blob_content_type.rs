@@ -0,0 +1,41 @@
+use super::schema::Blobs;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230420_000001_blob_content_type"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // The MIME type the blobs are served with, the existing blobs stay binary.
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blobs::Table)
+                    .add_column(
+                        ColumnDef::new(Blobs::ContentType)
+                            .string()
+                            .not_null()
+                            .default("application/octet-stream"),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blobs::Table)
+                    .drop_column(Blobs::ContentType)
+                    .to_owned(),
+            )
+            .await
+    }
+}
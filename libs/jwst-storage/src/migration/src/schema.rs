@@ -8,6 +8,17 @@ pub enum Blobs {
     Blob,
     Length,
     Timestamp,
+    UnreferencedSince,
+    ContentType,
+}
+
+#[derive(Iden)]
+pub enum BlobChunks {
+    Table,
+    Workspace,
+    Hash,
+    Idx,
+    Chunk,
 }
 
 #[derive(Iden)]
@@ -18,3 +29,14 @@ pub enum Docs {
     Timestamp,
     Blob,
 }
+
+#[derive(Iden)]
+pub enum Snapshots {
+    Table,
+    Id,
+    Workspace,
+    Name,
+    Timestamp,
+    StateVector,
+    Blob,
+}
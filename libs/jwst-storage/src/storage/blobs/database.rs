@@ -0,0 +1,884 @@
+use super::{entities::prelude::*, utils::BlobHasher, *};
+use bytes::Bytes;
+use futures::{
+    future,
+    stream::{self, BoxStream, StreamExt},
+};
+use jwst::{BlobInfo, BlobMetadata, BlobStorage, BlobUsage};
+use jwst_storage_migration::{Migrator, MigratorTrait};
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, SimpleExpr},
+    Condition, ConnectionTrait, DbBackend, QueryOrder, TransactionTrait,
+};
+use std::{
+    io::ErrorKind,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+pub(super) type BlobModel = <Blobs as EntityTrait>::Model;
+type BlobActiveModel = super::entities::blobs::ActiveModel;
+type BlobColumn = <Blobs as EntityTrait>::Column;
+type BlobChunkActiveModel = super::entities::blob_chunks::ActiveModel;
+type BlobChunkColumn = <BlobChunks as EntityTrait>::Column;
+
+/// Most databases limit the number of bound parameters of a statement
+const HASHES_PER_STATEMENT: usize = 500;
+/// The content of the blobs is stored in chunks of this size, so that only
+/// one chunk of a blob is held in memory while it is read or written.
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// The chunks are written under this key until the hash of the blob is known,
+/// hashes never start with a dot.
+const UPLOAD_PREFIX: &str = ".upload-";
+/// Uploads that are not committed after this many hours were interrupted, younger ones
+/// may still be written by other servers that share the database.
+const UPLOAD_MAX_AGE_HOURS: i64 = 24;
+
+static UPLOAD_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Key prefix of the chunks of an upload started at `time`, the timestamp is zero padded
+/// so that the keys of older uploads sort first.
+fn upload_key(time: DateTime<Utc>) -> String {
+    format!("{UPLOAD_PREFIX}{:019}", time.timestamp_nanos())
+}
+
+/// Only yield the bytes `range` of a blob from a stream of its content starting at `offset`.
+fn slice_stream(
+    stream: BoxStream<'static, std::io::Result<Bytes>>,
+    offset: u64,
+    range: Range<u64>,
+) -> BoxStream<'static, std::io::Result<Bytes>> {
+    stream
+        .scan(offset, move |position, chunk| {
+            if *position >= range.end {
+                return future::ready(None);
+            }
+            future::ready(Some(chunk.map(|chunk| {
+                let (start, len) = (*position, chunk.len() as u64);
+                *position += len;
+                let from = range.start.saturating_sub(start).min(len);
+                let to = (range.end - start).min(len);
+                chunk.slice(from as usize..to as usize)
+            })))
+        })
+        .filter(|chunk| future::ready(!matches!(chunk, Ok(chunk) if chunk.is_empty())))
+        .boxed()
+}
+
+#[derive(Clone)]
+pub struct BlobDBStorage {
+    bucket: Arc<Bucket>,
+    pool: DatabaseConnection,
+}
+
+impl BlobDBStorage {
+    pub async fn init_with_pool(pool: DatabaseConnection, bucket: Arc<Bucket>) -> JwstResult<Self> {
+        Migrator::up(&pool, None)
+            .await
+            .context("failed to run migration")?;
+        // chunks of uploads that were interrupted by a restart
+        Self::clean_up_uploads(
+            &pool,
+            Utc::now() - chrono::Duration::hours(UPLOAD_MAX_AGE_HOURS),
+        )
+        .await
+        .context("failed to clean up blob uploads")?;
+        Ok(Self { bucket, pool })
+    }
+
+    /// Delete the chunks of the uploads started before `before`.
+    async fn clean_up_uploads(
+        pool: &DatabaseConnection,
+        before: DateTime<Utc>,
+    ) -> Result<u64, DbErr> {
+        BlobChunks::delete_many()
+            .filter(BlobChunkColumn::Hash.starts_with(UPLOAD_PREFIX))
+            .filter(BlobChunkColumn::Hash.lt(upload_key(before)))
+            .exec(pool)
+            .await
+            .map(|result| result.rows_affected)
+    }
+
+    pub async fn init_pool(database: &str) -> JwstResult<Self> {
+        let is_sqlite = is_sqlite(database);
+        let pool = create_connection(database, is_sqlite).await?;
+
+        Self::init_with_pool(pool, get_bucket(is_sqlite)).await
+    }
+
+    async fn list(&self, table: &str, offset: u64, limit: u64) -> Result<Vec<BlobInfo>, DbErr> {
+        #[derive(FromQueryResult)]
+        struct Info {
+            hash: String,
+            length: i64,
+            timestamp: DateTime<Utc>,
+        }
+
+        // databases take the limit as a signed integer
+        let limit = limit.min(i64::MAX as u64);
+        Ok(Blobs::find()
+            .filter(BlobColumn::Workspace.eq(table))
+            .select_only()
+            .column(BlobColumn::Hash)
+            .column(BlobColumn::Length)
+            .column(BlobColumn::Timestamp)
+            .order_by_desc(BlobColumn::Timestamp)
+            .order_by_asc(BlobColumn::Hash)
+            .offset(offset)
+            .limit(limit)
+            .into_model::<Info>()
+            .all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|info| BlobInfo {
+                id: info.hash,
+                size: info.length as u64,
+                last_modified: info.timestamp.naive_local(),
+            })
+            .collect())
+    }
+
+    async fn usage(&self, table: &str) -> Result<BlobUsage, DbErr> {
+        #[derive(FromQueryResult)]
+        struct Usage {
+            count: i64,
+            size: i64,
+        }
+
+        // SUM is a decimal on some databases, which mysql only casts to SIGNED
+        let size_type = match self.pool.get_database_backend() {
+            DbBackend::MySql => "SIGNED",
+            _ => "BIGINT",
+        };
+        let size = Func::cast_as(
+            Func::coalesce([Expr::col(BlobColumn::Length).sum(), Expr::val(0).into()]),
+            Alias::new(size_type),
+        );
+        let usage = Blobs::find()
+            .filter(BlobColumn::Workspace.eq(table))
+            .select_only()
+            .column_as(Expr::cust("COUNT(*)"), "count")
+            .column_as(SimpleExpr::FunctionCall(size), "size")
+            .into_model::<Usage>()
+            .one(&self.pool)
+            .await?;
+
+        Ok(usage.map_or_else(BlobUsage::default, |usage| BlobUsage {
+            count: usage.count as u64,
+            size: usage.size as u64,
+        }))
+    }
+
+    async fn exists(&self, table: &str, hash: &str) -> Result<bool, DbErr> {
+        Blobs::find_by_id((table.into(), hash.into()))
+            .count(&self.pool)
+            .await
+            .map(|c| c > 0)
+    }
+
+    async fn metadata(&self, table: &str, hash: &str) -> Result<BlobMetadata, DbErr> {
+        #[derive(FromQueryResult)]
+        struct Metadata {
+            size: i64,
+            created_at: DateTime<Utc>,
+            content_type: String,
+        }
+
+        let ret = Blobs::find_by_id((table.into(), hash.into()))
+            .select_only()
+            .column_as(BlobColumn::Length, "size")
+            .column_as(BlobColumn::Timestamp, "created_at")
+            .column(BlobColumn::ContentType)
+            .into_model::<Metadata>()
+            .one(&self.pool)
+            .await
+            .and_then(|r| r.ok_or(DbErr::Query(RuntimeErr::Internal("blob not exists".into()))))?;
+
+        Ok(BlobMetadata {
+            size: ret.size as u64,
+            last_modified: ret.created_at.naive_local(),
+            content_type: ret.content_type,
+        })
+    }
+
+    /// The stored row of the blob, the content is in the chunks.
+    pub(super) async fn row(&self, table: &str, hash: &str) -> Result<BlobModel, DbErr> {
+        Blobs::find_by_id((table.into(), hash.into()))
+            .one(&self.pool)
+            .await
+            .and_then(|r| r.ok_or(DbErr::Query(RuntimeErr::Internal("blob not exists".into()))))
+    }
+
+    async fn insert_chunk(
+        &self,
+        table: &str,
+        key: &str,
+        idx: i32,
+        chunk: Vec<u8>,
+    ) -> Result<(), DbErr> {
+        let _lock = self.bucket.get_lock().await;
+        BlobChunks::insert(BlobChunkActiveModel {
+            workspace: Set(table.into()),
+            hash: Set(key.into()),
+            idx: Set(idx),
+            chunk: Set(chunk),
+        })
+        .exec(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Write the content in chunks under `key` while hashing it,
+    /// returns the hash, the length and the number of chunks.
+    async fn write_chunks(
+        &self,
+        table: &str,
+        key: &str,
+        stream: impl Stream<Item = Bytes> + Send,
+    ) -> Result<(String, i64, i32), DbErr> {
+        let mut stream = Box::pin(stream);
+        let mut hasher = BlobHasher::default();
+        let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+        let mut length = 0;
+        let mut chunks = 0;
+        while let Some(mut data) = stream.next().await {
+            hasher.update(&data);
+            length += data.len() as i64;
+            while !data.is_empty() {
+                let part = data.split_to((CHUNK_SIZE - buffer.len()).min(data.len()));
+                buffer.extend_from_slice(&part);
+                if buffer.len() == CHUNK_SIZE {
+                    let chunk = std::mem::replace(&mut buffer, Vec::with_capacity(CHUNK_SIZE));
+                    self.insert_chunk(table, key, chunks, chunk).await?;
+                    chunks += 1;
+                }
+            }
+        }
+        if !buffer.is_empty() {
+            self.insert_chunk(table, key, chunks, buffer).await?;
+            chunks += 1;
+        }
+
+        Ok((hasher.finalize(), length, chunks))
+    }
+
+    /// Store the content of the stream as a blob, returns its hash.
+    async fn insert(
+        &self,
+        table: &str,
+        stream: impl Stream<Item = Bytes> + Send,
+        content_type: &str,
+    ) -> Result<String, DbErr> {
+        let key = format!(
+            "{}-{}",
+            upload_key(Utc::now()),
+            UPLOAD_ID.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let result = match self.write_chunks(table, &key, stream).await {
+            Ok((hash, length, chunks)) => self
+                .commit_chunks(table, &key, &hash, length, chunks, content_type)
+                .await
+                .map(|_| hash),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let _lock = self.bucket.get_lock().await;
+            let _ = Self::delete_chunks(&self.pool, table, &[key]).await;
+        }
+        result
+    }
+
+    /// Move the chunks written under `key` to the blob of `hash`,
+    /// they are dropped if the blob already exists.
+    async fn commit_chunks(
+        &self,
+        table: &str,
+        key: &str,
+        hash: &str,
+        length: i64,
+        chunks: i32,
+        content_type: &str,
+    ) -> Result<(), DbErr> {
+        let _lock = self.bucket.get_lock().await;
+        let txn = self.pool.begin().await?;
+
+        let exists = Blobs::find_by_id((table.into(), hash.into()))
+            .count(&txn)
+            .await?
+            > 0;
+        if exists {
+            Self::delete_chunks(&txn, table, &[key.into()]).await?;
+            // uploaded again, it is about to be referenced
+            Blobs::update_many()
+                .col_expr(
+                    BlobColumn::UnreferencedSince,
+                    Expr::value(Option::<DateTimeWithTimeZone>::None),
+                )
+                .filter(BlobColumn::Workspace.eq(table))
+                .filter(BlobColumn::Hash.eq(hash))
+                .exec(&txn)
+                .await?;
+        } else {
+            let moved = BlobChunks::update_many()
+                .col_expr(BlobChunkColumn::Hash, Expr::value(hash))
+                .filter(BlobChunkColumn::Workspace.eq(table))
+                .filter(BlobChunkColumn::Hash.eq(key))
+                .exec(&txn)
+                .await?
+                .rows_affected;
+            if moved != chunks as u64 {
+                return Err(DbErr::Custom("chunks of the blob are missing".into()));
+            }
+            Blobs::insert(BlobActiveModel {
+                workspace: Set(table.into()),
+                hash: Set(hash.into()),
+                length: Set(length),
+                timestamp: Set(Utc::now().into()),
+                unreferenced_since: Set(None),
+                content_type: Set(content_type.into()),
+            })
+            .exec(&txn)
+            .await?;
+        }
+
+        txn.commit().await
+    }
+
+    /// The content of the blob, the chunks are read one by one as the stream is polled.
+    pub(super) fn chunks(
+        &self,
+        table: &str,
+        hash: &str,
+    ) -> BoxStream<'static, std::io::Result<Bytes>> {
+        self.chunks_from(table, hash, 0)
+    }
+
+    /// The content of the blob from the chunk `first` on, it fails if a chunk
+    /// is missing before the length of the blob is read.
+    fn chunks_from(
+        &self,
+        table: &str,
+        hash: &str,
+        first: i32,
+    ) -> BoxStream<'static, std::io::Result<Bytes>> {
+        let (bucket, pool) = (self.bucket.clone(), self.pool.clone());
+        let (table, hash) = (table.to_string(), hash.to_string());
+        let offset = first as u64 * CHUNK_SIZE as u64;
+        stream::try_unfold((first, offset), move |(idx, offset)| {
+            let (bucket, pool) = (bucket.clone(), pool.clone());
+            let (table, hash) = (table.clone(), hash.clone());
+            async move {
+                let _lock = bucket.get_lock().await;
+                let chunk = BlobChunks::find()
+                    .filter(BlobChunkColumn::Workspace.eq(table.clone()))
+                    .filter(BlobChunkColumn::Hash.eq(hash.clone()))
+                    .filter(BlobChunkColumn::Idx.eq(idx))
+                    .one(&pool)
+                    .await
+                    .map_err(|e| std::io::Error::new(ErrorKind::Other, e))?;
+                if let Some(chunk) = chunk {
+                    let next = offset + chunk.chunk.len() as u64;
+                    return Ok(Some((Bytes::from(chunk.chunk), (idx + 1, next))));
+                }
+
+                let blob = Blobs::find_by_id((table, hash))
+                    .one(&pool)
+                    .await
+                    .map_err(|e| std::io::Error::new(ErrorKind::Other, e))?;
+                match blob {
+                    Some(blob) if blob.length as u64 == offset => Ok(None),
+                    _ => Err(std::io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!("chunk {idx} of blob is missing"),
+                    )),
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// The chunk that holds the byte `offset` of the blob. The chunks have the same size,
+    /// except for the blobs stored before there were chunks, they are a single chunk.
+    async fn chunk_of(&self, table: &str, hash: &str, offset: u64) -> Result<i32, DbErr> {
+        let idx = i32::try_from(offset / CHUNK_SIZE as u64).unwrap_or(i32::MAX);
+        if idx == 0 {
+            return Ok(0);
+        }
+
+        let exists = BlobChunks::find()
+            .filter(BlobChunkColumn::Workspace.eq(table))
+            .filter(BlobChunkColumn::Hash.eq(hash))
+            .filter(BlobChunkColumn::Idx.eq(idx))
+            .count(&self.pool)
+            .await?
+            > 0;
+        Ok(if exists { idx } else { 0 })
+    }
+
+    async fn delete_chunks<C: ConnectionTrait>(
+        db: &C,
+        table: &str,
+        hashes: &[String],
+    ) -> Result<(), DbErr> {
+        for hashes in hashes.chunks(HASHES_PER_STATEMENT) {
+            BlobChunks::delete_many()
+                .filter(BlobChunkColumn::Workspace.eq(table))
+                .filter(BlobChunkColumn::Hash.is_in(hashes.iter().cloned()))
+                .exec(db)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, table: &str, hash: &str) -> Result<bool, DbErr> {
+        let txn = self.pool.begin().await?;
+        let deleted = Blobs::delete_by_id((table.into(), hash.into()))
+            .exec(&txn)
+            .await?
+            .rows_affected
+            == 1;
+        Self::delete_chunks(&txn, table, &[hash.into()]).await?;
+        txn.commit().await?;
+
+        Ok(deleted)
+    }
+
+    /// The workspaces that have any blob.
+    pub(super) async fn workspaces(&self) -> Result<Vec<String>, DbErr> {
+        #[derive(FromQueryResult)]
+        struct Row {
+            workspace: String,
+        }
+
+        let _lock = self.bucket.get_lock().await;
+        Ok(Blobs::find()
+            .select_only()
+            .column(BlobColumn::Workspace)
+            .distinct()
+            .into_model::<Row>()
+            .all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| row.workspace)
+            .collect())
+    }
+
+    pub(super) async fn gc_states(&self, table: &str) -> Result<Vec<BlobGcState>, DbErr> {
+        let _lock = self.bucket.get_lock().await;
+        Blobs::find()
+            .filter(BlobColumn::Workspace.eq(table))
+            .select_only()
+            .column(BlobColumn::Hash)
+            .column(BlobColumn::Length)
+            .column(BlobColumn::UnreferencedSince)
+            .into_model::<BlobGcState>()
+            .all(&self.pool)
+            .await
+    }
+
+    /// Mark the blobs referenced again and record when the others were found unreferenced.
+    pub(super) async fn update_gc_marks(
+        &self,
+        table: &str,
+        referenced: &[String],
+        unreferenced: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<(), DbErr> {
+        let _lock = self.bucket.get_lock().await;
+        self.mark_unreferenced(table, referenced, None).await?;
+        self.mark_unreferenced(table, unreferenced, Some(now)).await
+    }
+
+    /// Record when the blobs were found unreferenced, `None` marks them referenced again.
+    async fn mark_unreferenced(
+        &self,
+        table: &str,
+        hashes: &[String],
+        since: Option<DateTime<Utc>>,
+    ) -> Result<(), DbErr> {
+        let since = since.map(DateTimeWithTimeZone::from);
+        for hashes in hashes.chunks(HASHES_PER_STATEMENT) {
+            Blobs::update_many()
+                .col_expr(BlobColumn::UnreferencedSince, Expr::value(since))
+                .filter(BlobColumn::Workspace.eq(table))
+                .filter(BlobColumn::Hash.is_in(hashes.iter().cloned()))
+                .exec(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete the blobs whatever their gc marks are, e.g. once they are moved elsewhere.
+    pub(super) async fn delete_many(&self, table: &str, hashes: &[String]) -> Result<u64, DbErr> {
+        let _lock = self.bucket.get_lock().await;
+        let txn = self.pool.begin().await?;
+        let mut deleted = 0;
+        for hashes in hashes.chunks(HASHES_PER_STATEMENT) {
+            deleted += Blobs::delete_many()
+                .filter(BlobColumn::Workspace.eq(table))
+                .filter(BlobColumn::Hash.is_in(hashes.iter().cloned()))
+                .exec(&txn)
+                .await?
+                .rows_affected;
+        }
+        Self::delete_chunks(&txn, table, hashes).await?;
+        txn.commit().await?;
+
+        Ok(deleted)
+    }
+
+    /// Delete the blobs that are still unreferenced since `before` or earlier,
+    /// returns the deleted ones.
+    pub(super) async fn delete_unreferenced(
+        &self,
+        table: &str,
+        hashes: &[String],
+        before: DateTime<Utc>,
+    ) -> Result<Vec<String>, DbErr> {
+        let _lock = self.bucket.get_lock().await;
+        let before = DateTimeWithTimeZone::from(before);
+        let txn = self.pool.begin().await?;
+        let mut deleted = vec![];
+        for hashes in hashes.chunks(HASHES_PER_STATEMENT) {
+            let unreferenced = Condition::all()
+                .add(BlobColumn::Workspace.eq(table))
+                .add(BlobColumn::Hash.is_in(hashes.iter().cloned()))
+                .add(BlobColumn::UnreferencedSince.is_not_null())
+                .add(BlobColumn::UnreferencedSince.lte(before));
+            let found = Blobs::find()
+                .filter(unreferenced.clone())
+                .select_only()
+                .column(BlobColumn::Hash)
+                .column(BlobColumn::Length)
+                .column(BlobColumn::UnreferencedSince)
+                .into_model::<BlobGcState>()
+                .all(&txn)
+                .await?;
+            if found.is_empty() {
+                continue;
+            }
+            Blobs::delete_many().filter(unreferenced).exec(&txn).await?;
+            deleted.extend(found.into_iter().map(|state| state.hash));
+        }
+        Self::delete_chunks(&txn, table, &deleted).await?;
+        txn.commit().await?;
+
+        Ok(deleted)
+    }
+
+    async fn drop(&self, table: &str) -> Result<(), DbErr> {
+        let txn = self.pool.begin().await?;
+        Blobs::delete_many()
+            .filter(BlobColumn::Workspace.eq(table))
+            .exec(&txn)
+            .await?;
+        BlobChunks::delete_many()
+            .filter(BlobChunkColumn::Workspace.eq(table))
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await
+    }
+}
+
+#[async_trait]
+impl BlobStorage for BlobDBStorage {
+    type Read = BoxStream<'static, std::io::Result<Bytes>>;
+
+    async fn check_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool> {
+        let _lock = self.bucket.get_lock().await;
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Ok(exists) = self.exists(&workspace, &id).await {
+            return Ok(exists);
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn get_blob(&self, workspace: Option<String>, id: String) -> JwstResult<Self::Read> {
+        let _lock = self.bucket.get_lock().await;
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Ok(true) = self.exists(&workspace, &id).await {
+            return Ok(self.chunks(&workspace, &id));
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn get_blob_range(
+        &self,
+        workspace: Option<String>,
+        id: String,
+        range: Range<u64>,
+    ) -> JwstResult<Self::Read> {
+        let _lock = self.bucket.get_lock().await;
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Ok(true) = self.exists(&workspace, &id).await {
+            if let Ok(first) = self.chunk_of(&workspace, &id, range.start).await {
+                let offset = first as u64 * CHUNK_SIZE as u64;
+                let chunks = self.chunks_from(&workspace, &id, first);
+                return Ok(slice_stream(chunks, offset, range));
+            }
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn get_metadata(
+        &self,
+        workspace: Option<String>,
+        id: String,
+    ) -> JwstResult<BlobMetadata> {
+        let _lock = self.bucket.get_lock().await;
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Ok(metadata) = self.metadata(&workspace, &id).await {
+            Ok(metadata)
+        } else {
+            Err(JwstError::WorkspaceNotFound(workspace))
+        }
+    }
+
+    async fn put_blob(
+        &self,
+        workspace: Option<String>,
+        stream: impl Stream<Item = Bytes> + Send,
+        content_type: Option<String>,
+    ) -> JwstResult<String> {
+        let workspace = workspace.unwrap_or("__default__".into());
+
+        let (content_type, stream) = sniff_content_type(stream, content_type).await;
+        if let Ok(hash) = self.insert(&workspace, stream, &content_type).await {
+            Ok(hash)
+        } else {
+            Err(JwstError::WorkspaceNotFound(workspace))
+        }
+    }
+
+    async fn delete_blob(&self, workspace_id: Option<String>, id: String) -> JwstResult<bool> {
+        let _lock = self.bucket.get_lock().await;
+        let workspace_id = workspace_id.unwrap_or("__default__".into());
+        if let Ok(success) = self.delete(&workspace_id, &id).await {
+            Ok(success)
+        } else {
+            Err(JwstError::WorkspaceNotFound(workspace_id))
+        }
+    }
+
+    async fn delete_workspace(&self, workspace_id: String) -> JwstResult<()> {
+        let _lock = self.bucket.get_lock().await;
+        if self.drop(&workspace_id).await.is_ok() {
+            Ok(())
+        } else {
+            Err(JwstError::WorkspaceNotFound(workspace_id))
+        }
+    }
+
+    async fn list_blobs(
+        &self,
+        workspace: Option<String>,
+        offset: u64,
+        limit: u64,
+    ) -> JwstResult<Vec<BlobInfo>> {
+        let _lock = self.bucket.get_lock().await;
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Ok(blobs) = self.list(&workspace, offset, limit).await {
+            Ok(blobs)
+        } else {
+            Err(JwstError::WorkspaceNotFound(workspace))
+        }
+    }
+
+    async fn get_usage(&self, workspace: Option<String>) -> JwstResult<BlobUsage> {
+        let _lock = self.bucket.get_lock().await;
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Ok(usage) = self.usage(&workspace).await {
+            Ok(usage)
+        } else {
+            Err(JwstError::WorkspaceNotFound(workspace))
+        }
+    }
+}
+
+#[cfg(test)]
+pub async fn blobs_storage_test(pool: &BlobDBStorage) -> anyhow::Result<()> {
+    use futures::stream::iter;
+
+    // empty table
+    assert_eq!(pool.usage("basic").await?.count, 0);
+
+    // first insert
+    let hash = pool
+        .insert(
+            "basic",
+            iter(vec![Bytes::from(vec![1, 2, 3, 4])]),
+            DEFAULT_CONTENT_TYPE,
+        )
+        .await?;
+    assert_eq!(pool.usage("basic").await?.count, 1);
+
+    let all = Blobs::find()
+        .filter(BlobColumn::Workspace.eq("basic"))
+        .all(&pool.pool)
+        .await?;
+    assert_eq!(
+        all,
+        vec![BlobModel {
+            workspace: "basic".into(),
+            hash: hash.clone(),
+            length: 4,
+            timestamp: all.get(0).unwrap().timestamp,
+            unreferenced_since: None,
+            content_type: DEFAULT_CONTENT_TYPE.into(),
+        }]
+    );
+
+    // the same content in other pieces is the same blob
+    let pieces = vec![Bytes::from(vec![1, 2]), Bytes::from(vec![3, 4])];
+    assert_eq!(
+        pool.insert("basic", iter(pieces), DEFAULT_CONTENT_TYPE)
+            .await?,
+        hash
+    );
+    assert_eq!(pool.usage("basic").await?.count, 1);
+
+    let metadata = pool.metadata("basic", &hash).await?;
+
+    assert_eq!(metadata.size, 4);
+    assert!((metadata.last_modified.timestamp() - Utc::now().timestamp()).abs() < 2);
+
+    pool.insert(
+        "basic",
+        iter(vec![Bytes::from(vec![1, 2])]),
+        DEFAULT_CONTENT_TYPE,
+    )
+    .await?;
+    assert_eq!(pool.usage("basic").await?, BlobUsage { count: 2, size: 6 });
+    let blobs = pool.list("basic", 0, u64::MAX).await?;
+    assert_eq!(blobs.len(), 2);
+    assert_eq!(pool.list("basic", 1, 1).await?, blobs[1..]);
+    assert!(pool.list("basic", 2, 1).await?.is_empty());
+    assert_eq!(pool.usage("empty").await?, BlobUsage::default());
+
+    pool.drop("basic").await?;
+    assert_eq!(pool.usage("basic").await?.count, 0);
+
+    // larger blobs are split into chunks and read back in order
+    let content = (0..CHUNK_SIZE * 2 + 3)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let pieces = content
+        .chunks(100_000)
+        .map(Bytes::copy_from_slice)
+        .collect::<Vec<_>>();
+    let hash = pool
+        .insert("basic", iter(pieces), DEFAULT_CONTENT_TYPE)
+        .await?;
+    assert_eq!(
+        BlobChunks::find()
+            .filter(BlobChunkColumn::Workspace.eq("basic"))
+            .count(&pool.pool)
+            .await?,
+        3
+    );
+    let read = pool
+        .chunks("basic", &hash)
+        .map(|chunk| chunk.unwrap().to_vec())
+        .concat()
+        .await;
+    assert!(read == content);
+    for range in [
+        0..10,
+        CHUNK_SIZE as u64 - 5..CHUNK_SIZE as u64 + 5,
+        10..10 + 2 * CHUNK_SIZE as u64,
+    ] {
+        let read = pool
+            .get_blob_range(Some("basic".into()), hash.clone(), range.clone())
+            .await?
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert!(read == content[range.start as usize..range.end as usize]);
+    }
+    assert_eq!(
+        pool.metadata("basic", &hash).await?.size,
+        content.len() as u64
+    );
+
+    // a missing chunk fails the read instead of ending the content early
+    BlobChunks::delete_many()
+        .filter(BlobChunkColumn::Workspace.eq("basic"))
+        .filter(BlobChunkColumn::Hash.eq(hash.clone()))
+        .filter(BlobChunkColumn::Idx.eq(1))
+        .exec(&pool.pool)
+        .await?;
+    let read = pool.chunks("basic", &hash).collect::<Vec<_>>().await;
+    assert_eq!(read.len(), 2);
+    assert!(read[1].is_err());
+
+    assert!(pool.delete("basic", &hash).await?);
+
+    // only the uploads older than the cutoff are cleaned up, younger ones may still be written
+    let now = Utc::now();
+    let stale = format!("{}-0", upload_key(now - chrono::Duration::days(2)));
+    let running = format!("{}-0", upload_key(now));
+    for key in [&stale, &running] {
+        pool.insert_chunk("basic", key, 0, vec![1, 2, 3]).await?;
+    }
+    let cleaned =
+        BlobDBStorage::clean_up_uploads(&pool.pool, now - chrono::Duration::hours(1)).await?;
+    assert_eq!(cleaned, 1);
+    assert_eq!(
+        BlobChunks::find()
+            .filter(BlobChunkColumn::Workspace.eq("basic"))
+            .all(&pool.pool)
+            .await?
+            .into_iter()
+            .map(|chunk| chunk.hash)
+            .collect::<Vec<_>>(),
+        vec![running.clone()]
+    );
+    BlobDBStorage::delete_chunks(&pool.pool, "basic", &[running]).await?;
+
+    // the content type is detected from the content, or as declared by the upload
+    let png = Bytes::from_static(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+    let hash = pool
+        .put_blob(
+            Some("basic".into()),
+            iter(vec![png]),
+            Some("text/html".into()),
+        )
+        .await?;
+    assert_eq!(
+        pool.get_metadata(Some("basic".into()), hash)
+            .await?
+            .content_type,
+        "image/png"
+    );
+    let hash = pool
+        .put_blob(
+            Some("basic".into()),
+            iter(vec![Bytes::from("text")]),
+            Some("text/plain".into()),
+        )
+        .await?;
+    assert_eq!(
+        pool.get_metadata(Some("basic".into()), hash)
+            .await?
+            .content_type,
+        "text/plain"
+    );
+    pool.drop("basic").await?;
+    assert_eq!(
+        BlobChunks::find()
+            .filter(BlobChunkColumn::Workspace.eq("basic"))
+            .count(&pool.pool)
+            .await?,
+        0
+    );
+
+    Ok(())
+}
@@ -0,0 +1,684 @@
+use super::{utils::BlobHasher, *};
+use jwst::{Base64Engine, URL_SAFE_ENGINE};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::ErrorKind,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tokio_util::io::ReaderStream;
+
+/// Files are written here first and then renamed into place,
+/// the encoded workspace ids never start with a dot.
+const TEMP_DIR: &str = ".tmp";
+
+static TEMP_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Metadata stored next to the content of a blob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Sidecar {
+    size: u64,
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    unreferenced_since: Option<DateTime<Utc>>,
+    #[serde(default = "default_content_type")]
+    content_type: String,
+}
+
+fn default_content_type() -> String {
+    DEFAULT_CONTENT_TYPE.into()
+}
+
+/// Stores the blobs in a directory tree, `<root>/<workspace>/<shard>/<hash>` holds the
+/// content and `<hash>.json` next to it the metadata. The workspace id is encoded and
+/// the shard is the first two characters of the hash.
+#[derive(Clone)]
+pub struct BlobLocalStorage {
+    root: Arc<PathBuf>,
+    usage: UsageCache,
+    locks: BlobLocks,
+}
+
+impl BlobLocalStorage {
+    pub async fn init(root: PathBuf) -> JwstResult<Self> {
+        fs::create_dir_all(root.join(TEMP_DIR))
+            .await
+            .context("Failed to create blob directory")?;
+        Ok(Self {
+            root: Arc::new(root),
+            // the directory is only written by this process
+            usage: UsageCache::new(Duration::MAX),
+            locks: BlobLocks::default(),
+        })
+    }
+
+    fn workspace_dir(&self, workspace: &str) -> PathBuf {
+        self.root.join(URL_SAFE_ENGINE.encode(workspace))
+    }
+
+    /// The path of the content of a blob, ids that are not a hash have no path.
+    fn blob_path(&self, workspace: &str, hash: &str) -> Option<PathBuf> {
+        (hash.len() > 2 && is_hash(hash))
+            .then(|| self.workspace_dir(workspace).join(&hash[..2]).join(hash))
+    }
+
+    fn sidecar_path(path: &Path) -> PathBuf {
+        path.with_extension("json")
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        self.root.join(TEMP_DIR).join(format!(
+            "{}-{}",
+            std::process::id(),
+            TEMP_ID.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// Move a temporary file into place, it is removed if that fails.
+    async fn rename(temp: &Path, path: &Path) -> std::io::Result<()> {
+        let result = async {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::rename(temp, path).await
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(temp).await;
+        }
+        result
+    }
+
+    /// Write the file via a temporary file, so that readers never see a partial file.
+    async fn write_file(&self, path: &Path, data: &[u8]) -> std::io::Result<()> {
+        let temp = self.temp_path();
+
+        let result = async {
+            let mut file = File::create(&temp).await?;
+            file.write_all(data).await?;
+            file.sync_all().await
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp).await;
+            return result;
+        }
+        Self::rename(&temp, path).await
+    }
+
+    /// Write the stream into a temporary file while hashing it,
+    /// returns the file, the hash and the size.
+    async fn write_temp(
+        &self,
+        stream: impl Stream<Item = std::io::Result<Bytes>> + Send,
+    ) -> std::io::Result<(PathBuf, String, u64)> {
+        let temp = self.temp_path();
+
+        let result = async {
+            let mut stream = Box::pin(stream);
+            let mut file = File::create(&temp).await?;
+            let mut hasher = BlobHasher::default();
+            let mut size = 0;
+            while let Some(data) = stream.next().await {
+                let data = data?;
+                hasher.update(&data);
+                size += data.len() as u64;
+                file.write_all(&data).await?;
+            }
+            file.sync_all().await?;
+            Ok((hasher.finalize(), size))
+        }
+        .await;
+
+        match result {
+            Ok((hash, size)) => Ok((temp, hash, size)),
+            Err(e) => {
+                let _ = fs::remove_file(&temp).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn write_sidecar(&self, path: &Path, sidecar: &Sidecar) -> JwstResult<()> {
+        let data = serde_json::to_vec(sidecar).context("Failed to encode blob metadata")?;
+        Ok(self.write_file(&Self::sidecar_path(path), &data).await?)
+    }
+
+    /// Read the metadata of a blob, it falls back to the file metadata
+    /// if the sidecar is missing, e.g. when writing the sidecar failed.
+    async fn read_sidecar(path: &Path) -> std::io::Result<Option<Sidecar>> {
+        if let Ok(data) = fs::read(Self::sidecar_path(path)).await {
+            if let Ok(sidecar) = serde_json::from_slice(&data) {
+                return Ok(Some(sidecar));
+            }
+        }
+
+        match fs::metadata(path).await {
+            Ok(metadata) => Ok(Some(Sidecar {
+                size: metadata.len(),
+                created_at: metadata.modified()?.into(),
+                unreferenced_since: None,
+                content_type: default_content_type(),
+            })),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Move the content written to `temp` into place as the blob of `hash`.
+    async fn insert(
+        &self,
+        workspace: &str,
+        hash: &str,
+        temp: &Path,
+        sidecar: Sidecar,
+    ) -> JwstResult<()> {
+        let Some(path) = self.blob_path(workspace, hash) else {
+            let _ = fs::remove_file(temp).await;
+            return Err(JwstError::StorageError(anyhow::anyhow!(
+                "Invalid blob hash"
+            )));
+        };
+
+        // the gc must not delete the blob between the check and the update of its mark
+        let _lock = self.locks.lock(workspace, hash).await;
+        let exists = Self::read_sidecar(&path)
+            .await
+            .map(|sidecar| sidecar.is_some());
+        if !matches!(exists, Ok(false)) {
+            let _ = fs::remove_file(temp).await;
+            exists?;
+            // uploaded again, it is about to be referenced
+            return self.mark_unreferenced(workspace, hash, None).await;
+        }
+
+        Self::rename(temp, &path).await?;
+        self.usage.added(workspace, sidecar.size);
+        self.write_sidecar(&path, &sidecar).await
+    }
+
+    async fn delete(&self, workspace: &str, hash: &str) -> JwstResult<bool> {
+        let Some(path) = self.blob_path(workspace, hash) else {
+            return Ok(false);
+        };
+
+        let size = Self::read_sidecar(&path).await?.map(|sidecar| sidecar.size);
+        let deleted = match fs::remove_file(&path).await {
+            Ok(()) => {
+                self.usage.removed(workspace, size.unwrap_or_default());
+                true
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(e) => return Err(e.into()),
+        };
+        match fs::remove_file(Self::sidecar_path(&path)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(deleted),
+        }
+    }
+
+    /// All blobs of the workspace with their metadata.
+    async fn entries(&self, workspace: &str) -> JwstResult<Vec<(String, Sidecar)>> {
+        let mut shards = match fs::read_dir(self.workspace_dir(workspace)).await {
+            Ok(shards) => shards,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = vec![];
+        while let Some(shard) = shards.next_entry().await? {
+            if !shard.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut files = fs::read_dir(shard.path()).await?;
+            while let Some(file) = files.next_entry().await? {
+                let path = file.path();
+                // the sidecars have an extension, the contents have none
+                if path.extension().is_some() {
+                    continue;
+                }
+                let Some(hash) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if let Some(sidecar) = Self::read_sidecar(&path).await? {
+                    entries.push((hash.to_string(), sidecar));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// The workspaces that have any blob.
+    pub(super) async fn workspaces(&self) -> JwstResult<Vec<String>> {
+        let mut dirs = fs::read_dir(self.root.as_path()).await?;
+
+        let mut workspaces = vec![];
+        while let Some(dir) = dirs.next_entry().await? {
+            let name = dir.file_name();
+            let Some(workspace) = name
+                .to_str()
+                .and_then(|name| URL_SAFE_ENGINE.decode(name).ok())
+                .and_then(|name| String::from_utf8(name).ok())
+            else {
+                continue;
+            };
+            if dir.file_type().await?.is_dir() && !self.entries(&workspace).await?.is_empty() {
+                workspaces.push(workspace);
+            }
+        }
+
+        Ok(workspaces)
+    }
+
+    pub(super) async fn gc_states(&self, workspace: &str) -> JwstResult<Vec<BlobGcState>> {
+        Ok(self
+            .entries(workspace)
+            .await?
+            .into_iter()
+            .map(|(hash, sidecar)| BlobGcState {
+                hash,
+                length: sidecar.size as i64,
+                unreferenced_since: sidecar.unreferenced_since,
+            })
+            .collect())
+    }
+
+    pub(super) async fn update_gc_marks(
+        &self,
+        workspace: &str,
+        referenced: &[String],
+        unreferenced: &[String],
+        now: DateTime<Utc>,
+    ) -> JwstResult<()> {
+        for hash in referenced {
+            self.mark_unreferenced(workspace, hash, None).await?;
+        }
+        for hash in unreferenced {
+            self.mark_unreferenced(workspace, hash, Some(now)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Record when the blob was found unreferenced, `None` marks it referenced again.
+    async fn mark_unreferenced(
+        &self,
+        workspace: &str,
+        hash: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> JwstResult<()> {
+        let Some(path) = self.blob_path(workspace, hash) else {
+            return Ok(());
+        };
+        match Self::read_sidecar(&path).await? {
+            Some(sidecar) if sidecar.unreferenced_since != since => {
+                self.write_sidecar(
+                    &path,
+                    &Sidecar {
+                        unreferenced_since: since,
+                        ..sidecar
+                    },
+                )
+                .await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete the blobs that are still unreferenced since `before` or earlier,
+    /// returns the deleted ones.
+    pub(super) async fn delete_unreferenced(
+        &self,
+        workspace: &str,
+        hashes: &[String],
+        before: DateTime<Utc>,
+    ) -> JwstResult<Vec<String>> {
+        let mut deleted = vec![];
+        for hash in hashes {
+            let Some(path) = self.blob_path(workspace, hash) else {
+                continue;
+            };
+            // an upload clearing the mark waits until the blob is deleted or kept
+            let _lock = self.locks.lock(workspace, hash).await;
+            let unreferenced = Self::read_sidecar(&path)
+                .await?
+                .and_then(|sidecar| sidecar.unreferenced_since)
+                .map_or(false, |since| since <= before);
+            if unreferenced && self.delete(workspace, hash).await? {
+                deleted.push(hash.clone());
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    /// Move the blobs stored in the database into the directory.
+    pub(super) async fn import_from(&self, db: &BlobDBStorage) -> JwstResult<()> {
+        let workspaces = db
+            .workspaces()
+            .await
+            .context("Failed to query blobs of database")?;
+
+        for workspace in workspaces {
+            let hashes = db
+                .gc_states(&workspace)
+                .await
+                .context("Failed to query blobs of database")?
+                .into_iter()
+                .map(|state| state.hash)
+                .collect::<Vec<_>>();
+
+            for hash in &hashes {
+                let blob = db
+                    .row(&workspace, hash)
+                    .await
+                    .context("Failed to read blob of database")?;
+                let (temp, _, size) = self.write_temp(db.chunks(&workspace, hash)).await?;
+                self.insert(
+                    &workspace,
+                    hash,
+                    &temp,
+                    Sidecar {
+                        size,
+                        created_at: blob.timestamp.with_timezone(&Utc),
+                        unreferenced_since: blob
+                            .unreferenced_since
+                            .map(|since| since.with_timezone(&Utc)),
+                        content_type: blob.content_type,
+                    },
+                )
+                .await?;
+            }
+
+            db.delete_many(&workspace, &hashes)
+                .await
+                .context("Failed to delete blobs of database")?;
+            info!(
+                "moved {} blobs of {} from database to {}",
+                hashes.len(),
+                workspace,
+                self.root.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlobStorage for BlobLocalStorage {
+    type Read = BoxStream<'static, std::io::Result<Bytes>>;
+
+    async fn check_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        let Some(path) = self.blob_path(&workspace, &id) else {
+            return Ok(false);
+        };
+
+        match fs::metadata(path).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_blob(&self, workspace: Option<String>, id: String) -> JwstResult<Self::Read> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Some(path) = self.blob_path(&workspace, &id) {
+            if let Ok(file) = File::open(path).await {
+                return Ok(ReaderStream::new(file).boxed());
+            }
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn get_blob_range(
+        &self,
+        workspace: Option<String>,
+        id: String,
+        range: Range<u64>,
+    ) -> JwstResult<Self::Read> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Some(path) = self.blob_path(&workspace, &id) {
+            if let Ok(mut file) = File::open(path).await {
+                file.seek(std::io::SeekFrom::Start(range.start)).await?;
+                let file = file.take(range.end.saturating_sub(range.start));
+                return Ok(ReaderStream::new(file).boxed());
+            }
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn get_metadata(
+        &self,
+        workspace: Option<String>,
+        id: String,
+    ) -> JwstResult<BlobMetadata> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Some(path) = self.blob_path(&workspace, &id) {
+            if let Ok(Some(sidecar)) = Self::read_sidecar(&path).await {
+                return Ok(BlobMetadata {
+                    size: sidecar.size,
+                    last_modified: sidecar.created_at.naive_local(),
+                    content_type: sidecar.content_type,
+                });
+            }
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn put_blob(
+        &self,
+        workspace: Option<String>,
+        stream: impl Stream<Item = Bytes> + Send,
+        content_type: Option<String>,
+    ) -> JwstResult<String> {
+        let workspace = workspace.unwrap_or("__default__".into());
+
+        let (content_type, stream) = sniff_content_type(stream, content_type).await;
+        let (temp, hash, size) = self.write_temp(stream.map(Ok)).await?;
+        self.insert(
+            &workspace,
+            &hash,
+            &temp,
+            Sidecar {
+                size,
+                created_at: Utc::now(),
+                unreferenced_since: None,
+                content_type,
+            },
+        )
+        .await?;
+
+        Ok(hash)
+    }
+
+    async fn delete_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        self.delete(&workspace, &id).await
+    }
+
+    async fn delete_workspace(&self, workspace_id: String) -> JwstResult<()> {
+        let result = fs::remove_dir_all(self.workspace_dir(&workspace_id)).await;
+        self.usage.cleared(&workspace_id);
+        match result {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list_blobs(
+        &self,
+        workspace: Option<String>,
+        offset: u64,
+        limit: u64,
+    ) -> JwstResult<Vec<BlobInfo>> {
+        let workspace = workspace.unwrap_or("__default__".into());
+
+        let mut entries = self.entries(&workspace).await?;
+        entries.sort_by(|(a_hash, a), (b_hash, b)| {
+            b.created_at
+                .cmp(&a.created_at)
+                .then_with(|| a_hash.cmp(b_hash))
+        });
+
+        Ok(entries
+            .into_iter()
+            .skip(offset.try_into().unwrap_or(usize::MAX))
+            .take(limit.try_into().unwrap_or(usize::MAX))
+            .map(|(hash, sidecar)| BlobInfo {
+                id: hash,
+                size: sidecar.size,
+                last_modified: sidecar.created_at.naive_local(),
+            })
+            .collect())
+    }
+
+    async fn get_usage(&self, workspace: Option<String>) -> JwstResult<BlobUsage> {
+        let workspace = workspace.unwrap_or("__default__".into());
+
+        self.usage
+            .get(&workspace, async {
+                let entries = self.entries(&workspace).await?;
+                Ok(BlobUsage {
+                    count: entries.len() as u64,
+                    size: entries.iter().map(|(_, sidecar)| sidecar.size).sum(),
+                })
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::stream::iter;
+
+    #[tokio::test]
+    async fn local_blobs_storage_test() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("jwst-blobs-{}", std::process::id()));
+        let db = BlobDBStorage::init_pool("sqlite::memory:").await?;
+        let moved = db
+            .put_blob(
+                Some("basic".into()),
+                iter(vec![Bytes::from("moved")]),
+                Some("text/plain".into()),
+            )
+            .await?;
+
+        // the blobs of the database are moved into the directory
+        let storage = BlobLocalStorage::init(root.clone()).await?;
+        storage.import_from(&db).await?;
+        assert!(!db.check_blob(Some("basic".into()), moved.clone()).await?);
+        assert!(
+            storage
+                .check_blob(Some("basic".into()), moved.clone())
+                .await?
+        );
+        assert_eq!(
+            storage
+                .get_metadata(Some("basic".into()), moved.clone())
+                .await?
+                .content_type,
+            "text/plain"
+        );
+
+        let hash = storage
+            .put_blob(Some("basic".into()), iter(vec![Bytes::from("test")]), None)
+            .await?;
+        let content = storage
+            .get_blob(Some("basic".into()), hash.clone())
+            .await?
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(content, b"test");
+        let content = storage
+            .get_blob_range(Some("basic".into()), hash.clone(), 1..3)
+            .await?
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(content, b"es");
+        assert_eq!(
+            storage
+                .get_metadata(Some("basic".into()), hash.clone())
+                .await?
+                .size,
+            4
+        );
+        assert_eq!(
+            storage
+                .get_metadata(Some("basic".into()), hash.clone())
+                .await?
+                .content_type,
+            DEFAULT_CONTENT_TYPE
+        );
+        assert_eq!(
+            storage.get_usage(Some("basic".into())).await?,
+            BlobUsage { count: 2, size: 9 }
+        );
+        assert_eq!(
+            storage
+                .list_blobs(Some("basic".into()), 0, u64::MAX)
+                .await?
+                .len(),
+            2
+        );
+        assert_eq!(storage.workspaces().await?, vec!["basic".to_string()]);
+
+        // ids that are not a hash never reach the file system
+        assert!(
+            !storage
+                .check_blob(Some("basic".into()), "../../etc".into())
+                .await?
+        );
+
+        let now = Utc::now();
+        storage
+            .update_gc_marks("basic", &[], &[hash.clone()], now)
+            .await?;
+        let states = storage.gc_states("basic").await?;
+        let state = states.iter().find(|state| state.hash == hash).unwrap();
+        assert!(state.unreferenced_since.is_some());
+
+        // only the blobs unreferenced since the cutoff are deleted
+        let hashes = [hash.clone(), moved.clone()];
+        let before = now - chrono::Duration::seconds(1);
+        assert!(storage
+            .delete_unreferenced("basic", &hashes, before)
+            .await?
+            .is_empty());
+        assert_eq!(
+            storage.delete_unreferenced("basic", &hashes, now).await?,
+            vec![hash.clone()]
+        );
+        assert!(!storage.check_blob(Some("basic".into()), hash).await?);
+        assert!(storage.check_blob(Some("basic".into()), moved).await?);
+        // the cached usage follows the writes
+        assert_eq!(
+            storage.get_usage(Some("basic".into())).await?,
+            BlobUsage { count: 1, size: 5 }
+        );
+
+        storage.delete_workspace("basic".into()).await?;
+        assert_eq!(
+            storage.get_usage(Some("basic".into())).await?,
+            BlobUsage::default()
+        );
+
+        fs::remove_dir_all(root).await?;
+
+        Ok(())
+    }
+}
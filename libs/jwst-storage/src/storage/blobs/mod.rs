@@ -0,0 +1,442 @@
+mod database;
+mod local;
+#[cfg(feature = "s3")]
+mod s3;
+
+use super::*;
+use bytes::Bytes;
+use database::BlobDBStorage;
+use futures::stream::{self, BoxStream, StreamExt};
+use jwst::{BlobInfo, BlobMetadata, BlobStorage, BlobUsage};
+use local::BlobLocalStorage;
+#[cfg(feature = "s3")]
+use s3::BlobS3Storage;
+use std::{collections::HashMap, ops::Range, time::Instant};
+
+#[cfg(test)]
+pub(super) use database::blobs_storage_test;
+
+/// Where the content of the blobs is stored
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BlobStorageType {
+    /// In the `blobs` table of the database
+    #[default]
+    Database,
+    /// In a directory tree keyed by the hash of the blobs, blobs that are
+    /// stored in the database are moved into the directory on startup.
+    Local(PathBuf),
+    /// In an S3-compatible object storage, requires the `s3` feature. Blobs that
+    /// are stored in the database are moved into the bucket on startup.
+    S3(S3Options),
+}
+
+/// Connection to an S3-compatible object storage
+#[derive(Clone, PartialEq, Eq)]
+pub struct S3Options {
+    /// e.g. `https://s3.us-east-1.amazonaws.com` or `http://localhost:9000`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// The keys of the blobs start with the prefix, so that a bucket can be shared
+    pub prefix: String,
+    /// Address the bucket in the path instead of the host name, as MinIO does by default
+    pub path_style: bool,
+}
+
+impl std::fmt::Debug for S3Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Options")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("prefix", &self.prefix)
+            .field("path_style", &self.path_style)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The blob ids are the url safe base64 of their hash, other ids never reach a file
+/// system or an object key, e.g. ids like `../blob`.
+fn is_hash(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '='))
+}
+
+/// Served for blobs whose content type is unknown
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+/// The signatures of the content types are in the first bytes of the content
+const SNIFF_LEN: usize = 8192;
+
+/// Detect the content type of the blob from its first bytes, the type declared by the
+/// upload is used if the content is not recognized. The returned stream yields the
+/// whole content again.
+async fn sniff_content_type(
+    stream: impl Stream<Item = Bytes> + Send,
+    declared: Option<String>,
+) -> (String, impl Stream<Item = Bytes> + Send) {
+    let mut stream = Box::pin(stream);
+    let mut head = vec![];
+    while head.iter().map(Bytes::len).sum::<usize>() < SNIFF_LEN {
+        match stream.next().await {
+            Some(data) => head.push(data),
+            None => break,
+        }
+    }
+
+    let content_type = infer::get(&head.concat())
+        .map(|kind| kind.mime_type().to_string())
+        .or_else(|| declared.filter(|declared| is_content_type(declared)))
+        .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.into());
+    (content_type, stream::iter(head).chain(stream))
+}
+
+/// Declared content types are served as a header, so only plain `type/subtype` values
+/// with optional parameters are accepted.
+fn is_content_type(value: &str) -> bool {
+    let essence = value.split(';').next().unwrap_or_default().trim();
+    value.len() <= 255
+        && value.bytes().all(|b| b.is_ascii_graphic() || b == b' ')
+        && essence
+            .split_once('/')
+            .map_or(false, |(ty, subtype)| !ty.is_empty() && !subtype.is_empty())
+}
+
+/// A blob without its content, as seen by the garbage collection
+#[derive(FromQueryResult)]
+pub(super) struct BlobGcState {
+    pub(super) hash: String,
+    pub(super) length: i64,
+    pub(super) unreferenced_since: Option<DateTime<Utc>>,
+}
+
+/// The usage of a workspace as listed, with the writes made since then.
+#[derive(Default)]
+struct CachedUsage {
+    usage: Option<(BlobUsage, Instant)>,
+    /// Bumped by each write, so that a listing that overlaps a write is not cached
+    version: u64,
+}
+
+/// Usage of the workspaces for the storages that list all blobs to count them,
+/// a workspace is listed once and then kept current by the writes of this process.
+#[derive(Clone)]
+struct UsageCache {
+    workspaces: Arc<std::sync::Mutex<HashMap<String, CachedUsage>>>,
+    /// The workspaces are listed again after this age, to see the writes of other processes
+    max_age: Duration,
+}
+
+impl UsageCache {
+    fn new(max_age: Duration) -> Self {
+        Self {
+            workspaces: Default::default(),
+            max_age,
+        }
+    }
+
+    async fn get(
+        &self,
+        workspace: &str,
+        list: impl Future<Output = JwstResult<BlobUsage>>,
+    ) -> JwstResult<BlobUsage> {
+        let version = match self.workspaces.lock().unwrap().get(workspace) {
+            Some(CachedUsage {
+                usage: Some((usage, listed)),
+                ..
+            }) if listed.elapsed() < self.max_age => return Ok(*usage),
+            Some(cached) => cached.version,
+            None => 0,
+        };
+
+        let usage = list.await?;
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let cached = workspaces.entry(workspace.to_owned()).or_default();
+        if cached.version == version {
+            cached.usage = Some((usage, Instant::now()));
+        }
+        Ok(usage)
+    }
+
+    fn update(&self, workspace: &str, f: impl FnOnce(&mut BlobUsage)) {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let cached = workspaces.entry(workspace.to_owned()).or_default();
+        cached.version += 1;
+        if let Some((usage, _)) = &mut cached.usage {
+            f(usage);
+        }
+    }
+
+    fn added(&self, workspace: &str, size: u64) {
+        self.update(workspace, |usage| {
+            usage.count += 1;
+            usage.size += size;
+        });
+    }
+
+    fn removed(&self, workspace: &str, size: u64) {
+        self.update(workspace, |usage| {
+            usage.count = usage.count.saturating_sub(1);
+            usage.size = usage.size.saturating_sub(size);
+        });
+    }
+
+    fn cleared(&self, workspace: &str) {
+        self.update(workspace, |usage| *usage = BlobUsage::default());
+    }
+}
+
+/// Locks of single blobs for the storages that can't delete a blob on a condition,
+/// the gc holds the lock of a blob from checking its mark until it is deleted, and an
+/// upload of the blob from checking that it exists until its mark is cleared.
+#[derive(Clone, Default)]
+struct BlobLocks {
+    locks: Arc<std::sync::Mutex<HashMap<(String, String), Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl BlobLocks {
+    async fn lock(&self, workspace: &str, hash: &str) -> tokio::sync::OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            // drop the locks nobody holds or waits for
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks
+                .entry((workspace.to_owned(), hash.to_owned()))
+                .or_default()
+                .clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+#[derive(Clone)]
+pub enum BlobAutoStorage {
+    Database(BlobDBStorage),
+    Local(BlobLocalStorage),
+    #[cfg(feature = "s3")]
+    S3(BlobS3Storage),
+}
+
+impl BlobAutoStorage {
+    pub async fn init_with_pool(
+        pool: DatabaseConnection,
+        bucket: Arc<Bucket>,
+        storage_type: BlobStorageType,
+    ) -> JwstResult<Self> {
+        let db = BlobDBStorage::init_with_pool(pool, bucket).await?;
+        match storage_type {
+            BlobStorageType::Database => Ok(Self::Database(db)),
+            BlobStorageType::Local(root) => {
+                let local = BlobLocalStorage::init(root).await?;
+                local.import_from(&db).await?;
+                Ok(Self::Local(local))
+            }
+            #[cfg(feature = "s3")]
+            BlobStorageType::S3(options) => {
+                let s3 = BlobS3Storage::init(options).await?;
+                s3.import_from(&db).await?;
+                Ok(Self::S3(s3))
+            }
+            #[cfg(not(feature = "s3"))]
+            BlobStorageType::S3(_) => Err(JwstError::StorageError(anyhow::anyhow!(
+                "jwst-storage is built without the s3 feature"
+            ))),
+        }
+    }
+
+    pub async fn init_pool(database: &str, storage_type: BlobStorageType) -> JwstResult<Self> {
+        let is_sqlite = is_sqlite(database);
+        let pool = create_connection(database, is_sqlite).await?;
+
+        Self::init_with_pool(pool, get_bucket(is_sqlite), storage_type).await
+    }
+
+    /// The workspaces that have any blob.
+    pub(super) async fn workspaces(&self) -> JwstResult<Vec<String>> {
+        match self {
+            Self::Database(db) => Ok(db.workspaces().await.context("Failed to query blobs")?),
+            Self::Local(local) => local.workspaces().await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.workspaces().await,
+        }
+    }
+
+    pub(super) async fn gc_states(&self, workspace: &str) -> JwstResult<Vec<BlobGcState>> {
+        match self {
+            Self::Database(db) => Ok(db
+                .gc_states(workspace)
+                .await
+                .context("Failed to query blobs")?),
+            Self::Local(local) => local.gc_states(workspace).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.gc_states(workspace).await,
+        }
+    }
+
+    /// Mark the blobs referenced again and record when the others were found unreferenced.
+    pub(super) async fn update_gc_marks(
+        &self,
+        workspace: &str,
+        referenced: &[String],
+        unreferenced: &[String],
+        now: DateTime<Utc>,
+    ) -> JwstResult<()> {
+        match self {
+            Self::Database(db) => Ok(db
+                .update_gc_marks(workspace, referenced, unreferenced, now)
+                .await
+                .context("Failed to update blobs")?),
+            Self::Local(local) => {
+                local
+                    .update_gc_marks(workspace, referenced, unreferenced, now)
+                    .await
+            }
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => {
+                s3.update_gc_marks(workspace, referenced, unreferenced, now)
+                    .await
+            }
+        }
+    }
+
+    /// Delete the blobs that are still unreferenced since `before` or earlier,
+    /// blobs referred to or uploaded again in the meantime are kept.
+    /// Returns the deleted blobs.
+    pub(super) async fn delete_unreferenced(
+        &self,
+        workspace: &str,
+        hashes: &[String],
+        before: DateTime<Utc>,
+    ) -> JwstResult<Vec<String>> {
+        match self {
+            Self::Database(db) => Ok(db
+                .delete_unreferenced(workspace, hashes, before)
+                .await
+                .context("Failed to delete blobs")?),
+            Self::Local(local) => local.delete_unreferenced(workspace, hashes, before).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.delete_unreferenced(workspace, hashes, before).await,
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStorage for BlobAutoStorage {
+    type Read = BoxStream<'static, std::io::Result<Bytes>>;
+
+    async fn check_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool> {
+        match self {
+            Self::Database(db) => db.check_blob(workspace, id).await,
+            Self::Local(local) => local.check_blob(workspace, id).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.check_blob(workspace, id).await,
+        }
+    }
+
+    async fn get_blob(&self, workspace: Option<String>, id: String) -> JwstResult<Self::Read> {
+        match self {
+            Self::Database(db) => Ok(db.get_blob(workspace, id).await?.boxed()),
+            Self::Local(local) => Ok(local.get_blob(workspace, id).await?.boxed()),
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => Ok(s3.get_blob(workspace, id).await?.boxed()),
+        }
+    }
+
+    async fn get_blob_range(
+        &self,
+        workspace: Option<String>,
+        id: String,
+        range: Range<u64>,
+    ) -> JwstResult<Self::Read> {
+        match self {
+            Self::Database(db) => db.get_blob_range(workspace, id, range).await,
+            Self::Local(local) => local.get_blob_range(workspace, id, range).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.get_blob_range(workspace, id, range).await,
+        }
+    }
+
+    async fn get_metadata(
+        &self,
+        workspace: Option<String>,
+        id: String,
+    ) -> JwstResult<BlobMetadata> {
+        match self {
+            Self::Database(db) => db.get_metadata(workspace, id).await,
+            Self::Local(local) => local.get_metadata(workspace, id).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.get_metadata(workspace, id).await,
+        }
+    }
+
+    async fn put_blob(
+        &self,
+        workspace: Option<String>,
+        stream: impl Stream<Item = Bytes> + Send,
+        content_type: Option<String>,
+    ) -> JwstResult<String> {
+        match self {
+            Self::Database(db) => db.put_blob(workspace, stream, content_type).await,
+            Self::Local(local) => local.put_blob(workspace, stream, content_type).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.put_blob(workspace, stream, content_type).await,
+        }
+    }
+
+    async fn delete_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool> {
+        match self {
+            Self::Database(db) => db.delete_blob(workspace, id).await,
+            Self::Local(local) => local.delete_blob(workspace, id).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.delete_blob(workspace, id).await,
+        }
+    }
+
+    async fn delete_workspace(&self, workspace_id: String) -> JwstResult<()> {
+        match self {
+            Self::Database(db) => db.delete_workspace(workspace_id).await,
+            Self::Local(local) => local.delete_workspace(workspace_id).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.delete_workspace(workspace_id).await,
+        }
+    }
+
+    async fn list_blobs(
+        &self,
+        workspace: Option<String>,
+        offset: u64,
+        limit: u64,
+    ) -> JwstResult<Vec<BlobInfo>> {
+        match self {
+            Self::Database(db) => db.list_blobs(workspace, offset, limit).await,
+            Self::Local(local) => local.list_blobs(workspace, offset, limit).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.list_blobs(workspace, offset, limit).await,
+        }
+    }
+
+    async fn get_usage(&self, workspace: Option<String>) -> JwstResult<BlobUsage> {
+        match self {
+            Self::Database(db) => db.get_usage(workspace).await,
+            Self::Local(local) => local.get_usage(workspace).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.get_usage(workspace).await,
+        }
+    }
+
+    async fn get_blob_url(
+        &self,
+        workspace: Option<String>,
+        id: String,
+    ) -> JwstResult<Option<String>> {
+        match self {
+            Self::Database(db) => db.get_blob_url(workspace, id).await,
+            Self::Local(local) => local.get_blob_url(workspace, id).await,
+            #[cfg(feature = "s3")]
+            Self::S3(s3) => s3.get_blob_url(workspace, id).await,
+        }
+    }
+}
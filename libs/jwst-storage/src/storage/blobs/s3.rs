@@ -0,0 +1,1160 @@
+use super::{utils::BlobHasher, *};
+use jwst::{blob_content_disposition, Base64Engine, URL_SAFE_ENGINE};
+use reqwest::{
+    header::{
+        HeaderName, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED, RANGE,
+    },
+    Client, RequestBuilder, Response, StatusCode,
+};
+use rusty_s3::{
+    actions::{CreateMultipartUpload, ListObjectsContent, ListObjectsV2},
+    Bucket as S3Bucket, Credentials, S3Action, UrlStyle,
+};
+use std::{
+    io::ErrorKind,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Parts of a multipart upload must be at least 5 MiB, except the last one
+const PART_SIZE: usize = 8 * 1024 * 1024;
+/// How long the signed requests and the download urls are valid
+const SIGN_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Blobs larger than a part are uploaded here first, since their key is only known at the end
+const UPLOADS_DIR: &str = ".uploads";
+/// An empty object marks a blob as unreferenced since the object was uploaded
+const MARKS_DIR: &str = ".unreferenced";
+/// How long the usage of a workspace is used before the workspace is listed again
+const USAGE_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+static UPLOAD_ID: AtomicU64 = AtomicU64::new(0);
+
+struct MultipartUpload {
+    key: String,
+    id: String,
+    etags: Vec<String>,
+}
+
+/// An object as seen by a HEAD request
+struct ObjectHead {
+    size: u64,
+    last_modified: DateTime<Utc>,
+    content_type: String,
+}
+
+/// A blob as listed in the bucket
+struct Entry {
+    hash: String,
+    size: u64,
+    last_modified: DateTime<Utc>,
+    unreferenced_since: Option<DateTime<Utc>>,
+}
+
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Stores the blobs in an S3-compatible object storage, the key of a blob is
+/// `<prefix>/<workspace>/<hash>` with the workspace id encoded.
+#[derive(Clone)]
+pub struct BlobS3Storage {
+    bucket: Arc<S3Bucket>,
+    credentials: Arc<Credentials>,
+    client: Client,
+    prefix: String,
+    usage: UsageCache,
+    locks: BlobLocks,
+}
+
+impl BlobS3Storage {
+    pub async fn init(options: S3Options) -> JwstResult<Self> {
+        let endpoint = options.endpoint.parse().context("Invalid s3 endpoint")?;
+        let url_style = if options.path_style {
+            UrlStyle::Path
+        } else {
+            UrlStyle::VirtualHost
+        };
+        let bucket = S3Bucket::new(endpoint, url_style, options.bucket, options.region)
+            .context("Invalid s3 bucket")?;
+        let prefix = match options.prefix.trim_matches('/') {
+            "" => String::new(),
+            prefix => format!("{prefix}/"),
+        };
+
+        let storage = Self {
+            bucket: Arc::new(bucket),
+            credentials: Arc::new(Credentials::new(options.access_key, options.secret_key)),
+            client: Client::new(),
+            prefix,
+            // the bucket may be shared by several servers
+            usage: UsageCache::new(USAGE_MAX_AGE),
+            locks: BlobLocks::default(),
+        };
+
+        // fail on startup if the bucket cannot be read
+        let mut action = storage.bucket.list_objects_v2(storage.credentials());
+        action.with_prefix(storage.prefix.as_str());
+        action.query_mut().insert("max-keys", "1");
+        storage
+            .send(storage.client.get(action.sign(SIGN_DURATION)))
+            .await?;
+
+        Ok(storage)
+    }
+
+    fn credentials(&self) -> Option<&Credentials> {
+        Some(self.credentials.as_ref())
+    }
+
+    fn workspace_prefix(&self, workspace: &str) -> String {
+        format!("{}{}/", self.prefix, URL_SAFE_ENGINE.encode(workspace))
+    }
+
+    /// The key of a blob, ids that are not a hash have no key.
+    fn blob_key(&self, workspace: &str, hash: &str) -> Option<String> {
+        is_hash(hash).then(|| format!("{}{}", self.workspace_prefix(workspace), hash))
+    }
+
+    fn mark_key(&self, workspace: &str, hash: &str) -> String {
+        format!("{}{}/{}", self.workspace_prefix(workspace), MARKS_DIR, hash)
+    }
+
+    /// Send the request, responses other than 2xx are errors.
+    async fn send(&self, request: RequestBuilder) -> JwstResult<Response> {
+        let response = request.send().await.context("Failed to request s3")?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(response)
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(JwstError::StorageError(anyhow::anyhow!(
+                "s3 responded {status}: {body}"
+            )))
+        }
+    }
+
+    /// The headers that the object of a blob is served with.
+    fn blob_headers(hash: &str, content_type: &str) -> Vec<(HeaderName, String)> {
+        vec![
+            (CONTENT_TYPE, content_type.into()),
+            (
+                CONTENT_DISPOSITION,
+                blob_content_disposition(content_type, hash),
+            ),
+        ]
+    }
+
+    /// Size, upload time and content type of the object, `None` if it not exists.
+    async fn head(&self, key: &str) -> JwstResult<Option<ObjectHead>> {
+        let url = self
+            .bucket
+            .head_object(self.credentials(), key)
+            .sign(SIGN_DURATION);
+        let response = self
+            .client
+            .head(url)
+            .send()
+            .await
+            .context("Failed to request s3")?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let header = |name: HeaderName| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                };
+                let size = header(CONTENT_LENGTH)
+                    .and_then(|size| size.parse().ok())
+                    .unwrap_or_default();
+                let last_modified = header(LAST_MODIFIED)
+                    .and_then(|time| DateTime::parse_from_rfc2822(time).ok())
+                    .map(|time| time.with_timezone(&Utc))
+                    .unwrap_or_else(Utc::now);
+                let content_type = header(CONTENT_TYPE)
+                    .unwrap_or(DEFAULT_CONTENT_TYPE)
+                    .to_string();
+                Ok(Some(ObjectHead {
+                    size,
+                    last_modified,
+                    content_type,
+                }))
+            }
+            status => Err(JwstError::StorageError(anyhow::anyhow!(
+                "s3 responded {status} for {key}"
+            ))),
+        }
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        headers: Vec<(HeaderName, String)>,
+    ) -> JwstResult<()> {
+        let url = self
+            .bucket
+            .put_object(self.credentials(), key)
+            .sign(SIGN_DURATION);
+        let request = headers
+            .into_iter()
+            .fold(self.client.put(url), |request, (name, value)| {
+                request.header(name, value)
+            });
+        self.send(request.body(data)).await?;
+        Ok(())
+    }
+
+    /// Delete the object, it succeeds if the object not exists.
+    async fn delete_object(&self, key: &str) -> JwstResult<()> {
+        let url = self
+            .bucket
+            .delete_object(self.credentials(), key)
+            .sign(SIGN_DURATION);
+        self.send(self.client.delete(url)).await?;
+        Ok(())
+    }
+
+    /// Copy the object within the bucket with new headers,
+    /// objects up to 5 GiB can be copied at once.
+    async fn copy_object(
+        &self,
+        from: &str,
+        to: &str,
+        headers: Vec<(HeaderName, String)>,
+    ) -> JwstResult<()> {
+        let source = format!("/{}/{}", self.bucket.name(), from);
+        let mut action = self.bucket.put_object(self.credentials(), to);
+        action
+            .headers_mut()
+            .insert("x-amz-copy-source", source.clone());
+        action
+            .headers_mut()
+            .insert("x-amz-metadata-directive", "REPLACE");
+        let url = action.sign(SIGN_DURATION);
+        let request = self
+            .client
+            .put(url)
+            .header("x-amz-copy-source", source)
+            .header("x-amz-metadata-directive", "REPLACE");
+        let request = headers.into_iter().fold(request, |request, (name, value)| {
+            request.header(name, value)
+        });
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// The objects under the prefix, and the prefixes of the nested objects
+    /// unless `recursive` is set.
+    async fn list(
+        &self,
+        prefix: &str,
+        recursive: bool,
+    ) -> JwstResult<(Vec<ListObjectsContent>, Vec<String>)> {
+        let (mut objects, mut prefixes) = (vec![], vec![]);
+        let mut token: Option<String> = None;
+        loop {
+            let url = {
+                let mut action = self.bucket.list_objects_v2(self.credentials());
+                action.with_prefix(prefix);
+                if !recursive {
+                    action.query_mut().insert("delimiter", "/");
+                }
+                if let Some(token) = &token {
+                    action.with_continuation_token(token.as_str());
+                }
+                action.sign(SIGN_DURATION)
+            };
+
+            let body = self
+                .send(self.client.get(url))
+                .await?
+                .text()
+                .await
+                .context("Failed to read s3 objects")?;
+            let list =
+                ListObjectsV2::parse_response(&body).context("Failed to parse s3 objects")?;
+
+            objects.extend(list.contents);
+            prefixes.extend(list.common_prefixes.into_iter().map(|p| p.prefix));
+            match list.next_continuation_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+
+        Ok((objects, prefixes))
+    }
+
+    /// All blobs of the workspace with their gc marks.
+    async fn entries(&self, workspace: &str) -> JwstResult<Vec<Entry>> {
+        let prefix = self.workspace_prefix(workspace);
+        let (objects, _) = self.list(&prefix, false).await?;
+        let (marks, _) = self.list(&format!("{prefix}{MARKS_DIR}/"), false).await?;
+
+        let marks = marks
+            .into_iter()
+            .filter_map(|mark| {
+                let hash = mark.key.rsplit('/').next()?.to_string();
+                Some((hash, parse_time(&mark.last_modified)?))
+            })
+            .collect::<HashMap<_, _>>();
+
+        Ok(objects
+            .into_iter()
+            .filter_map(|object| {
+                let hash = object.key.strip_prefix(&prefix)?.to_string();
+                Some(Entry {
+                    unreferenced_since: marks.get(&hash).copied(),
+                    size: object.size,
+                    last_modified: parse_time(&object.last_modified)?,
+                    hash,
+                })
+            })
+            .collect())
+    }
+
+    async fn create_upload(&self, workspace: &str) -> JwstResult<MultipartUpload> {
+        let key = format!(
+            "{}{}/{}-{}",
+            self.workspace_prefix(workspace),
+            UPLOADS_DIR,
+            Utc::now().timestamp_nanos(),
+            UPLOAD_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let url = self
+            .bucket
+            .create_multipart_upload(self.credentials(), &key)
+            .sign(SIGN_DURATION);
+        let body = self
+            .send(self.client.post(url))
+            .await?
+            .text()
+            .await
+            .context("Failed to read s3 upload")?;
+        let id = CreateMultipartUpload::parse_response(&body)
+            .context("Failed to parse s3 upload")?
+            .upload_id()
+            .to_string();
+
+        Ok(MultipartUpload {
+            key,
+            id,
+            etags: vec![],
+        })
+    }
+
+    async fn upload_part(&self, upload: &mut MultipartUpload, part: Vec<u8>) -> JwstResult<()> {
+        let number = upload.etags.len() as u16 + 1;
+        let url = self
+            .bucket
+            .upload_part(self.credentials(), &upload.key, number, &upload.id)
+            .sign(SIGN_DURATION);
+        let response = self.send(self.client.put(url).body(part)).await?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .context("Missing etag of s3 upload part")?;
+        upload.etags.push(etag.to_string());
+
+        Ok(())
+    }
+
+    async fn abort_upload(&self, upload: &MultipartUpload) {
+        let url = self
+            .bucket
+            .abort_multipart_upload(self.credentials(), &upload.key, &upload.id)
+            .sign(SIGN_DURATION);
+        if let Err(e) = self.send(self.client.delete(url)).await {
+            warn!("failed to abort s3 upload {}: {}", upload.key, e);
+        }
+    }
+
+    /// Upload the last part and move the object to the key of the blob,
+    /// returns whether the blob was created.
+    async fn finish_upload(
+        &self,
+        mut upload: MultipartUpload,
+        last: Vec<u8>,
+        key: &str,
+        headers: Vec<(HeaderName, String)>,
+    ) -> JwstResult<bool> {
+        let uploaded = async {
+            if !last.is_empty() {
+                self.upload_part(&mut upload, last).await?;
+            }
+            let action = self.bucket.complete_multipart_upload(
+                self.credentials(),
+                &upload.key,
+                &upload.id,
+                upload.etags.iter().map(String::as_str),
+            );
+            let url = action.sign(SIGN_DURATION);
+            self.send(self.client.post(url).body(action.body())).await
+        }
+        .await;
+        if let Err(e) = uploaded {
+            self.abort_upload(&upload).await;
+            return Err(e);
+        }
+
+        let copied = async {
+            let created = self.head(key).await?.is_none();
+            if created {
+                self.copy_object(&upload.key, key, headers).await?;
+            }
+            Ok(created)
+        }
+        .await;
+        self.delete_object(&upload.key).await?;
+
+        copied
+    }
+
+    /// Upload the blob to the key of its hash, returns the hash.
+    async fn upload(
+        &self,
+        workspace: &str,
+        stream: impl Stream<Item = std::io::Result<Bytes>> + Send,
+        content_type: &str,
+    ) -> JwstResult<String> {
+        let mut stream = Box::pin(stream);
+
+        let mut hasher = BlobHasher::default();
+        let mut size = 0;
+        let mut buffer = Vec::with_capacity(PART_SIZE);
+        let mut upload: Option<MultipartUpload> = None;
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    if let Some(upload) = &upload {
+                        self.abort_upload(upload).await;
+                    }
+                    return Err(e.into());
+                }
+            };
+            hasher.update(&chunk);
+            size += chunk.len() as u64;
+            buffer.extend_from_slice(&chunk);
+            if buffer.len() >= PART_SIZE {
+                let part = std::mem::replace(&mut buffer, Vec::with_capacity(PART_SIZE));
+                let mut current = match upload.take() {
+                    Some(upload) => upload,
+                    None => self.create_upload(workspace).await?,
+                };
+                if let Err(e) = self.upload_part(&mut current, part).await {
+                    self.abort_upload(&current).await;
+                    return Err(e);
+                }
+                upload = Some(current);
+            }
+        }
+
+        let hash = hasher.finalize();
+        let key = format!("{}{}", self.workspace_prefix(workspace), hash);
+        let headers = Self::blob_headers(&hash, content_type);
+        // the gc must not delete the blob between the check and the deletion of its mark
+        let _lock = self.locks.lock(workspace, &hash).await;
+        let created = match upload {
+            Some(upload) => self.finish_upload(upload, buffer, &key, headers).await?,
+            None if self.head(&key).await?.is_none() => {
+                self.put_object(&key, buffer, headers).await?;
+                true
+            }
+            None => false,
+        };
+        if created {
+            self.usage.added(workspace, size);
+        }
+        // uploaded again, it is about to be referenced
+        self.delete_object(&self.mark_key(workspace, &hash)).await?;
+
+        Ok(hash)
+    }
+
+    /// The workspaces that have any blob.
+    pub(super) async fn workspaces(&self) -> JwstResult<Vec<String>> {
+        let (_, prefixes) = self.list(&self.prefix, false).await?;
+
+        Ok(prefixes
+            .into_iter()
+            .filter_map(|prefix| {
+                let name = prefix.strip_prefix(&self.prefix)?.trim_end_matches('/');
+                String::from_utf8(URL_SAFE_ENGINE.decode(name).ok()?).ok()
+            })
+            .collect())
+    }
+
+    pub(super) async fn gc_states(&self, workspace: &str) -> JwstResult<Vec<BlobGcState>> {
+        Ok(self
+            .entries(workspace)
+            .await?
+            .into_iter()
+            .map(|entry| BlobGcState {
+                hash: entry.hash,
+                length: entry.size as i64,
+                unreferenced_since: entry.unreferenced_since,
+            })
+            .collect())
+    }
+
+    /// The upload time of a mark is the time since when the blob is unreferenced,
+    /// so it is the clock of the object storage that counts instead of `_now`.
+    pub(super) async fn update_gc_marks(
+        &self,
+        workspace: &str,
+        referenced: &[String],
+        unreferenced: &[String],
+        _now: DateTime<Utc>,
+    ) -> JwstResult<()> {
+        for hash in referenced {
+            self.delete_object(&self.mark_key(workspace, hash)).await?;
+        }
+        for hash in unreferenced {
+            self.put_object(&self.mark_key(workspace, hash), vec![], vec![])
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete the blobs whose mark was uploaded at `before` or earlier,
+    /// returns the deleted ones.
+    ///
+    /// Object storages can't delete on a condition, so the uploads of this process wait
+    /// until the blob is deleted or kept. An upload of the same blob by another server
+    /// sharing the bucket at that moment is not seen.
+    pub(super) async fn delete_unreferenced(
+        &self,
+        workspace: &str,
+        hashes: &[String],
+        before: DateTime<Utc>,
+    ) -> JwstResult<Vec<String>> {
+        let mut deleted = vec![];
+        for hash in hashes {
+            let Some(key) = self.blob_key(workspace, hash) else {
+                continue;
+            };
+            let _lock = self.locks.lock(workspace, hash).await;
+            let mark = self.mark_key(workspace, hash);
+            let unreferenced = self
+                .head(&mark)
+                .await?
+                .map_or(false, |mark| mark.last_modified <= before);
+            if !unreferenced {
+                continue;
+            }
+            // deleting a missing object succeeds, so only existing blobs are reported
+            if let Some(head) = self.head(&key).await? {
+                self.delete_object(&key).await?;
+                self.usage.removed(workspace, head.size);
+                deleted.push(hash.clone());
+            }
+            self.delete_object(&mark).await?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Move the blobs stored in the database into the bucket, the unreferenced
+    /// blobs are marked again and so their grace period starts over.
+    pub(super) async fn import_from(&self, db: &BlobDBStorage) -> JwstResult<()> {
+        let workspaces = db
+            .workspaces()
+            .await
+            .context("Failed to query blobs of database")?;
+
+        for workspace in workspaces {
+            let hashes = db
+                .gc_states(&workspace)
+                .await
+                .context("Failed to query blobs of database")?
+                .into_iter()
+                .map(|state| state.hash)
+                .collect::<Vec<_>>();
+
+            for hash in &hashes {
+                let blob = db
+                    .row(&workspace, hash)
+                    .await
+                    .context("Failed to read blob of database")?;
+                let hash = self
+                    .upload(&workspace, db.chunks(&workspace, hash), &blob.content_type)
+                    .await?;
+                if blob.unreferenced_since.is_some() {
+                    self.put_object(&self.mark_key(&workspace, &hash), vec![], vec![])
+                        .await?;
+                }
+            }
+
+            db.delete_many(&workspace, &hashes)
+                .await
+                .context("Failed to delete blobs of database")?;
+            info!(
+                "moved {} blobs of {} from database to s3",
+                hashes.len(),
+                workspace
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlobStorage for BlobS3Storage {
+    type Read = BoxStream<'static, std::io::Result<Bytes>>;
+
+    async fn check_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        match self.blob_key(&workspace, &id) {
+            Some(key) => Ok(self.head(&key).await?.is_some()),
+            None => Ok(false),
+        }
+    }
+
+    async fn get_blob(&self, workspace: Option<String>, id: String) -> JwstResult<Self::Read> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Some(key) = self.blob_key(&workspace, &id) {
+            let url = self
+                .bucket
+                .get_object(self.credentials(), &key)
+                .sign(SIGN_DURATION);
+            if let Ok(response) = self.send(self.client.get(url)).await {
+                return Ok(response
+                    .bytes_stream()
+                    .map(|chunk| chunk.map_err(|e| std::io::Error::new(ErrorKind::Other, e)))
+                    .boxed());
+            }
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn get_blob_range(
+        &self,
+        workspace: Option<String>,
+        id: String,
+        range: Range<u64>,
+    ) -> JwstResult<Self::Read> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Some(key) = self.blob_key(&workspace, &id) {
+            // an empty range can't be expressed in the range header
+            if range.is_empty() {
+                return Ok(stream::empty().boxed());
+            }
+            let url = self
+                .bucket
+                .get_object(self.credentials(), &key)
+                .sign(SIGN_DURATION);
+            let request = self
+                .client
+                .get(url)
+                .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1));
+            if let Ok(response) = self.send(request).await {
+                return Ok(response
+                    .bytes_stream()
+                    .map(|chunk| chunk.map_err(|e| std::io::Error::new(ErrorKind::Other, e)))
+                    .boxed());
+            }
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    async fn get_metadata(
+        &self,
+        workspace: Option<String>,
+        id: String,
+    ) -> JwstResult<BlobMetadata> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        if let Some(key) = self.blob_key(&workspace, &id) {
+            if let Ok(Some(head)) = self.head(&key).await {
+                return Ok(BlobMetadata {
+                    size: head.size,
+                    last_modified: head.last_modified.naive_local(),
+                    content_type: head.content_type,
+                });
+            }
+        }
+
+        Err(JwstError::WorkspaceNotFound(workspace))
+    }
+
+    /// Blobs up to the part size are uploaded at once, larger ones are uploaded in
+    /// parts while they are read and then copied to the key of their hash.
+    async fn put_blob(
+        &self,
+        workspace: Option<String>,
+        stream: impl Stream<Item = Bytes> + Send,
+        content_type: Option<String>,
+    ) -> JwstResult<String> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        let (content_type, stream) = sniff_content_type(stream, content_type).await;
+        self.upload(&workspace, stream.map(Ok), &content_type).await
+    }
+
+    async fn delete_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        let Some(key) = self.blob_key(&workspace, &id) else {
+            return Ok(false);
+        };
+        let Some(head) = self.head(&key).await? else {
+            return Ok(false);
+        };
+
+        self.delete_object(&key).await?;
+        self.usage.removed(&workspace, head.size);
+        self.delete_object(&self.mark_key(&workspace, &id)).await?;
+        Ok(true)
+    }
+
+    async fn delete_workspace(&self, workspace_id: String) -> JwstResult<()> {
+        let (objects, _) = self
+            .list(&self.workspace_prefix(&workspace_id), true)
+            .await?;
+        let deleted = async {
+            for object in objects {
+                self.delete_object(&object.key).await?;
+            }
+            Ok(())
+        }
+        .await;
+        self.usage.cleared(&workspace_id);
+
+        deleted
+    }
+
+    async fn list_blobs(
+        &self,
+        workspace: Option<String>,
+        offset: u64,
+        limit: u64,
+    ) -> JwstResult<Vec<BlobInfo>> {
+        let workspace = workspace.unwrap_or("__default__".into());
+
+        let mut entries = self.entries(&workspace).await?;
+        entries.sort_by(|a, b| {
+            b.last_modified
+                .cmp(&a.last_modified)
+                .then_with(|| a.hash.cmp(&b.hash))
+        });
+
+        Ok(entries
+            .into_iter()
+            .skip(offset.try_into().unwrap_or(usize::MAX))
+            .take(limit.try_into().unwrap_or(usize::MAX))
+            .map(|entry| BlobInfo {
+                id: entry.hash,
+                size: entry.size,
+                last_modified: entry.last_modified.naive_local(),
+            })
+            .collect())
+    }
+
+    async fn get_usage(&self, workspace: Option<String>) -> JwstResult<BlobUsage> {
+        let workspace = workspace.unwrap_or("__default__".into());
+
+        self.usage
+            .get(&workspace, async {
+                let entries = self.entries(&workspace).await?;
+                Ok(BlobUsage {
+                    count: entries.len() as u64,
+                    size: entries.iter().map(|entry| entry.size).sum(),
+                })
+            })
+            .await
+    }
+
+    /// A presigned url of the object, it is valid for an hour.
+    async fn get_blob_url(
+        &self,
+        workspace: Option<String>,
+        id: String,
+    ) -> JwstResult<Option<String>> {
+        let workspace = workspace.unwrap_or("__default__".into());
+        Ok(self.blob_key(&workspace, &id).map(|key| {
+            self.bucket
+                .get_object(self.credentials(), &key)
+                .sign(SIGN_DURATION)
+                .to_string()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, Method, Uri},
+        response::{IntoResponse, Response as AxumResponse},
+        Router,
+    };
+    use futures::stream::iter;
+    use std::{collections::BTreeMap, net::SocketAddr, sync::Mutex as StdMutex};
+
+    const MOCK_BUCKET: &str = "jwst";
+
+    struct MockObject {
+        data: Vec<u8>,
+        content_type: String,
+        last_modified: DateTime<Utc>,
+    }
+
+    /// Just enough of S3 for the storage, the requests are not authenticated
+    #[derive(Default)]
+    struct MockS3 {
+        objects: BTreeMap<String, MockObject>,
+        uploads: HashMap<String, BTreeMap<u16, Vec<u8>>>,
+    }
+
+    type MockState = Arc<StdMutex<MockS3>>;
+
+    /// The keys are percent-encoded in the path of the requests.
+    fn decode_key(path: &str) -> String {
+        let mut key = vec![];
+        let mut rest = path.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            let escaped = (byte == b'%')
+                .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+                .flatten()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(byte) => {
+                    key.push(byte);
+                    rest = &tail[2..];
+                }
+                None => {
+                    key.push(byte);
+                    rest = tail;
+                }
+            }
+        }
+        String::from_utf8(key).unwrap()
+    }
+
+    fn list_objects(s3: &MockS3, query: &HashMap<String, String>) -> String {
+        let prefix = query.get("prefix").cloned().unwrap_or_default();
+        let delimiter = query.get("delimiter").filter(|d| !d.is_empty());
+
+        let (mut contents, mut prefixes) = (String::new(), Vec::<String>::new());
+        for (key, object) in s3.objects.range(prefix.clone()..) {
+            let Some(name) = key.strip_prefix(&prefix) else {
+                break;
+            };
+            if let Some(pos) = delimiter.and_then(|d| name.find(d.as_str())) {
+                let common = format!("{prefix}{}", &name[..=pos]);
+                if !prefixes.contains(&common) {
+                    prefixes.push(common);
+                }
+                continue;
+            }
+            contents.push_str(&format!(
+                "<Contents><Key>{key}</Key><LastModified>{}</LastModified>\
+                 <ETag>\"{}\"</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass>\
+                 <Owner><ID>jwst</ID><DisplayName>jwst</DisplayName></Owner></Contents>",
+                object
+                    .last_modified
+                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                key.len(),
+                object.data.len(),
+            ));
+        }
+        let prefixes = prefixes
+            .iter()
+            .map(|prefix| format!("<CommonPrefixes><Prefix>{prefix}</Prefix></CommonPrefixes>"))
+            .collect::<String>();
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <ListBucketResult><Name>{MOCK_BUCKET}</Name><Prefix>{prefix}</Prefix>\
+             <KeyCount>0</KeyCount><MaxKeys>1000</MaxKeys><IsTruncated>false</IsTruncated>\
+             {contents}{prefixes}</ListBucketResult>"
+        )
+    }
+
+    async fn mock_handler(
+        State(state): State<MockState>,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> AxumResponse {
+        let path = uri.path().trim_start_matches('/');
+        let key = decode_key(path.split_once('/').map_or("", |(_, key)| key));
+        let query = url::form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        let mut s3 = state.lock().unwrap();
+        match method {
+            Method::GET if key.is_empty() => list_objects(&s3, &query).into_response(),
+            Method::HEAD | Method::GET => {
+                let Some(object) = s3.objects.get(&key) else {
+                    return StatusCode::NOT_FOUND.into_response();
+                };
+                let mut data = object.data.clone();
+                let mut status = StatusCode::OK;
+                if let Some((start, end)) = header("range")
+                    .and_then(|range| range.strip_prefix("bytes="))
+                    .and_then(|range| range.split_once('-'))
+                {
+                    let (start, end): (usize, usize) =
+                        (start.parse().unwrap(), end.parse().unwrap());
+                    data = data[start..=end].to_vec();
+                    status = StatusCode::PARTIAL_CONTENT;
+                }
+                let headers = [
+                    (CONTENT_TYPE, object.content_type.clone()),
+                    (CONTENT_LENGTH, data.len().to_string()),
+                    (LAST_MODIFIED, object.last_modified.to_rfc2822()),
+                ];
+                if method == Method::HEAD {
+                    data.clear();
+                }
+                (status, headers, data).into_response()
+            }
+            Method::PUT if query.contains_key("uploadId") => {
+                let number = query["partNumber"].parse().unwrap();
+                let Some(parts) = s3.uploads.get_mut(&query["uploadId"]) else {
+                    return StatusCode::NOT_FOUND.into_response();
+                };
+                parts.insert(number, body.to_vec());
+                ([(ETAG, format!("\"{number}\""))], "").into_response()
+            }
+            Method::PUT => {
+                let content_type = header("content-type")
+                    .unwrap_or(DEFAULT_CONTENT_TYPE)
+                    .to_string();
+                let data = match header("x-amz-copy-source") {
+                    Some(source) => {
+                        let source = source
+                            .trim_start_matches('/')
+                            .split_once('/')
+                            .map_or("", |(_, key)| key);
+                        match s3.objects.get(source) {
+                            Some(object) => object.data.clone(),
+                            None => return StatusCode::NOT_FOUND.into_response(),
+                        }
+                    }
+                    None => body.to_vec(),
+                };
+                s3.objects.insert(
+                    key,
+                    MockObject {
+                        data,
+                        content_type,
+                        last_modified: Utc::now(),
+                    },
+                );
+                "".into_response()
+            }
+            Method::POST if query.contains_key("uploads") => {
+                let id = s3.uploads.len().to_string();
+                s3.uploads.insert(id.clone(), BTreeMap::new());
+                format!(
+                    "<InitiateMultipartUploadResult><Bucket>{MOCK_BUCKET}</Bucket>\
+                     <Key>{key}</Key><UploadId>{id}</UploadId></InitiateMultipartUploadResult>"
+                )
+                .into_response()
+            }
+            Method::POST if query.contains_key("uploadId") => {
+                let Some(parts) = s3.uploads.remove(&query["uploadId"]) else {
+                    return StatusCode::NOT_FOUND.into_response();
+                };
+                s3.objects.insert(
+                    key,
+                    MockObject {
+                        data: parts.into_values().flatten().collect(),
+                        content_type: DEFAULT_CONTENT_TYPE.into(),
+                        last_modified: Utc::now(),
+                    },
+                );
+                "<CompleteMultipartUploadResult></CompleteMultipartUploadResult>".into_response()
+            }
+            Method::DELETE if query.contains_key("uploadId") => {
+                s3.uploads.remove(&query["uploadId"]);
+                StatusCode::NO_CONTENT.into_response()
+            }
+            Method::DELETE => {
+                s3.objects.remove(&key);
+                StatusCode::NO_CONTENT.into_response()
+            }
+            _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+        }
+    }
+
+    /// Serve the mock on a free port, returns its state and endpoint.
+    fn mock_s3() -> (MockState, String) {
+        let state = MockState::default();
+        let app = Router::new()
+            .fallback(mock_handler)
+            .with_state(state.clone());
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let endpoint = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        (state, endpoint)
+    }
+
+    #[tokio::test]
+    async fn s3_blobs_storage_test() -> anyhow::Result<()> {
+        let (mock, endpoint) = mock_s3();
+        let storage = BlobS3Storage::init(S3Options {
+            endpoint,
+            bucket: MOCK_BUCKET.into(),
+            region: "us-east-1".into(),
+            access_key: "minioadmin".into(),
+            secret_key: "minioadmin".into(),
+            prefix: "test".into(),
+            path_style: true,
+        })
+        .await?;
+
+        // the blobs of the database are moved into the bucket
+        let db = BlobDBStorage::init_pool("sqlite::memory:").await?;
+        let moved = db
+            .put_blob(
+                Some("basic".into()),
+                iter(vec![Bytes::from("moved")]),
+                Some("text/plain".into()),
+            )
+            .await?;
+        storage.import_from(&db).await?;
+        assert!(!db.check_blob(Some("basic".into()), moved.clone()).await?);
+        assert_eq!(
+            storage
+                .get_metadata(Some("basic".into()), moved.clone())
+                .await?
+                .content_type,
+            "text/plain"
+        );
+        storage.delete_blob(Some("basic".into()), moved).await?;
+
+        let small = storage
+            .put_blob(
+                Some("basic".into()),
+                iter(vec![Bytes::from("test")]),
+                Some("text/plain".into()),
+            )
+            .await?;
+        // larger than a part, so that it is uploaded in parts
+        let large = storage
+            .put_blob(
+                Some("basic".into()),
+                iter(vec![Bytes::from(vec![1; PART_SIZE]), Bytes::from("end")]),
+                Some("text/plain".into()),
+            )
+            .await?;
+        assert!(
+            storage
+                .check_blob(Some("basic".into()), large.clone())
+                .await?
+        );
+        assert_eq!(
+            storage
+                .get_metadata(Some("basic".into()), large.clone())
+                .await?
+                .size,
+            PART_SIZE as u64 + 3
+        );
+        // the parts are uploaded to a temporary object that is deleted afterwards
+        assert!(mock
+            .lock()
+            .unwrap()
+            .objects
+            .keys()
+            .all(|key| !key.contains(UPLOADS_DIR)));
+        assert_eq!(
+            storage
+                .get_metadata(Some("basic".into()), large.clone())
+                .await?
+                .content_type,
+            "text/plain"
+        );
+
+        let content = storage
+            .get_blob(Some("basic".into()), small.clone())
+            .await?
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(content, b"test");
+        let content = storage
+            .get_blob_range(Some("basic".into()), small.clone(), 1..3)
+            .await?
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(content, b"es");
+        let url = storage
+            .get_blob_url(Some("basic".into()), small.clone())
+            .await?
+            .unwrap();
+        let response = reqwest::get(url).await?;
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(response.bytes().await?, "test");
+
+        assert_eq!(
+            storage.get_usage(Some("basic".into())).await?,
+            BlobUsage {
+                count: 2,
+                size: PART_SIZE as u64 + 7
+            }
+        );
+        assert_eq!(
+            storage.list_blobs(Some("basic".into()), 1, 1).await?.len(),
+            1
+        );
+        assert!(storage.workspaces().await?.contains(&"basic".to_string()));
+
+        storage
+            .update_gc_marks("basic", &[], &[small.clone()], Utc::now())
+            .await?;
+        let states = storage.gc_states("basic").await?;
+        assert!(states
+            .iter()
+            .any(|state| state.hash == small && state.unreferenced_since.is_some()));
+        // uploading the blob again unmarks it
+        storage
+            .put_blob(Some("basic".into()), iter(vec![Bytes::from("test")]), None)
+            .await?;
+        let states = storage.gc_states("basic").await?;
+        assert!(states
+            .iter()
+            .all(|state| state.unreferenced_since.is_none()));
+
+        // only the marked blobs are deleted, the marks are timed by the object storage
+        let hashes = [small.clone(), large.clone()];
+        let later = Utc::now() + chrono::Duration::hours(1);
+        assert!(storage
+            .delete_unreferenced("basic", &hashes, later)
+            .await?
+            .is_empty());
+        storage
+            .update_gc_marks("basic", &[], &[small.clone()], Utc::now())
+            .await?;
+        assert_eq!(
+            storage.delete_unreferenced("basic", &hashes, later).await?,
+            vec![small.clone()]
+        );
+        assert!(!storage.check_blob(Some("basic".into()), small).await?);
+        assert!(storage.check_blob(Some("basic".into()), large).await?);
+
+        storage.delete_workspace("basic".into()).await?;
+        assert_eq!(
+            storage.get_usage(Some("basic".into())).await?,
+            BlobUsage::default()
+        );
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
-use super::{entities::prelude::*, *};
-use jwst::{sync_encode_update, DocStorage, Workspace};
+use super::{entities::prelude::*, utils::get_index_dir, *};
+use chrono::TimeZone;
+use jwst::{sync_encode_update, DocStorage, Workspace, WorkspaceSnapshot};
 use jwst_storage_migration::{Migrator, MigratorTrait};
+use sea_orm::QueryOrder;
 use std::{
     collections::hash_map::Entry,
     panic::{catch_unwind, AssertUnwindSafe},
@@ -38,16 +40,31 @@ fn migrate_update(updates: Vec<<Docs as EntityTrait>::Model>, doc: Doc) -> Doc {
 type DocsModel = <Docs as EntityTrait>::Model;
 type DocsActiveModel = super::entities::docs::ActiveModel;
 type DocsColumn = <Docs as EntityTrait>::Column;
+type SnapshotsActiveModel = super::entities::snapshots::ActiveModel;
+type SnapshotsColumn = <Snapshots as EntityTrait>::Column;
+
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct SnapshotMetadata {
+    pub id: i32,
+    pub name: String,
+    pub timestamp: DateTime<Utc>,
+}
 
 pub struct DocDBStorage {
     bucket: Arc<Bucket>,
     pub(super) pool: DatabaseConnection,
     workspaces: RwLock<HashMap<String, Workspace>>,
     remote: RwLock<HashMap<String, Sender<Vec<u8>>>>,
+    /// Root of the persisted search indexes, the indexes are kept in memory if not set
+    index_dir: Option<PathBuf>,
 }
 
 impl DocDBStorage {
-    pub async fn init_with_pool(pool: DatabaseConnection, bucket: Arc<Bucket>) -> JwstResult<Self> {
+    pub async fn init_with_pool(
+        pool: DatabaseConnection,
+        bucket: Arc<Bucket>,
+        index_dir: Option<PathBuf>,
+    ) -> JwstResult<Self> {
         Migrator::up(&pool, None)
             .await
             .context("failed to run migration")?;
@@ -57,6 +74,7 @@ impl DocDBStorage {
             pool,
             workspaces: RwLock::new(HashMap::new()),
             remote: RwLock::new(HashMap::new()),
+            index_dir,
         })
     }
 
@@ -64,7 +82,7 @@ impl DocDBStorage {
         let is_sqlite = is_sqlite(database);
         let pool = create_connection(database, is_sqlite).await?;
 
-        Self::init_with_pool(pool, get_bucket(is_sqlite)).await
+        Self::init_with_pool(pool, get_bucket(is_sqlite), None).await
     }
 
     pub fn remote(&self) -> &RwLock<HashMap<String, Sender<Vec<u8>>>> {
@@ -203,6 +221,98 @@ impl DocDBStorage {
         Ok(())
     }
 
+    async fn insert_snapshot<C>(
+        conn: &C,
+        table: &str,
+        snapshot: WorkspaceSnapshot,
+    ) -> JwstResult<SnapshotMetadata>
+    where
+        C: ConnectionTrait,
+    {
+        trace!("start insert snapshot: {table}");
+        let timestamp = Utc
+            .timestamp_millis_opt(snapshot.timestamp)
+            .single()
+            .unwrap_or_else(Utc::now);
+        let id = Snapshots::insert(SnapshotsActiveModel {
+            workspace: Set(table.into()),
+            name: Set(snapshot.name.clone()),
+            timestamp: Set(timestamp.into()),
+            state_vector: Set(snapshot.state_vector),
+            blob: Set(snapshot.doc),
+            ..Default::default()
+        })
+        .exec(conn)
+        .await
+        .context("failed to insert snapshot")?
+        .last_insert_id;
+        trace!("end insert snapshot: {table}, {id}");
+
+        Ok(SnapshotMetadata {
+            id,
+            name: snapshot.name,
+            timestamp,
+        })
+    }
+
+    async fn list_snapshots<C>(conn: &C, table: &str) -> JwstResult<Vec<SnapshotMetadata>>
+    where
+        C: ConnectionTrait,
+    {
+        trace!("start list snapshots: {table}");
+        let snapshots = Snapshots::find()
+            .select_only()
+            .column(SnapshotsColumn::Id)
+            .column(SnapshotsColumn::Name)
+            .column(SnapshotsColumn::Timestamp)
+            .filter(SnapshotsColumn::Workspace.eq(table))
+            .order_by_asc(SnapshotsColumn::Id)
+            .into_model::<SnapshotMetadata>()
+            .all(conn)
+            .await
+            .context("failed to list snapshots")?;
+        trace!("end list snapshots: {table}, {}", snapshots.len());
+        Ok(snapshots)
+    }
+
+    async fn find_snapshot<C>(
+        conn: &C,
+        table: &str,
+        id: i32,
+    ) -> JwstResult<Option<WorkspaceSnapshot>>
+    where
+        C: ConnectionTrait,
+    {
+        trace!("start get snapshot: {table}, {id}");
+        let snapshot = Snapshots::find_by_id(id)
+            .filter(SnapshotsColumn::Workspace.eq(table))
+            .one(conn)
+            .await
+            .context("failed to get snapshot")?
+            .map(|model| WorkspaceSnapshot {
+                name: model.name,
+                timestamp: model.timestamp.timestamp_millis(),
+                state_vector: model.state_vector,
+                doc: model.blob,
+            });
+        trace!("end get snapshot: {table}, {id}");
+        Ok(snapshot)
+    }
+
+    async fn drop_snapshots<C>(conn: &C, table: &str) -> JwstResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        trace!("start drop snapshots: {table}");
+        Snapshots::delete_many()
+            .filter(SnapshotsColumn::Workspace.eq(table))
+            .exec(conn)
+            .await
+            .context("failed to delete snapshots")?;
+        trace!("end drop snapshots: {table}");
+        Ok(())
+    }
+
     async fn create_doc<C>(conn: &C, workspace: &str) -> JwstResult<Doc>
     where
         C: ConnectionTrait,
@@ -229,6 +339,64 @@ impl DocDBStorage {
     }
 }
 
+impl DocDBStorage {
+    /// Store the current state of the workspace as a named snapshot.
+    pub async fn create_snapshot(
+        &self,
+        workspace_id: String,
+        name: String,
+    ) -> JwstResult<SnapshotMetadata> {
+        let workspace = self.get(workspace_id.clone()).await?;
+        let snapshot = workspace.snapshot(name);
+
+        debug!("create snapshot: get lock");
+        let _lock = self.bucket.get_lock().await;
+
+        Self::insert_snapshot(&self.pool, &workspace_id, snapshot)
+            .await
+            .context("Failed to create snapshot")
+            .map_err(JwstError::StorageError)
+    }
+
+    pub async fn snapshots(&self, workspace_id: String) -> JwstResult<Vec<SnapshotMetadata>> {
+        trace!("list snapshots: get lock");
+        let _lock = self.bucket.get_lock().await;
+
+        Self::list_snapshots(&self.pool, &workspace_id)
+            .await
+            .context("Failed to list snapshots")
+            .map_err(JwstError::StorageError)
+    }
+
+    pub async fn snapshot(
+        &self,
+        workspace_id: String,
+        id: i32,
+    ) -> JwstResult<Option<WorkspaceSnapshot>> {
+        trace!("get snapshot: get lock");
+        let _lock = self.bucket.get_lock().await;
+
+        Self::find_snapshot(&self.pool, &workspace_id, id)
+            .await
+            .context("Failed to get snapshot")
+            .map_err(JwstError::StorageError)
+    }
+
+    /// Restore the workspace to a snapshot with a forward update,
+    /// return false if the snapshot not exists.
+    pub async fn restore_snapshot(&self, workspace_id: String, id: i32) -> JwstResult<bool> {
+        let Some(snapshot) = self.snapshot(workspace_id.clone(), id).await? else {
+            return Ok(false);
+        };
+
+        let workspace = self.get(workspace_id.clone()).await?;
+        let update = workspace.restore_snapshot(&snapshot)?;
+        self.write_update(workspace_id, &update).await?;
+
+        Ok(true)
+    }
+}
+
 #[async_trait]
 impl DocStorage for DocDBStorage {
     async fn exists(&self, workspace_id: String) -> JwstResult<bool> {
@@ -262,6 +430,11 @@ impl DocStorage for DocDBStorage {
                     .map_err(JwstError::StorageError)?;
 
                 let ws = Workspace::from_doc(doc, workspace_id);
+                if let Some(index_dir) = &self.index_dir {
+                    if let Err(e) = ws.persist_search_index(get_index_dir(index_dir, &id)) {
+                        warn!("failed to persist search index of {id}, use memory index: {e}");
+                    }
+                }
                 Ok(v.insert(ws).clone())
             }
         }
@@ -304,6 +477,19 @@ impl DocStorage for DocDBStorage {
             .await
             .context("failed to delete workspace")
             .map_err(JwstError::StorageError)?;
+        DocDBStorage::drop_snapshots(&self.pool, &workspace_id)
+            .await
+            .context("failed to delete snapshots")
+            .map_err(JwstError::StorageError)?;
+        if let Some(index_dir) = &self.index_dir {
+            let index_dir = get_index_dir(index_dir, &workspace_id);
+            if index_dir.exists() {
+                tokio::fs::remove_dir_all(index_dir)
+                    .await
+                    .context("failed to delete search index")
+                    .map_err(JwstError::StorageError)?;
+            }
+        }
 
         Ok(())
     }
@@ -357,6 +543,67 @@ pub async fn docs_storage_test(pool: &DocDBStorage) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(test)]
+pub async fn snapshots_storage_test(pool: &DocDBStorage) -> anyhow::Result<()> {
+    use jwst::Block;
+
+    let set_text = |workspace: &Workspace, text: &str| {
+        workspace.with_trx(|mut t| {
+            let block = t.get_blocks().create(&mut t.trx, "block", "text");
+            block.set(&mut t.trx, "text", text);
+            t.trx.encode_update_v1()
+        })
+    };
+    let get_text = |workspace: &Workspace| {
+        workspace.with_trx(|mut t| {
+            t.get_blocks()
+                .get(&t.trx, "block")
+                .and_then(|block: Block| block.get(&t.trx, "text"))
+                .map(|text| text.to_string())
+        })
+    };
+
+    pool.delete("snapshot".into()).await?;
+    let workspace = pool.get("snapshot".into()).await?;
+
+    let update = set_text(&workspace, "first");
+    pool.write_update("snapshot".into(), &update).await?;
+    let first = pool
+        .create_snapshot("snapshot".into(), "first".into())
+        .await?;
+
+    let update = set_text(&workspace, "second");
+    pool.write_update("snapshot".into(), &update).await?;
+    let second = pool
+        .create_snapshot("snapshot".into(), "second".into())
+        .await?;
+
+    assert_eq!(
+        pool.snapshots("snapshot".into())
+            .await?
+            .into_iter()
+            .map(|s| (s.id, s.name))
+            .collect::<Vec<_>>(),
+        vec![(first.id, "first".into()), (second.id, "second".into())]
+    );
+    assert!(pool.snapshot("other".into(), first.id).await?.is_none());
+
+    assert!(pool.restore_snapshot("snapshot".into(), first.id).await?);
+    assert_eq!(get_text(&workspace), Some("first".into()));
+    assert!(!pool.restore_snapshot("snapshot".into(), -1).await?);
+
+    // restore is stored as update, the reloaded doc keeps the restored state
+    let updates = DocDBStorage::all(&pool.pool, "snapshot").await?;
+    let doc = migrate_update(updates, Doc::default());
+    let reloaded = Workspace::from_doc(doc, "snapshot");
+    assert_eq!(get_text(&reloaded), Some("first".into()));
+
+    pool.delete("snapshot".into()).await?;
+    assert!(pool.snapshots("snapshot".into()).await?.is_empty());
+
+    Ok(())
+}
+
 #[cfg(test)]
 #[cfg(feature = "postgres")]
 pub async fn full_migration_test(pool: &DocDBStorage) -> anyhow::Result<()> {
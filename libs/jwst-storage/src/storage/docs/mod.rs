@@ -2,21 +2,28 @@ mod database;
 
 use super::*;
 use database::DocDBStorage;
+use jwst::WorkspaceSnapshot;
+
+pub use database::SnapshotMetadata;
 use tokio::sync::{broadcast::Sender, RwLock};
 
-#[cfg(test)]
-pub(super) use database::docs_storage_test;
 #[cfg(test)]
 #[cfg(feature = "postgres")]
 pub(super) use database::full_migration_test;
+#[cfg(test)]
+pub(super) use database::{docs_storage_test, snapshots_storage_test};
 
 #[derive(Clone)]
 pub struct DocAutoStorage(pub(super) Arc<DocDBStorage>);
 
 impl DocAutoStorage {
-    pub async fn init_with_pool(pool: DatabaseConnection, bucket: Arc<Bucket>) -> JwstResult<Self> {
+    pub async fn init_with_pool(
+        pool: DatabaseConnection,
+        bucket: Arc<Bucket>,
+        index_dir: Option<PathBuf>,
+    ) -> JwstResult<Self> {
         Ok(Self(Arc::new(
-            DocDBStorage::init_with_pool(pool, bucket).await?,
+            DocDBStorage::init_with_pool(pool, bucket, index_dir).await?,
         )))
     }
 
@@ -27,6 +34,33 @@ impl DocAutoStorage {
     pub fn remote(&self) -> &RwLock<HashMap<String, Sender<Vec<u8>>>> {
         self.0.remote()
     }
+
+    /// Store the current state of the workspace as a named snapshot.
+    pub async fn create_snapshot(
+        &self,
+        workspace_id: String,
+        name: String,
+    ) -> JwstResult<SnapshotMetadata> {
+        self.0.create_snapshot(workspace_id, name).await
+    }
+
+    pub async fn snapshots(&self, workspace_id: String) -> JwstResult<Vec<SnapshotMetadata>> {
+        self.0.snapshots(workspace_id).await
+    }
+
+    pub async fn snapshot(
+        &self,
+        workspace_id: String,
+        id: i32,
+    ) -> JwstResult<Option<WorkspaceSnapshot>> {
+        self.0.snapshot(workspace_id, id).await
+    }
+
+    /// Restore the workspace to a snapshot with a forward update,
+    /// return false if the snapshot not exists.
+    pub async fn restore_snapshot(&self, workspace_id: String, id: i32) -> JwstResult<bool> {
+        self.0.restore_snapshot(workspace_id, id).await
+    }
 }
 
 #[async_trait]
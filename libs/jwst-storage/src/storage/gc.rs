@@ -0,0 +1,247 @@
+use super::*;
+use jwst::{BlobGcReport, DocStorage};
+use std::collections::{HashMap, HashSet};
+
+/// Blobs uploaded without a workspace, no doc refers to them
+const DEFAULT_BLOB_WORKSPACE: &str = "__default__";
+
+/// How [JwstStorage::gc_blobs] treats the unreferenced blobs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobGcOptions {
+    /// How long a blob must stay unreferenced before it is deleted, this keeps the blobs
+    /// that are uploaded before their block is created and the blobs of undone deletions.
+    pub grace_period: Duration,
+    /// Only report the blobs, nothing is marked or deleted
+    pub dry_run: bool,
+}
+
+impl Default for BlobGcOptions {
+    fn default() -> Self {
+        Self {
+            grace_period: Duration::from_secs(7 * 24 * 60 * 60),
+            dry_run: false,
+        }
+    }
+}
+
+impl JwstStorage {
+    /// The blobs referred to by the workspace and by any of its snapshots,
+    /// so that restoring a snapshot does not break its embeds.
+    async fn referenced_blobs(&self, workspace_id: &str) -> JwstResult<HashSet<String>> {
+        let mut referenced = self.get_workspace(workspace_id).await?.referenced_ids()?;
+
+        for metadata in self.docs.snapshots(workspace_id.into()).await? {
+            if let Some(snapshot) = self.docs.snapshot(workspace_id.into(), metadata.id).await? {
+                referenced.extend(snapshot.referenced_ids(workspace_id)?);
+            }
+        }
+
+        Ok(referenced)
+    }
+
+    /// Delete the blobs of the workspace that neither a block nor the workspace metadata
+    /// refers to any more.
+    ///
+    /// A blob is first marked when it is found unreferenced, and deleted by a later run
+    /// once it stayed unreferenced for the grace period. Blobs that are referred to again
+    /// or uploaded again are unmarked.
+    pub async fn gc_blobs(
+        &self,
+        workspace_id: &str,
+        options: BlobGcOptions,
+    ) -> JwstResult<BlobGcReport> {
+        let referenced = self.referenced_blobs(workspace_id).await?;
+        let blobs = self.blobs.gc_states(workspace_id).await?;
+
+        let now = Utc::now();
+        // blobs unreferenced since the cutoff or earlier are past the grace period
+        let cutoff = chrono::Duration::from_std(options.grace_period)
+            .ok()
+            .and_then(|grace_period| now.checked_sub_signed(grace_period));
+        let mut report = BlobGcReport {
+            workspace: workspace_id.into(),
+            dry_run: options.dry_run,
+            ..Default::default()
+        };
+        let (mut revived, mut unmarked, mut expired) = (vec![], vec![], vec![]);
+        let mut lengths = HashMap::new();
+
+        for blob in blobs {
+            if referenced.contains(&blob.hash) {
+                report.referenced += 1;
+                if blob.unreferenced_since.is_some() {
+                    revived.push(blob.hash);
+                }
+                continue;
+            }
+            match blob.unreferenced_since {
+                Some(since) if cutoff.map_or(false, |cutoff| since <= cutoff) => {
+                    lengths.insert(blob.hash.clone(), blob.length as u64);
+                    expired.push(blob.hash);
+                }
+                Some(_) => report.pending.push(blob.hash),
+                None => {
+                    unmarked.push(blob.hash.clone());
+                    report.pending.push(blob.hash);
+                }
+            }
+        }
+
+        if options.dry_run {
+            report.deleted = expired;
+        } else {
+            self.blobs
+                .update_gc_marks(workspace_id, &revived, &unmarked, now)
+                .await?;
+            // a blob uploaded or marked again since it was read is kept
+            if let Some(cutoff) = cutoff.filter(|_| !expired.is_empty()) {
+                report.deleted = self
+                    .blobs
+                    .delete_unreferenced(workspace_id, &expired, cutoff)
+                    .await?;
+            }
+        }
+        report.freed = report
+            .deleted
+            .iter()
+            .filter_map(|hash| lengths.get(hash))
+            .sum();
+
+        info!(
+            "gc blobs of {}: {} referenced, {} pending, {} deleted",
+            workspace_id,
+            report.referenced,
+            report.pending.len(),
+            report.deleted.len()
+        );
+
+        Ok(report)
+    }
+
+    /// Run [JwstStorage::gc_blobs] for every workspace that has blobs,
+    /// workspaces that fail are logged and skipped.
+    pub async fn gc_all_blobs(&self, options: BlobGcOptions) -> JwstResult<Vec<BlobGcReport>> {
+        let workspaces = self.blobs.workspaces().await?;
+
+        let mut reports = Vec::with_capacity(workspaces.len());
+        for workspace_id in workspaces {
+            if workspace_id == DEFAULT_BLOB_WORKSPACE {
+                continue;
+            }
+            // the blobs of a missing doc are kept, they are dropped with the workspace
+            if !self.docs.exists(workspace_id.clone()).await? {
+                warn!("skip gc of blobs without workspace: {}", workspace_id);
+                continue;
+            }
+            match self.gc_blobs(&workspace_id, options).await {
+                Ok(report) => reports.push(report),
+                Err(e) => error!("failed to gc blobs of {}: {}", workspace_id, e),
+            }
+        }
+
+        Ok(reports)
+    }
+
+    /// Collect the blobs of all workspaces once per interval, the first run starts
+    /// after one interval. The future never completes, spawn it as a task.
+    pub async fn gc_blobs_every(&self, interval: Duration, options: BlobGcOptions) {
+        let mut ticker = tokio::time::interval(interval);
+        // the first tick completes immediately
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.gc_all_blobs(options).await {
+                error!("failed to gc blobs: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+    use futures::stream::iter;
+    use jwst::BlobStorage;
+
+    #[tokio::test]
+    async fn blobs_gc_test() -> anyhow::Result<()> {
+        let storage = JwstStorage::new("sqlite::memory:").await?;
+        let workspace = storage.create_workspace("gc").await?;
+
+        let kept = storage
+            .blobs()
+            .put_blob(Some("gc".into()), iter(vec![Bytes::from("kept")]), None)
+            .await?;
+        let dropped = storage
+            .blobs()
+            .put_blob(Some("gc".into()), iter(vec![Bytes::from("dropped")]), None)
+            .await?;
+        let avatar = storage
+            .blobs()
+            .put_blob(Some("gc".into()), iter(vec![Bytes::from("avatar")]), None)
+            .await?;
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("page");
+            let image = space.create(&mut t.trx, "image", "affine:embed");
+            image.set(&mut t.trx, "type", "image");
+            image.set(&mut t.trx, "sourceId", kept.clone());
+            // the avatar is only referred to by the workspace metadata
+            t.set_metadata("avatar", avatar.clone());
+        });
+
+        let options = BlobGcOptions {
+            grace_period: Duration::ZERO,
+            dry_run: true,
+        };
+        // a dry run does not mark the blob, so it is never deleted by dry runs
+        for _ in 0..2 {
+            let report = storage.gc_blobs("gc", options).await?;
+            assert_eq!(report.referenced, 2);
+            assert_eq!(report.pending, vec![dropped.clone()]);
+            assert!(report.deleted.is_empty());
+        }
+
+        let options = BlobGcOptions {
+            dry_run: false,
+            ..options
+        };
+        let report = storage.gc_blobs("gc", options).await?;
+        assert_eq!(report.pending, vec![dropped.clone()]);
+        assert!(
+            storage
+                .blobs()
+                .check_blob(Some("gc".into()), dropped.clone())
+                .await?
+        );
+
+        let report = storage.gc_blobs("gc", options).await?;
+        assert_eq!(report.deleted, vec![dropped.clone()]);
+        assert_eq!(report.freed, 7);
+        assert!(
+            !storage
+                .blobs()
+                .check_blob(Some("gc".into()), dropped)
+                .await?
+        );
+        assert!(storage.blobs().check_blob(Some("gc".into()), kept).await?);
+        assert!(
+            storage
+                .blobs()
+                .check_blob(Some("gc".into()), avatar)
+                .await?
+        );
+
+        // unreferenced blobs are kept within the grace period
+        workspace.with_trx(|mut t| t.get_space("page").remove(&mut t.trx, "image"));
+        let options = BlobGcOptions::default();
+        for _ in 0..2 {
+            let report = storage.gc_blobs("gc", options).await?;
+            assert_eq!(report.referenced, 1);
+            assert_eq!(report.pending.len(), 1);
+            assert!(report.deleted.is_empty());
+        }
+
+        Ok(())
+    }
+}
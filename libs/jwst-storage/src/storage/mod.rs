@@ -1,10 +1,15 @@
 mod blobs;
 mod docs;
+mod gc;
 mod test;
 
 use super::*;
 use blobs::BlobAutoStorage;
 use docs::DocAutoStorage;
+
+pub use blobs::{BlobStorageType, S3Options};
+pub use docs::SnapshotMetadata;
+pub use gc::BlobGcOptions;
 use std::{collections::HashMap, time::Instant};
 use tokio::sync::Mutex;
 
@@ -17,14 +22,33 @@ pub struct JwstStorage {
 
 impl JwstStorage {
     pub async fn new(database: &str) -> JwstResult<Self> {
+        Self::new_with_index_dir(database, None).await
+    }
+
+    /// The search indexes of the workspaces are persisted under `index_dir`,
+    /// one directory for each workspace.
+    pub async fn new_with_index_dir(
+        database: &str,
+        index_dir: Option<PathBuf>,
+    ) -> JwstResult<Self> {
+        Self::new_with_options(database, index_dir, BlobStorageType::Database).await
+    }
+
+    /// Like [JwstStorage::new_with_index_dir], the content of the blobs is stored as
+    /// configured by `blob_storage`.
+    pub async fn new_with_options(
+        database: &str,
+        index_dir: Option<PathBuf>,
+        blob_storage: BlobStorageType,
+    ) -> JwstResult<Self> {
         let is_sqlite = is_sqlite(database);
         let pool = create_connection(database, is_sqlite).await?;
         let bucket = get_bucket(is_sqlite);
 
-        let blobs = BlobAutoStorage::init_with_pool(pool.clone(), bucket.clone())
+        let blobs = BlobAutoStorage::init_with_pool(pool.clone(), bucket.clone(), blob_storage)
             .await
             .context("Failed to init blobs")?;
-        let docs = DocAutoStorage::init_with_pool(pool.clone(), bucket.clone())
+        let docs = DocAutoStorage::init_with_pool(pool.clone(), bucket.clone(), index_dir)
             .await
             .context("Failed to init docs")?;
 
@@ -36,7 +60,11 @@ impl JwstStorage {
         })
     }
 
-    pub async fn new_with_sqlite(file: &str) -> JwstResult<Self> {
+    pub async fn new_with_sqlite(
+        file: &str,
+        index_dir: Option<PathBuf>,
+        blob_storage: BlobStorageType,
+    ) -> JwstResult<Self> {
         use std::fs::create_dir;
 
         let data = PathBuf::from("./data");
@@ -44,12 +72,16 @@ impl JwstStorage {
             create_dir(&data).context("Failed to create data directory")?;
         }
 
-        Self::new(&format!(
-            "sqlite:{}?mode=rwc",
-            data.join(PathBuf::from(file).name_str())
-                .with_extension("db")
-                .display()
-        ))
+        Self::new_with_options(
+            &format!(
+                "sqlite:{}?mode=rwc",
+                data.join(PathBuf::from(file).name_str())
+                    .with_extension("db")
+                    .display()
+            ),
+            index_dir,
+            blob_storage,
+        )
         .await
     }
 
@@ -1,20 +1,24 @@
 use super::*;
-use bytes::Bytes;
-use futures::stream::{iter, StreamExt};
 use jwst::{Base64Engine, URL_SAFE_ENGINE};
 use sha2::{Digest, Sha256};
+use std::path::Path;
 
-pub async fn get_hash(stream: impl Stream<Item = Bytes> + Send) -> (String, Vec<u8>) {
-    let mut hasher = Sha256::new();
+/// Hashes the content of a blob while it is streamed, the hash is the id of the blob.
+#[derive(Default)]
+pub struct BlobHasher(Sha256);
 
-    let buffer = stream
-        .flat_map(|buffer| {
-            hasher.update(&buffer);
-            iter(buffer)
-        })
-        .collect()
-        .await;
+impl BlobHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
 
-    let hash = URL_SAFE_ENGINE.encode(hasher.finalize());
-    (hash, buffer)
+    pub fn finalize(self) -> String {
+        URL_SAFE_ENGINE.encode(self.0.finalize())
+    }
+}
+
+/// Directory of the search index of the workspace, the id is encoded
+/// so that any workspace id can be used as a directory name.
+pub fn get_index_dir(root: &Path, workspace_id: &str) -> PathBuf {
+    root.join(URL_SAFE_ENGINE.encode(workspace_id))
 }
@@ -1,9 +1,19 @@
-use super::{constants::sys, utils::JS_INT_RANGE, *};
+use super::{
+    constants::sys,
+    text::{
+        delta_links, delta_to_html, delta_to_markdown, delta_to_string, TextAttributes, TextDelta,
+    },
+    utils::{escape_html, JS_INT_RANGE},
+    *,
+};
 use lib0::any::Any;
-use serde::{Serialize, Serializer};
-use std::collections::HashMap;
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+use utoipa::ToSchema;
 use yrs::{
-    types::ToJson, Array, ArrayPrelim, ArrayRef, Doc, Map, MapPrelim, MapRef, ReadTxn, Transact,
+    types::{text::YChange, Attrs, ToJson, Value},
+    Array, ArrayPrelim, ArrayRef, Doc, Map, MapPrelim, MapRef, ReadTxn, Text, TextPrelim, Transact,
     TransactionMut,
 };
 
@@ -154,7 +164,7 @@ impl Block {
         self.block
             .get(trx, &key)
             .and_then(|v| match v.to_json(trx) {
-                Any::Null | Any::Undefined | Any::Array(_) | Any::Buffer(_) | Any::Map(_) => {
+                Any::Null | Any::Undefined => {
                     error!("get wrong value at key {}", key);
                     None
                 }
@@ -162,23 +172,39 @@ impl Block {
             })
     }
 
+    /// Set a prop, values that cannot be stored are logged and ignored,
+    /// use [Block::try_set] to handle them.
     pub fn set<T>(&self, trx: &mut TransactionMut, key: &str, value: T)
     where
         T: Into<Any>,
     {
+        if let Err(e) = self.try_set(trx, key, value) {
+            error!("failed to set value: {}", e);
+        }
+    }
+
+    /// Set a prop, arrays are stored as Y.Array and maps as Y.Map,
+    /// their items are stored as plain values.
+    pub fn try_set<T>(&self, trx: &mut TransactionMut, key: &str, value: T) -> JwstResult<()>
+    where
+        T: Into<Any>,
+    {
+        let value = value.into();
+        if !matches!(value, Any::Null | Any::Undefined) {
+            // validate before touching the doc, so that a failed set leaves no trace
+            check_nested_value(key, &value)?;
+        }
+
         let key = format!("prop:{key}");
-        match value.into() {
+        match value {
             Any::Bool(bool) => {
                 self.block.insert(trx, key, bool);
-                self.log_update(trx, HistoryOperation::Update);
             }
             Any::String(text) => {
                 self.block.insert(trx, key, text.to_string());
-                self.log_update(trx, HistoryOperation::Update);
             }
             Any::Number(number) => {
                 self.block.insert(trx, key, number);
-                self.log_update(trx, HistoryOperation::Update);
             }
             Any::BigInt(number) => {
                 if JS_INT_RANGE.contains(&number) {
@@ -186,20 +212,159 @@ impl Block {
                 } else {
                     self.block.insert(trx, key, number);
                 }
-                self.log_update(trx, HistoryOperation::Update);
+            }
+            Any::Buffer(buffer) => {
+                self.block.insert(trx, key, Any::Buffer(buffer));
+            }
+            Any::Array(array) => {
+                let array = array.iter().cloned().map(normalize_nested_value).collect();
+                self.block
+                    .insert(trx, key, ArrayPrelim::<Vec<Any>, Any>::from(array));
+            }
+            Any::Map(map) => {
+                let map = (*map)
+                    .into_iter()
+                    .map(|(k, v)| (k, normalize_nested_value(v)))
+                    .collect::<HashMap<_, _>>();
+                self.block.insert(trx, key, MapPrelim::<Any>::from(map));
             }
             Any::Null | Any::Undefined => {
                 self.block.remove(trx, &key);
                 self.log_update(trx, HistoryOperation::Delete);
+                return Ok(());
             }
-            Any::Buffer(_) | Any::Array(_) | Any::Map(_) => {}
         }
+        self.log_update(trx, HistoryOperation::Update);
+
+        Ok(())
+    }
+
+    /// Get the rich text behind a prop as a delta with attributes.
+    ///
+    /// A prop that stores a plain string is returned as a single insert without attributes.
+    pub fn get_text_delta<T>(&self, trx: &T, key: &str) -> Option<Vec<TextDelta>>
+    where
+        T: ReadTxn,
+    {
+        let key = format!("prop:{key}");
+        match self.block.get(trx, &key)? {
+            Value::YText(text) => Some(
+                text.diff(trx, YChange::identity)
+                    .into_iter()
+                    .filter_map(|diff| match diff.insert {
+                        Value::Any(Any::String(insert)) => Some(TextDelta::insert_with_attributes(
+                            insert.to_string(),
+                            diff.attributes
+                                .map(|attributes| {
+                                    (*attributes)
+                                        .into_iter()
+                                        .map(|(key, value)| (key.to_string(), value))
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                        )),
+                        insert => {
+                            warn!("skip unsupported embed in text {}: {:?}", key, insert);
+                            None
+                        }
+                    })
+                    .collect(),
+            ),
+            value => match value.to_json(trx) {
+                Any::String(text) => Some(vec![TextDelta::insert(text.to_string())]),
+                _ => None,
+            },
+        }
+    }
+
+    /// Apply a delta to the rich text behind a prop.
+    ///
+    /// A prop that stores a plain string will be converted to rich text first,
+    /// a prop that not exists will be created as empty rich text.
+    pub fn apply_text_delta(&self, trx: &mut TransactionMut, key: &str, delta: &[TextDelta]) {
+        let key = format!("prop:{key}");
+        let text = match self.block.get(&*trx, &key) {
+            Some(Value::YText(text)) => text,
+            value => {
+                let content = match value.map(|v| v.to_json(&*trx)) {
+                    Some(Any::String(content)) => content.to_string(),
+                    _ => String::new(),
+                };
+                self.block.insert(trx, key, TextPrelim::new(content))
+            }
+        };
+
+        fn to_attrs(attributes: &TextAttributes) -> Attrs {
+            attributes
+                .iter()
+                .map(|(key, value)| (key.as_str().into(), value.clone()))
+                .collect()
+        }
+
+        let mut index = 0;
+        for op in delta {
+            let len = text.len(&*trx);
+            match op {
+                TextDelta::Insert { insert, attributes } => {
+                    let index_at = index.min(len);
+                    match attributes {
+                        Some(attributes) => {
+                            text.insert_with_attributes(trx, index_at, insert, to_attrs(attributes))
+                        }
+                        None => text.insert(trx, index_at, insert),
+                    }
+                    // the length of insert depends on the offset kind of doc
+                    index = index_at + (text.len(&*trx) - len);
+                }
+                TextDelta::Retain { retain, attributes } => {
+                    let retain = (*retain).min(len.saturating_sub(index));
+                    if let Some(attributes) = attributes {
+                        if retain > 0 {
+                            text.format(trx, index, retain, to_attrs(attributes));
+                        }
+                    }
+                    index += retain;
+                }
+                TextDelta::Delete { delete } => {
+                    let delete = (*delete).min(len.saturating_sub(index));
+                    if delete > 0 {
+                        text.remove_range(trx, index, delete);
+                    }
+                }
+            }
+        }
+
+        self.log_update(trx, HistoryOperation::Update);
+    }
+
+    /// Get the text of a prop for indexing, link targets of rich text are included.
+    pub(crate) fn get_search_text<T>(&self, trx: &T, key: &str) -> Option<String>
+    where
+        T: ReadTxn,
+    {
+        self.get_text_delta(trx, key).map(|delta| {
+            let text = delta_to_string(&delta);
+            let links = delta_links(&delta);
+            if links.is_empty() {
+                text
+            } else {
+                format!("{text} {}", links.join(" "))
+            }
+        })
     }
 
     pub fn block_id(&self) -> String {
         self.block_id.clone()
     }
 
+    pub(crate) fn doc(&self) -> Doc {
+        self.doc.clone()
+    }
+
+    pub(crate) fn map_ref(&self) -> &MapRef {
+        &self.block
+    }
+
     // start with a namespace
     // for example: affine:text
     pub fn flavor<T>(&self, trx: &T) -> String
@@ -327,7 +492,8 @@ impl Block {
             .any(|bid| bid == block_id.as_ref())
     }
 
-    pub(crate) fn content<T>(&self, trx: &T) -> HashMap<String, Any>
+    // all props of block, without the `prop:` prefix
+    pub fn content<T>(&self, trx: &T) -> HashMap<String, Any>
     where
         T: ReadTxn,
     {
@@ -429,11 +595,102 @@ impl Block {
             .position(|c| c.to_string(trx) == block_id)
     }
 
+    /// Copy the block with its props and descendants into a space, which can be the
+    /// space of the block itself.
+    ///
+    /// The copied root is not attached to any parent, insert it into a block of the
+    /// target space to place it. Returns the copied root.
+    pub fn clone_into(
+        &self,
+        trx: &mut TransactionMut,
+        space: &Space,
+        strategy: BlockIdStrategy,
+    ) -> JwstResult<Block> {
+        let source = Space::new(trx, self.doc.clone(), &self.id, &self.space_id);
+        let blocks = self.subtree(&*trx, &source);
+        let ids = blocks
+            .iter()
+            .map(|block| (block.block_id.clone(), strategy.new_id(&block.block_id)))
+            .collect::<HashMap<_, _>>();
+        check_conflicts(&*trx, space, ids.values())?;
+        let copies = read_copies(&*trx, &blocks)?;
+
+        Ok(write_copies(trx, &copies, space, &ids, false))
+    }
+
+    /// Move the block with its descendants to another space, the block ids are kept.
+    ///
+    /// The block is detached from its parent, insert it into a block of the target space
+    /// to place it. Nothing is changed if any block of the subtree exists in the target space
+    /// or can't be copied. Returns the block in the target space.
+    pub fn move_to(&self, trx: &mut TransactionMut, space: &Space) -> JwstResult<Block> {
+        if space.space_id() == self.space_id {
+            return Ok(self.clone());
+        }
+
+        let source = Space::new(trx, self.doc.clone(), &self.id, &self.space_id);
+        let blocks = self.subtree(&*trx, &source);
+        let ids = blocks
+            .iter()
+            .map(|block| (block.block_id.clone(), block.block_id.clone()))
+            .collect::<HashMap<_, _>>();
+        check_conflicts(&*trx, space, ids.values())?;
+        // everything is read and checked before the first change, so a failed move
+        // leaves the doc untouched
+        let copies = read_copies(&*trx, &blocks)?;
+
+        if let Some(parent) = self
+            .parent(&*trx)
+            .and_then(|parent| source.get(&*trx, parent))
+        {
+            parent.remove_children(trx, self);
+        }
+        let root = write_copies(trx, &copies, space, &ids, true);
+        for block in &blocks {
+            source.remove(trx, &block.block_id);
+        }
+
+        Ok(root)
+    }
+
+    /// The block and its descendants in depth-first order, missing children are skipped.
+    fn subtree<T>(&self, trx: &T, space: &Space) -> Vec<Block>
+    where
+        T: ReadTxn,
+    {
+        let mut blocks = vec![];
+        let mut visited = HashSet::new();
+        let mut stack = vec![self.clone()];
+        while let Some(block) = stack.pop() {
+            // a broken doc may contain cycles
+            if !visited.insert(block.block_id.clone()) {
+                continue;
+            }
+            stack.extend(
+                block
+                    .children(trx)
+                    .iter()
+                    .rev()
+                    .filter_map(|id| space.get(trx, id)),
+            );
+            blocks.push(block);
+        }
+        blocks
+    }
+
     pub fn to_markdown<T>(&self, trx: &T, state: &mut MarkdownState) -> Option<String>
     where
         T: ReadTxn,
     {
-        match self.get(trx, "text").map(|t| t.to_string()) {
+        let text = self.get_text_delta(trx, "text").map(|delta| {
+            if self.flavor(trx) == "affine:code" {
+                delta_to_string(&delta)
+            } else {
+                delta_to_markdown(&delta)
+            }
+        });
+
+        match text {
             Some(text) => match self.flavor(trx).as_str() {
                 "affine:code" => {
                     state.numbered_count = 0;
@@ -446,7 +703,12 @@ impl Block {
                     state.numbered_count = 0;
                     match self.get(trx, "type").map(|v| v.to_string()).as_deref() {
                         Some(
-                            head @ "h1" | head @ "h2" | head @ "h3" | head @ "h4" | head @ "h5",
+                            head @ "h1"
+                            | head @ "h2"
+                            | head @ "h3"
+                            | head @ "h4"
+                            | head @ "h5"
+                            | head @ "h6",
                         ) => Some(format!(
                             "{} {}\n",
                             "#".repeat(head[1..].parse().unwrap()),
@@ -521,6 +783,255 @@ impl Block {
             },
         }
     }
+
+    pub fn to_html<T>(&self, trx: &T, state: &mut HtmlState) -> Option<String>
+    where
+        T: ReadTxn,
+    {
+        let text = self.get_text_delta(trx, "text").map(|delta| {
+            if self.flavor(trx) == "affine:code" {
+                escape_html(&delta_to_string(&delta))
+            } else {
+                delta_to_html(&delta)
+            }
+        });
+
+        match text {
+            Some(text) => match self.flavor(trx).as_str() {
+                "affine:code" => {
+                    let prefix = state.switch_list(None);
+                    match self.get(trx, "language").map(|v| v.to_string()).as_deref() {
+                        Some(language) => Some(format!(
+                            "{prefix}<pre><code class=\"language-{}\">{text}</code></pre>\n",
+                            escape_html(language)
+                        )),
+                        None => Some(format!("{prefix}<pre><code>{text}</code></pre>\n")),
+                    }
+                }
+                format @ "affine:paragraph" => {
+                    let prefix = state.switch_list(None);
+                    let text = text.replace('\n', "<br>");
+                    match self.get(trx, "type").map(|v| v.to_string()).as_deref() {
+                        Some(
+                            head @ "h1"
+                            | head @ "h2"
+                            | head @ "h3"
+                            | head @ "h4"
+                            | head @ "h5"
+                            | head @ "h6",
+                        ) => Some(format!("{prefix}<{head}>{text}</{head}>\n")),
+                        Some("quote") => Some(format!("{prefix}<blockquote>{text}</blockquote>\n")),
+                        Some("text") => Some(format!("{prefix}<p>{text}</p>\n")),
+                        r#type @ Some(_) | r#type @ None => {
+                            if let Some(r#type) = r#type {
+                                warn!("Unprocessed format: {format}, {}", r#type);
+                            } else {
+                                warn!("Unprocessed format: {format}");
+                            }
+                            Some(format!("{prefix}<p>{text}</p>\n"))
+                        }
+                    }
+                }
+                format @ "affine:list" => {
+                    match self.get(trx, "type").map(|v| v.to_string()).as_deref() {
+                        Some("numbered") => {
+                            let prefix = state.switch_list(Some(HtmlList::Numbered));
+                            Some(format!("{prefix}<li>{text}</li>\n"))
+                        }
+                        Some("todo") => {
+                            let prefix = state.switch_list(Some(HtmlList::Todo));
+                            let clicked = self
+                                .get(trx, "checked")
+                                .map(|v| v.to_string() == "true")
+                                .unwrap_or(false);
+                            Some(format!(
+                                "{prefix}<li><input type=\"checkbox\" disabled{}> {text}</li>\n",
+                                if clicked { " checked" } else { "" }
+                            ))
+                        }
+                        Some("bulleted") => {
+                            let prefix = state.switch_list(Some(HtmlList::Bulleted));
+                            Some(format!("{prefix}<li>{text}</li>\n"))
+                        }
+                        r#type @ Some("text") | r#type @ Some(_) | r#type @ None => {
+                            let prefix = state.switch_list(None);
+                            if let Some(r#type) = r#type {
+                                warn!("Unprocessed format: {format}, {}", r#type);
+                            } else {
+                                warn!("Unprocessed format: {format}");
+                            }
+                            Some(format!("{prefix}<p>{text}</p>\n"))
+                        }
+                    }
+                }
+                format => {
+                    let prefix = state.switch_list(None);
+                    warn!("Unprocessed format: {format}");
+                    Some(format!("{prefix}<p>{text}</p>\n"))
+                }
+            },
+            None => match self.flavor(trx).as_str() {
+                "affine:divider" => {
+                    let prefix = state.switch_list(None);
+                    Some(format!("{prefix}<hr>\n"))
+                }
+                "affine:embed" => {
+                    let prefix = state.switch_list(None);
+                    let html = match self.get(trx, "type").map(|v| v.to_string()).as_deref() {
+                        Some("image") => self.get(trx, "sourceId").map(|v| {
+                            format!(
+                                "{prefix}<img src=\"/api/workspace/{}/blob/{}\">\n",
+                                escape_html(&self.id),
+                                escape_html(&v.to_string())
+                            )
+                        }),
+                        _ => None,
+                    };
+                    // keep the closing tag of the previous list
+                    html.or_else(|| (!prefix.is_empty()).then_some(prefix))
+                }
+                format => {
+                    warn!("Unprocessed format: {format}");
+                    None
+                }
+            },
+        }
+    }
+}
+
+/// How the blocks copied by [Block::clone_into] get their ids.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockIdStrategy {
+    /// Keep the ids of the source blocks, the target space must not contain them
+    Keep,
+    /// Generate random ids
+    #[default]
+    Random,
+    /// Append a suffix to the ids of the source blocks
+    Suffix(String),
+}
+
+impl BlockIdStrategy {
+    fn new_id(&self, block_id: &str) -> String {
+        match self {
+            Self::Keep => block_id.to_owned(),
+            Self::Random => nanoid!(),
+            Self::Suffix(suffix) => format!("{block_id}{suffix}"),
+        }
+    }
+}
+
+fn check_conflicts<'a, T>(
+    trx: &T,
+    space: &Space,
+    mut ids: impl Iterator<Item = &'a String>,
+) -> JwstResult<()>
+where
+    T: ReadTxn,
+{
+    match ids.find(|id| space.exists(trx, id)) {
+        Some(id) => Err(JwstError::BlockExists(space.space_id(), id.clone())),
+        None => Ok(()),
+    }
+}
+
+enum PropValue {
+    Text(Vec<TextDelta>),
+    Value(Any),
+}
+
+/// The content of a block to copy.
+struct BlockCopy {
+    block_id: String,
+    flavour: String,
+    version: [i32; 2],
+    created: u64,
+    props: Vec<(String, PropValue)>,
+    children: Vec<String>,
+}
+
+/// Read the blocks to copy and check that their props can be written, so that
+/// [write_copies] can't fail halfway.
+fn read_copies<T>(trx: &T, blocks: &[Block]) -> JwstResult<Vec<BlockCopy>>
+where
+    T: ReadTxn,
+{
+    blocks
+        .iter()
+        .map(|block| {
+            let mut props = vec![];
+            for (key, value) in block.block.iter(trx) {
+                let Some(key) = key.strip_prefix("prop:") else {
+                    continue;
+                };
+                let value = match value {
+                    Value::YText(_) => match block.get_text_delta(trx, key) {
+                        Some(delta) => PropValue::Text(delta),
+                        None => continue,
+                    },
+                    value => {
+                        let value = value.to_json(trx);
+                        if !matches!(value, Any::Null | Any::Undefined) {
+                            check_nested_value(key, &value)?;
+                        }
+                        PropValue::Value(value)
+                    }
+                };
+                props.push((key.to_owned(), value));
+            }
+
+            Ok(BlockCopy {
+                block_id: block.block_id.clone(),
+                flavour: block.flavor(trx),
+                version: block.version(trx).map(|v| v as i32),
+                created: block.created(trx),
+                props,
+                children: block.children(trx),
+            })
+        })
+        .collect()
+}
+
+/// Create the copies of the blocks in the target space, `copies` starts with the root
+/// and `ids` maps every source id to the id of its copy. Returns the copied root.
+fn write_copies(
+    trx: &mut TransactionMut,
+    copies: &[BlockCopy],
+    space: &Space,
+    ids: &HashMap<String, String>,
+    keep_created: bool,
+) -> Block {
+    let mut blocks = HashMap::new();
+    for source in copies {
+        let copy = space.create(trx, &ids[&source.block_id], source.flavour.as_str());
+        copy.block
+            .insert(trx, sys::VERSION, ArrayPrelim::from(source.version));
+        if keep_created {
+            copy.block.insert(trx, sys::CREATED, source.created as f64);
+        }
+        for (key, value) in &source.props {
+            match value {
+                PropValue::Text(delta) => copy.apply_text_delta(trx, key, delta),
+                // checked by read_copies
+                PropValue::Value(value) => copy.set(trx, key, value.clone()),
+            }
+        }
+        blocks.insert(source.block_id.clone(), copy);
+    }
+
+    for source in copies {
+        let copy = &blocks[&source.block_id];
+        for child in &source.children {
+            if let Some(child) = blocks.get(child) {
+                copy.push_children(trx, child);
+            }
+        }
+    }
+
+    blocks
+        .remove(&copies[0].block_id)
+        .expect("subtree contains the root")
 }
 
 #[derive(Default)]
@@ -528,6 +1039,96 @@ pub struct MarkdownState {
     numbered_count: usize,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum HtmlList {
+    Numbered,
+    Bulleted,
+    Todo,
+}
+
+impl HtmlList {
+    fn open(&self) -> &'static str {
+        match self {
+            Self::Numbered => "<ol>",
+            Self::Bulleted => "<ul>",
+            Self::Todo => "<ul class=\"todo\">",
+        }
+    }
+
+    fn close(&self) -> &'static str {
+        match self {
+            Self::Numbered => "</ol>",
+            Self::Bulleted | Self::Todo => "</ul>",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct HtmlState {
+    list: Option<HtmlList>,
+}
+
+impl HtmlState {
+    fn switch_list(&mut self, list: Option<HtmlList>) -> String {
+        if self.list == list {
+            return String::new();
+        }
+
+        let mut html = String::new();
+        if let Some(prev) = self.list {
+            html.push_str(prev.close());
+            html.push('\n');
+        }
+        if let Some(next) = list {
+            html.push_str(next.open());
+            html.push('\n');
+        }
+        self.list = list;
+
+        html
+    }
+
+    /// Close the list that is still open, must be called after the last block.
+    pub fn finish(&mut self) -> String {
+        self.switch_list(None)
+    }
+}
+
+/// Undefined has no json representation, so it cannot be read back by the clients.
+fn check_nested_value(path: &str, value: &Any) -> JwstResult<()> {
+    match value {
+        Any::Undefined => Err(JwstError::InvalidValue(
+            path.into(),
+            "undefined is not allowed in nested values".into(),
+        )),
+        Any::Array(array) => array
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, v)| check_nested_value(&format!("{path}[{i}]"), v)),
+        Any::Map(map) => map
+            .iter()
+            .try_for_each(|(k, v)| check_nested_value(&format!("{path}.{k}"), v)),
+        _ => Ok(()),
+    }
+}
+
+/// Store integers in js safe range as numbers, same as top level props.
+fn normalize_nested_value(value: Any) -> Any {
+    match value {
+        Any::BigInt(number) if JS_INT_RANGE.contains(&number) => Any::Number(number as f64),
+        Any::Array(array) => {
+            Any::Array(array.iter().cloned().map(normalize_nested_value).collect())
+        }
+        Any::Map(map) => Any::Map(Box::new(
+            (*map)
+                .into_iter()
+                .map(|(k, v)| (k, normalize_nested_value(v)))
+                .collect(),
+        )),
+        value => value,
+    }
+}
+
 impl Serialize for Block {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -619,6 +1220,140 @@ mod test {
         });
     }
 
+    #[test]
+    fn set_nested_value() {
+        let workspace = Workspace::new("test");
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+
+            let block = space.create(&mut t.trx, "test", "affine:text");
+
+            let tags = Any::Array(Box::new([
+                Any::String("a".into()),
+                Any::BigInt(1),
+                Any::Null,
+            ]));
+            let config = Any::Map(Box::new(HashMap::from([
+                ("width".to_owned(), Any::BigInt(100)),
+                (
+                    "inner".to_owned(),
+                    Any::Map(Box::new(HashMap::from([(
+                        "enabled".to_owned(),
+                        Any::Bool(true),
+                    )]))),
+                ),
+            ])));
+
+            block.try_set(&mut t.trx, "tags", tags).unwrap();
+            block.try_set(&mut t.trx, "config", config).unwrap();
+            block
+                .try_set(&mut t.trx, "buffer", Any::Buffer(Box::new([1, 2, 3])))
+                .unwrap();
+
+            assert_eq!(
+                block.get(&t.trx, "tags"),
+                Some(Any::Array(Box::new([
+                    Any::String("a".into()),
+                    Any::Number(1.0),
+                    Any::Null,
+                ])))
+            );
+            assert_eq!(
+                block.get(&t.trx, "config"),
+                Some(Any::Map(Box::new(HashMap::from([
+                    ("width".to_owned(), Any::Number(100.0)),
+                    (
+                        "inner".to_owned(),
+                        Any::Map(Box::new(HashMap::from([(
+                            "enabled".to_owned(),
+                            Any::Bool(true),
+                        )]))),
+                    ),
+                ]))))
+            );
+            assert_eq!(
+                block.get(&t.trx, "buffer"),
+                Some(Any::Buffer(Box::new([1, 2, 3])))
+            );
+
+            let json = serde_json::to_value(&block).unwrap();
+            assert_eq!(json["prop:tags"], serde_json::json!(["a", 1.0, null]));
+            assert_eq!(json["prop:config"]["inner"]["enabled"], true);
+
+            let invalid = Any::Map(Box::new(HashMap::from([(
+                "missing".to_owned(),
+                Any::Undefined,
+            )])));
+            assert!(block.try_set(&mut t.trx, "invalid", invalid).is_err());
+            assert_eq!(block.get(&t.trx, "invalid"), None);
+        });
+    }
+
+    #[test]
+    fn text_delta() {
+        let workspace = Workspace::new("test");
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+
+            let block = space.create(&mut t.trx, "test", "affine:paragraph");
+            block.set(&mut t.trx, "type", "text");
+            block.set(&mut t.trx, "text", "hello world");
+
+            // plain string is converted to rich text
+            block.apply_text_delta(
+                &mut t.trx,
+                "text",
+                &[
+                    TextDelta::Retain {
+                        retain: 6,
+                        attributes: None,
+                    },
+                    TextDelta::Retain {
+                        retain: 5,
+                        attributes: Some(HashMap::from([("bold".into(), Any::Bool(true))])),
+                    },
+                    TextDelta::insert_with_attributes(
+                        "!",
+                        HashMap::from([("italic".into(), Any::Bool(true))]),
+                    ),
+                ],
+            );
+
+            assert_eq!(
+                block.get_text_delta(&t.trx, "text").unwrap(),
+                vec![
+                    TextDelta::insert("hello "),
+                    TextDelta::insert_with_attributes(
+                        "world",
+                        HashMap::from([("bold".into(), Any::Bool(true))])
+                    ),
+                    TextDelta::insert_with_attributes(
+                        "!",
+                        HashMap::from([("italic".into(), Any::Bool(true))])
+                    ),
+                ]
+            );
+            assert_eq!(
+                block.get(&t.trx, "text").unwrap().to_string(),
+                "hello world!"
+            );
+            assert_eq!(
+                block.to_markdown(&t.trx, &mut MarkdownState::default()),
+                Some("hello **world**_!_\n".into())
+            );
+
+            block.apply_text_delta(
+                &mut t.trx,
+                "text",
+                &[TextDelta::Delete { delete: 6 }, TextDelta::insert("new ")],
+            );
+
+            assert_eq!(block.get(&t.trx, "text").unwrap().to_string(), "new world!");
+        });
+    }
+
     #[test]
     fn insert_remove_children() {
         let workspace = Workspace::new("text");
@@ -758,4 +1493,103 @@ mod test {
             unreachable!();
         }
     }
+
+    #[test]
+    fn clone_and_move_subtree() {
+        let workspace = Workspace::new("test");
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("template");
+            let page = space.create(&mut t.trx, "page", "affine:page");
+            page.set(&mut t.trx, "title", "template");
+            let frame = space.create(&mut t.trx, "frame", "affine:frame");
+            page.push_children(&mut t.trx, &frame);
+            for id in ["a", "b"] {
+                let paragraph = space.create(&mut t.trx, id, "affine:paragraph");
+                paragraph.apply_text_delta(&mut t.trx, "text", &[TextDelta::insert(id)]);
+                frame.push_children(&mut t.trx, &paragraph);
+            }
+
+            let target = t.get_space("page1");
+            let copy = page
+                .clone_into(&mut t.trx, &target, BlockIdStrategy::Suffix("-copy".into()))
+                .unwrap();
+            assert_eq!(copy.block_id(), "page-copy");
+            assert_eq!(copy.parent(&t.trx), None);
+            assert_eq!(
+                copy.get(&t.trx, "title"),
+                Some(Any::String("template".into()))
+            );
+            assert_eq!(copy.children(&t.trx), vec!["frame-copy"]);
+            let frame = target.get(&t.trx, "frame-copy").unwrap();
+            assert_eq!(frame.parent(&t.trx), Some("page-copy".into()));
+            assert_eq!(frame.children(&t.trx), vec!["a-copy", "b-copy"]);
+            assert_eq!(
+                target
+                    .get(&t.trx, "b-copy")
+                    .and_then(|b| b.get_text_delta(&t.trx, "text")),
+                Some(vec![TextDelta::insert("b")])
+            );
+
+            // ids are kept in another space, but conflict in the same space
+            let page2 = t.get_space("page2");
+            assert!(page
+                .clone_into(&mut t.trx, &page2, BlockIdStrategy::Keep)
+                .is_ok());
+            assert!(matches!(
+                page.clone_into(&mut t.trx, &space, BlockIdStrategy::Keep),
+                Err(JwstError::BlockExists(..))
+            ));
+            let random = page
+                .clone_into(&mut t.trx, &space, BlockIdStrategy::Random)
+                .unwrap();
+            assert_ne!(random.block_id(), "page");
+            assert_eq!(random.children(&t.trx).len(), 1);
+        });
+
+        workspace.with_trx(|mut t| {
+            let source = t.get_space("template");
+            let target = t.get_space("page1");
+            let page = target.get(&t.trx, "page-copy").unwrap();
+            let frame = source.get(&t.trx, "frame").unwrap();
+
+            let moved = frame.move_to(&mut t.trx, &target).unwrap();
+            page.push_children(&mut t.trx, &moved);
+
+            assert_eq!(moved.block_id(), "frame");
+            assert_eq!(moved.parent(&t.trx), Some("page-copy".into()));
+            assert_eq!(moved.children(&t.trx), vec!["a", "b"]);
+            assert_eq!(
+                target.get(&t.trx, "a").and_then(|a| a.parent(&t.trx)),
+                Some("frame".into())
+            );
+            for id in ["frame", "a", "b"] {
+                assert!(!source.exists(&t.trx, id));
+            }
+            assert!(source
+                .get(&t.trx, "page")
+                .unwrap()
+                .children(&t.trx)
+                .is_empty());
+
+            // the subtree exists in the target space
+            let page = source.get(&t.trx, "page").unwrap();
+            let page2 = t.get_space("page2");
+            assert!(page.move_to(&mut t.trx, &page2).is_err());
+            assert!(source.exists(&t.trx, "page"));
+
+            // nothing is changed if a block of the subtree can't be copied
+            let bad = source.create(&mut t.trx, "bad", "affine:paragraph");
+            bad.block.insert(
+                &mut t.trx,
+                "prop:bad",
+                MapPrelim::<Any>::from(HashMap::from([("x".to_owned(), Any::Undefined)])),
+            );
+            page.push_children(&mut t.trx, &bad);
+            let page3 = t.get_space("page3");
+            assert!(page.move_to(&mut t.trx, &page3).is_err());
+            assert!(!page3.exists(&t.trx, "page"));
+            assert_eq!(page.children(&t.trx), vec!["bad"]);
+        });
+    }
 }
@@ -26,3 +26,43 @@ pub mod space {
     /// `space:meta`
     pub const META: &str = "space:meta";
 }
+
+/// The fields that every block has in the search index,
+/// they cannot be used as search index fields of workspace.
+pub mod search {
+    /// `block_id`, the space id and block id joined by `:`
+    pub const BLOCK_ID: &str = "block_id";
+
+    /// `space_id`
+    pub const SPACE_ID: &str = "space_id";
+
+    /// `flavour`
+    pub const FLAVOUR: &str = "flavour";
+
+    /// `created`
+    pub const CREATED: &str = "created";
+
+    /// `updated`
+    pub const UPDATED: &str = "updated";
+
+    pub const RESERVED: [&str; 5] = [BLOCK_ID, SPACE_ID, FLAVOUR, CREATED, UPDATED];
+}
+
+/// The props and text attributes that refer to other blocks, pages or blobs.
+pub mod reference {
+    /// `sourceId`, the blob or block shown by an embed
+    pub const SOURCE_ID: &str = "sourceId";
+
+    /// `reference`, the text attribute of a page link, a map with the `pageId`
+    pub const TEXT_ATTRIBUTE: &str = "reference";
+
+    /// `pageId`
+    pub const PAGE_ID: &str = "pageId";
+
+    /// The props that hold the ids of referenced blocks or pages,
+    /// either a string or an array of strings.
+    pub const PROPS: [&str; 3] = [PAGE_ID, "reference", "references"];
+
+    /// The keys of the workspace metadata that hold the ids of blobs of the workspace
+    pub const METADATA_BLOBS: [&str; 1] = ["avatar"];
+}
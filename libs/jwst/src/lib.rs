@@ -1,23 +1,38 @@
 mod block;
 mod history;
+mod schema;
 mod space;
+mod text;
 mod types;
+mod undo;
 mod utils;
 mod workspaces;
 
 pub mod constants;
 
-pub use block::Block;
+pub use block::{Block, BlockIdStrategy};
 pub use history::{
     parse_history, parse_history_client, BlockHistory, HistoryOperation, RawHistory,
 };
+pub use schema::{FlavourSchema, PropSchema, PropType, SchemaRegistry, SchemaViolation};
 pub use space::Space;
+pub use text::{TextAttributes, TextDelta};
 pub use tracing::{debug, error, info, log::LevelFilter, trace, warn};
-pub use types::{BlobMetadata, BlobStorage, DocStorage, JwstError, JwstResult};
+pub use types::{
+    blob_content_disposition, read_blob_ranges, BlobGcReport, BlobInfo, BlobMetadata, BlobRanges,
+    BlobRangesBody, BlobStorage, BlobUsage, DocStorage, JwstError, JwstResult,
+};
+pub use undo::{UndoManager, UndoOptions, UndoStack, UndoStackEvent};
 pub use utils::{sync_encode_update, Base64DecodeError, Base64Engine, URL_SAFE_ENGINE};
-pub use workspaces::{MapSubscription, Workspace, WorkspaceMetadata, WorkspaceTransaction};
+pub use workspaces::{
+    BlockDiff, BlockEvent, BlockSummary, ChildrenChange, MapSubscription, PluginImpl,
+    PluginRegister, PropChange, Reference, ReferenceKind, SearchAnalyzer, SpaceDiff, Workspace,
+    WorkspaceDiff, WorkspaceMetadata, WorkspaceSnapshot, WorkspaceTransaction,
+};
 #[cfg(feature = "workspace-search")]
-pub use workspaces::{SearchResult, SearchResults};
+pub use workspaces::{
+    SearchMode, SearchOptions, SearchOrder, SearchResult, SearchResults, Suggestion,
+};
 
 #[inline]
 pub fn print_versions(pkg_name: &str, pkg_version: &str) {
@@ -0,0 +1,455 @@
+use lib0::any::Any;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// The type of a block prop, checked against the stored value.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PropType {
+    Boolean,
+    Number,
+    String,
+    /// Plain string or rich text
+    Text,
+    Array,
+    Map,
+    Buffer,
+    Any,
+}
+
+impl PropType {
+    fn accept(&self, value: &Any) -> bool {
+        match self {
+            Self::Boolean => matches!(value, Any::Bool(_)),
+            Self::Number => matches!(value, Any::Number(_) | Any::BigInt(_)),
+            Self::String | Self::Text => matches!(value, Any::String(_)),
+            Self::Array => matches!(value, Any::Array(_)),
+            Self::Map => matches!(value, Any::Map(_)),
+            Self::Buffer => matches!(value, Any::Buffer(_)),
+            Self::Any => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct PropSchema {
+    #[serde(rename = "type")]
+    pub kind: PropType,
+    #[serde(default)]
+    pub required: bool,
+    /// Value set on the block when the prop is missing on creation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub default: Option<Any>,
+    /// Allowed values of a string prop, empty means any value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<String>,
+}
+
+impl PropSchema {
+    pub fn new(kind: PropType) -> Self {
+        Self {
+            kind,
+            required: false,
+            default: None,
+            choices: vec![],
+        }
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub fn default_value<T: Into<Any>>(mut self, value: T) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    pub fn choices<I, S>(mut self, choices: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.choices = choices.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// Declares the props that blocks of a flavour can hold.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct FlavourSchema {
+    pub flavour: String,
+    #[serde(default)]
+    pub props: HashMap<String, PropSchema>,
+    /// Accept props that are not declared in `props`
+    #[serde(default)]
+    pub additional_props: bool,
+}
+
+impl FlavourSchema {
+    pub fn new<S: Into<String>>(flavour: S) -> Self {
+        Self {
+            flavour: flavour.into(),
+            props: HashMap::new(),
+            additional_props: false,
+        }
+    }
+
+    pub fn prop<S: Into<String>>(mut self, key: S, prop: PropSchema) -> Self {
+        self.props.insert(key.into(), prop);
+        self
+    }
+
+    pub fn additional_props(mut self) -> Self {
+        self.additional_props = true;
+        self
+    }
+
+    /// Default values of the props that have one.
+    pub fn defaults(&self) -> HashMap<String, Any> {
+        self.props
+            .iter()
+            .filter_map(|(key, prop)| prop.default.clone().map(|value| (key.clone(), value)))
+            .collect()
+    }
+
+    /// Check the props of a block, the keys are without the `prop:` prefix.
+    pub fn validate(&self, props: &HashMap<String, Any>) -> Vec<SchemaViolation> {
+        let mut violations = self.check_values(props);
+
+        for (key, prop) in &self.props {
+            let missing = props
+                .get(key)
+                .map_or(true, |value| matches!(value, Any::Null | Any::Undefined));
+            if prop.required && missing {
+                violations.push(SchemaViolation::new(key, "required prop is missing"));
+            }
+        }
+
+        violations.sort_by(|a, b| a.prop.cmp(&b.prop));
+        violations
+    }
+
+    /// Check the props changed on an existing block, a null value removes the prop.
+    /// Props that are not changed are left as they are, even if they don't match the schema.
+    pub fn validate_update(&self, props: &HashMap<String, Any>) -> Vec<SchemaViolation> {
+        let mut violations = self.check_values(props);
+
+        for (key, value) in props {
+            let removed = matches!(value, Any::Null | Any::Undefined);
+            if removed && self.props.get(key).map_or(false, |prop| prop.required) {
+                violations.push(SchemaViolation::new(key, "required prop cannot be removed"));
+            }
+        }
+
+        violations.sort_by(|a, b| a.prop.cmp(&b.prop));
+        violations
+    }
+
+    /// Check the type and choices of the values, null values are skipped.
+    fn check_values(&self, props: &HashMap<String, Any>) -> Vec<SchemaViolation> {
+        let mut violations = vec![];
+
+        for (key, value) in props {
+            if matches!(value, Any::Null | Any::Undefined) {
+                continue;
+            }
+            match self.props.get(key) {
+                Some(prop) if !prop.kind.accept(value) => {
+                    violations.push(SchemaViolation::new(
+                        key,
+                        format!("expect {:?}, got {}", prop.kind, value),
+                    ));
+                }
+                Some(PropSchema { choices, .. }) if !choices.is_empty() => {
+                    if let Any::String(value) = value {
+                        if !choices
+                            .iter()
+                            .any(|choice| choice.as_str() == value.as_ref())
+                        {
+                            violations.push(SchemaViolation::new(
+                                key,
+                                format!("expect one of {choices:?}, got {value:?}"),
+                            ));
+                        }
+                    }
+                }
+                Some(_) => {}
+                None if self.additional_props => {}
+                None => violations.push(SchemaViolation::new(
+                    key,
+                    format!("unknown prop of {}", self.flavour),
+                )),
+            }
+        }
+
+        violations
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// Prop key without the `prop:` prefix
+    pub prop: String,
+    pub message: String,
+}
+
+impl SchemaViolation {
+    fn new<K: Into<String>, M: Into<String>>(prop: K, message: M) -> Self {
+        Self {
+            prop: prop.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// The schemas of block flavours, blocks of flavours that are not registered are not checked.
+///
+/// The default registry contains the affine flavours and `text`,
+/// the flavour that keck gives to blocks created without one.
+#[derive(Debug, Clone)]
+pub struct SchemaRegistry {
+    flavours: HashMap<String, FlavourSchema>,
+}
+
+impl Default for SchemaRegistry {
+    fn default() -> Self {
+        let text = || PropSchema::new(PropType::Text).default_value("");
+
+        let mut registry = Self::empty();
+        registry.register(FlavourSchema::new("text").additional_props());
+        registry.register(FlavourSchema::new("affine:page").prop("title", text()));
+        registry.register(
+            FlavourSchema::new("affine:surface").prop("elements", PropSchema::new(PropType::Map)),
+        );
+        registry.register(
+            FlavourSchema::new("affine:frame")
+                .prop("xywh", PropSchema::new(PropType::String))
+                .prop("background", PropSchema::new(PropType::String)),
+        );
+        registry.register(
+            FlavourSchema::new("affine:paragraph")
+                .prop(
+                    "type",
+                    PropSchema::new(PropType::String)
+                        .default_value("text")
+                        .choices(["text", "quote", "h1", "h2", "h3", "h4", "h5", "h6"]),
+                )
+                .prop("text", text()),
+        );
+        registry.register(
+            FlavourSchema::new("affine:list")
+                .prop(
+                    "type",
+                    PropSchema::new(PropType::String)
+                        .default_value("bulleted")
+                        .choices(["bulleted", "numbered", "todo", "toggle"]),
+                )
+                .prop(
+                    "checked",
+                    PropSchema::new(PropType::Boolean).default_value(false),
+                )
+                .prop("text", text()),
+        );
+        registry.register(
+            FlavourSchema::new("affine:code")
+                .prop(
+                    "language",
+                    PropSchema::new(PropType::String).default_value("Plain Text"),
+                )
+                .prop("text", text()),
+        );
+        registry.register(FlavourSchema::new("affine:divider"));
+        registry.register(
+            FlavourSchema::new("affine:embed")
+                .prop(
+                    "type",
+                    PropSchema::new(PropType::String)
+                        .required()
+                        .choices(["image", "video", "audio", "file"]),
+                )
+                .prop("sourceId", PropSchema::new(PropType::String).required())
+                .prop("caption", PropSchema::new(PropType::String))
+                .prop("width", PropSchema::new(PropType::Number))
+                .prop("height", PropSchema::new(PropType::Number)),
+        );
+
+        registry
+    }
+}
+
+impl SchemaRegistry {
+    /// Registry without any flavour, no block is checked until flavours are registered.
+    pub fn empty() -> Self {
+        Self {
+            flavours: HashMap::new(),
+        }
+    }
+
+    /// Register a flavour, replacing the schema with the same flavour.
+    pub fn register(&mut self, schema: FlavourSchema) {
+        self.flavours.insert(schema.flavour.clone(), schema);
+    }
+
+    pub fn get(&self, flavour: &str) -> Option<&FlavourSchema> {
+        self.flavours.get(flavour)
+    }
+
+    /// All registered schemas, sorted by flavour.
+    pub fn schemas(&self) -> Vec<&FlavourSchema> {
+        let mut schemas = self.flavours.values().collect::<Vec<_>>();
+        schemas.sort_by(|a, b| a.flavour.cmp(&b.flavour));
+        schemas
+    }
+
+    /// Check the props of a block against the schema of its flavour.
+    pub fn validate(
+        &self,
+        flavour: &str,
+        props: &HashMap<String, Any>,
+    ) -> Result<(), Vec<SchemaViolation>> {
+        Self::into_result(self.get(flavour).map(|schema| schema.validate(props)))
+    }
+
+    /// Check the props changed on an existing block against the schema of its flavour.
+    pub fn validate_update(
+        &self,
+        flavour: &str,
+        props: &HashMap<String, Any>,
+    ) -> Result<(), Vec<SchemaViolation>> {
+        Self::into_result(
+            self.get(flavour)
+                .map(|schema| schema.validate_update(props)),
+        )
+    }
+
+    fn into_result(violations: Option<Vec<SchemaViolation>>) -> Result<(), Vec<SchemaViolation>> {
+        match violations {
+            Some(violations) if !violations.is_empty() => Err(violations),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validate_props() {
+        let registry = SchemaRegistry::default();
+
+        let props = HashMap::from([
+            ("type".to_owned(), Any::String("todo".into())),
+            ("checked".to_owned(), Any::Bool(true)),
+            ("text".to_owned(), Any::String("hello".into())),
+        ]);
+        assert_eq!(registry.validate("affine:list", &props), Ok(()));
+
+        let props = HashMap::from([
+            ("type".to_owned(), Any::String("unknown".into())),
+            ("checked".to_owned(), Any::String("yes".into())),
+            ("color".to_owned(), Any::String("red".into())),
+        ]);
+        assert_eq!(
+            registry
+                .validate("affine:list", &props)
+                .unwrap_err()
+                .into_iter()
+                .map(|v| v.prop)
+                .collect::<Vec<_>>(),
+            vec!["checked", "color", "type"]
+        );
+
+        let props = HashMap::from([("type".to_owned(), Any::String("image".into()))]);
+        assert_eq!(
+            registry.validate("affine:embed", &props),
+            Err(vec![SchemaViolation::new(
+                "sourceId",
+                "required prop is missing"
+            )])
+        );
+
+        assert_eq!(registry.validate("affine:database", &props), Ok(()));
+        assert_eq!(registry.validate("text", &props), Ok(()));
+    }
+
+    #[test]
+    fn validate_update() {
+        let registry = SchemaRegistry::default();
+
+        // only the changed props are checked
+        let props = HashMap::from([("sourceId".to_owned(), Any::String("blob".into()))]);
+        assert_eq!(registry.validate_update("affine:embed", &props), Ok(()));
+
+        let props = HashMap::from([
+            ("sourceId".to_owned(), Any::Null),
+            ("width".to_owned(), Any::String("wide".into())),
+        ]);
+        assert_eq!(
+            registry
+                .validate_update("affine:embed", &props)
+                .unwrap_err()
+                .into_iter()
+                .map(|v| v.prop)
+                .collect::<Vec<_>>(),
+            vec!["sourceId", "width"]
+        );
+
+        let props = HashMap::from([("custom".to_owned(), Any::Bool(true))]);
+        assert_eq!(registry.validate_update("affine:bookmark", &props), Ok(()));
+    }
+
+    #[test]
+    fn register_flavour() {
+        let mut registry = SchemaRegistry::empty();
+        registry.register(
+            FlavourSchema::new("custom:tag")
+                .prop("name", PropSchema::new(PropType::String).required())
+                .prop(
+                    "color",
+                    PropSchema::new(PropType::String).default_value("gray"),
+                ),
+        );
+
+        let schema = registry.get("custom:tag").unwrap();
+        assert_eq!(
+            schema.defaults(),
+            HashMap::from([("color".to_owned(), Any::String("gray".into()))])
+        );
+        assert!(registry
+            .validate(
+                "custom:tag",
+                &HashMap::from([("name".to_owned(), Any::String("rust".into()))])
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn deserialize_flavour() {
+        let schema: FlavourSchema = serde_json::from_str(
+            r#"{
+                "flavour": "custom:tag",
+                "props": {
+                    "name": { "type": "string", "required": true },
+                    "color": { "type": "string", "default": "gray", "choices": ["gray", "red"] }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            schema,
+            FlavourSchema::new("custom:tag")
+                .prop("name", PropSchema::new(PropType::String).required())
+                .prop(
+                    "color",
+                    PropSchema::new(PropType::String)
+                        .default_value("gray")
+                        .choices(["gray", "red"]),
+                )
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! A small line based markdown reader, the inverse of [Block::to_markdown].
+//!
+//! It only understands the constructs that the markdown exporter emits,
+//! everything else is treated as paragraph text.
+//!
+//! [Block::to_markdown]: crate::Block::to_markdown
+
+#[derive(Debug, PartialEq)]
+pub(super) enum ListKind {
+    Numbered,
+    Bulleted,
+    Todo(bool),
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum MarkdownBlock {
+    Heading(usize, String),
+    Quote(String),
+    Paragraph(String),
+    List {
+        indent: usize,
+        kind: ListKind,
+        text: String,
+    },
+    Code {
+        language: Option<String>,
+        text: String,
+    },
+    Divider,
+    Image(String),
+}
+
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn parse_heading(line: &str) -> Option<MarkdownBlock> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) {
+        line[level..]
+            .strip_prefix(' ')
+            .map(|text| MarkdownBlock::Heading(level, text.trim().into()))
+    } else {
+        None
+    }
+}
+
+fn parse_divider(line: &str) -> Option<MarkdownBlock> {
+    let mut chars = line.chars().filter(|c| !c.is_whitespace());
+    let first = chars.next()?;
+    let count = 1 + chars.clone().count();
+
+    (matches!(first, '-' | '*' | '_') && count >= 3 && chars.all(|c| c == first))
+        .then_some(MarkdownBlock::Divider)
+}
+
+fn parse_image(line: &str) -> Option<MarkdownBlock> {
+    let url = line
+        .strip_prefix("![")?
+        .split_once("](")?
+        .1
+        .strip_suffix(')')?;
+    // drop the optional title: ![alt](url "title")
+    let url = url.split_whitespace().next()?;
+    let url = url.split(['?', '#']).next()?;
+
+    url.rsplit('/')
+        .next()
+        .filter(|id| !id.is_empty())
+        .map(|id| MarkdownBlock::Image(id.into()))
+}
+
+fn parse_todo(line: &str) -> Option<(bool, &str)> {
+    let checked = match line.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+
+    Some((checked, line[3..].trim_start()))
+}
+
+fn parse_list(line: &str) -> Option<MarkdownBlock> {
+    let indent = indent_width(line);
+    let trimmed = line.trim_start();
+
+    let (kind, text) = if let Some(text) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|bullet| trimmed.strip_prefix(bullet))
+    {
+        match parse_todo(text) {
+            Some((checked, text)) => (ListKind::Todo(checked), text),
+            None => (ListKind::Bulleted, text),
+        }
+    } else if let Some((checked, text)) = parse_todo(trimmed) {
+        (ListKind::Todo(checked), text)
+    } else {
+        let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+        let text = trimmed
+            .get(digits..)
+            .filter(|_| digits > 0)
+            .and_then(|rest| rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")))?;
+        (ListKind::Numbered, text)
+    };
+
+    Some(MarkdownBlock::List {
+        indent,
+        kind,
+        text: text.trim().into(),
+    })
+}
+
+pub(super) fn parse_markdown(markdown: &str) -> Vec<MarkdownBlock> {
+    let mut blocks = vec![];
+    let mut paragraph: Vec<&str> = vec![];
+    let mut quote: Vec<&str> = vec![];
+    let mut code: Option<(Option<String>, Vec<&str>)> = None;
+
+    fn flush(blocks: &mut Vec<MarkdownBlock>, paragraph: &mut Vec<&str>, quote: &mut Vec<&str>) {
+        if !paragraph.is_empty() {
+            blocks.push(MarkdownBlock::Paragraph(paragraph.join("\n")));
+            paragraph.clear();
+        }
+        if !quote.is_empty() {
+            blocks.push(MarkdownBlock::Quote(quote.join("\n")));
+            quote.clear();
+        }
+    }
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+
+        if let Some((language, lines)) = &mut code {
+            if trimmed.starts_with("```") {
+                blocks.push(MarkdownBlock::Code {
+                    language: language.take(),
+                    text: lines.join("\n"),
+                });
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+
+        if let Some(language) = trimmed.strip_prefix("```") {
+            flush(&mut blocks, &mut paragraph, &mut quote);
+            let language = language.trim();
+            code = Some(((!language.is_empty()).then(|| language.into()), vec![]));
+        } else if trimmed.is_empty() {
+            flush(&mut blocks, &mut paragraph, &mut quote);
+        } else if let Some(text) = trimmed.strip_prefix('>') {
+            if !paragraph.is_empty() {
+                flush(&mut blocks, &mut paragraph, &mut quote);
+            }
+            quote.push(text.trim());
+        } else if let Some(block) = parse_heading(trimmed)
+            .or_else(|| parse_divider(trimmed))
+            .or_else(|| parse_image(trimmed))
+            .or_else(|| parse_list(line))
+        {
+            flush(&mut blocks, &mut paragraph, &mut quote);
+            blocks.push(block);
+        } else {
+            if !quote.is_empty() {
+                flush(&mut blocks, &mut paragraph, &mut quote);
+            }
+            paragraph.push(trimmed);
+        }
+    }
+
+    // unclosed code fence, keep the content anyway
+    if let Some((language, lines)) = code {
+        blocks.push(MarkdownBlock::Code {
+            language,
+            text: lines.join("\n"),
+        });
+    }
+    flush(&mut blocks, &mut paragraph, &mut quote);
+
+    blocks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_blocks() {
+        let markdown = "# Title\n\nhello\nworld\n\n> quote\n\n1. one\n2. two\n   - nested\n\n[x] done\n- [ ] todo\n\n``` rust\nfn main() {}\n```\n\n---\n\n![](/api/workspace/ws/blob/hash)\n";
+
+        assert_eq!(
+            parse_markdown(markdown),
+            vec![
+                MarkdownBlock::Heading(1, "Title".into()),
+                MarkdownBlock::Paragraph("hello\nworld".into()),
+                MarkdownBlock::Quote("quote".into()),
+                MarkdownBlock::List {
+                    indent: 0,
+                    kind: ListKind::Numbered,
+                    text: "one".into()
+                },
+                MarkdownBlock::List {
+                    indent: 0,
+                    kind: ListKind::Numbered,
+                    text: "two".into()
+                },
+                MarkdownBlock::List {
+                    indent: 3,
+                    kind: ListKind::Bulleted,
+                    text: "nested".into()
+                },
+                MarkdownBlock::List {
+                    indent: 0,
+                    kind: ListKind::Todo(true),
+                    text: "done".into()
+                },
+                MarkdownBlock::List {
+                    indent: 0,
+                    kind: ListKind::Todo(false),
+                    text: "todo".into()
+                },
+                MarkdownBlock::Code {
+                    language: Some("rust".into()),
+                    text: "fn main() {}".into()
+                },
+                MarkdownBlock::Divider,
+                MarkdownBlock::Image("hash".into()),
+            ]
+        );
+    }
+}
@@ -1,6 +1,13 @@
+mod markdown;
 mod transaction;
 
-use super::{block::MarkdownState, *};
+use super::{
+    block::{HtmlState, MarkdownState},
+    utils::escape_html,
+    *,
+};
+use markdown::{parse_markdown, ListKind, MarkdownBlock};
+use nanoid::nanoid;
 use serde::{ser::SerializeMap, Serialize, Serializer};
 use transaction::SpaceTransaction;
 use yrs::{Doc, Map, MapRef, ReadTxn, Transact, TransactionMut, WriteTxn};
@@ -179,7 +186,7 @@ impl Space {
     where
         T: ReadTxn,
     {
-        if let Some(title) = self.get_blocks_by_flavour(trx, "affine:page").first() {
+        if let Some(title) = self.get_blocks_by_flavour(&*trx, "affine:page").first() {
             let mut markdown = String::new();
 
             if let Some(title) = title.get(trx, "title") {
@@ -207,6 +214,164 @@ impl Space {
             None
         }
     }
+
+    /// Render the page of this space as a standalone html document.
+    pub fn to_html<T>(&self, trx: &T) -> Option<String>
+    where
+        T: ReadTxn,
+    {
+        if let Some(page) = self.get_blocks_by_flavour(trx, "affine:page").first() {
+            let mut html =
+                String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+
+            let title = page
+                .get(trx, "title")
+                .map(|title| escape_html(&title.to_string()));
+            if let Some(title) = &title {
+                html.push_str(&format!("<title>{title}</title>\n"));
+            }
+            html.push_str("</head>\n<body>\n");
+            if let Some(title) = &title {
+                html.push_str(&format!("<h1>{title}</h1>\n"));
+            }
+
+            for frame in page.children(trx) {
+                if let Some(frame) = self.get(trx, &frame) {
+                    let mut state = HtmlState::default();
+                    for child in frame.children(trx) {
+                        if let Some(text) = self
+                            .get(trx, &child)
+                            .and_then(|child| child.to_html(trx, &mut state))
+                        {
+                            html.push_str(&text);
+                        }
+                    }
+                    html.push_str(&state.finish());
+                }
+            }
+
+            html.push_str("</body>\n</html>\n");
+
+            Some(html)
+        } else {
+            None
+        }
+    }
+
+    /// Import markdown into this space, the inverse of [Space::to_markdown].
+    ///
+    /// The blocks are appended to the first frame of the page, a page and a frame
+    /// will be created if they don't exist yet. Returns the page block.
+    pub fn from_markdown(&self, trx: &mut TransactionMut, markdown: &str) -> Block {
+        let mut blocks = parse_markdown(markdown).into_iter().peekable();
+
+        let page = match self
+            .get_blocks_by_flavour(&*trx, "affine:page")
+            .into_iter()
+            .next()
+        {
+            Some(page) => page,
+            None => self.create(trx, nanoid!(), "affine:page"),
+        };
+
+        if page.get(&*trx, "title").is_none() {
+            if let Some(MarkdownBlock::Heading(1, _)) = blocks.peek() {
+                if let Some(MarkdownBlock::Heading(_, title)) = blocks.next() {
+                    page.set(trx, "title", title);
+                }
+            }
+        }
+
+        let frame = match page.children(&*trx).iter().find_map(|id| {
+            self.get(&*trx, id)
+                .filter(|frame| frame.flavor(&*trx) == "affine:frame")
+        }) {
+            Some(frame) => frame,
+            None => {
+                let frame = self.create(trx, nanoid!(), "affine:frame");
+                page.push_children(trx, &frame);
+                frame
+            }
+        };
+
+        // the list blocks that nested list items can be attached to, with their indent
+        let mut lists: Vec<(usize, Block)> = vec![];
+
+        for block in blocks {
+            let (parent, child) = match block {
+                MarkdownBlock::List { indent, kind, text } => {
+                    while matches!(lists.last(), Some((last, _)) if *last >= indent) {
+                        lists.pop();
+                    }
+
+                    let list = self.create(trx, nanoid!(), "affine:list");
+                    list.set(trx, "text", text);
+                    match kind {
+                        ListKind::Numbered => list.set(trx, "type", "numbered"),
+                        ListKind::Bulleted => list.set(trx, "type", "bulleted"),
+                        ListKind::Todo(checked) => {
+                            list.set(trx, "type", "todo");
+                            list.set(trx, "checked", checked);
+                        }
+                    }
+
+                    let parent = lists
+                        .last()
+                        .map(|(_, parent)| parent.clone())
+                        .unwrap_or_else(|| frame.clone());
+                    lists.push((indent, list.clone()));
+
+                    (parent, list)
+                }
+                block => {
+                    lists.clear();
+
+                    let child = match block {
+                        MarkdownBlock::Heading(level, text) => {
+                            let block = self.create(trx, nanoid!(), "affine:paragraph");
+                            block.set(trx, "type", format!("h{level}"));
+                            block.set(trx, "text", text);
+                            block
+                        }
+                        MarkdownBlock::Quote(text) => {
+                            let block = self.create(trx, nanoid!(), "affine:paragraph");
+                            block.set(trx, "type", "quote");
+                            block.set(trx, "text", text);
+                            block
+                        }
+                        MarkdownBlock::Paragraph(text) => {
+                            let block = self.create(trx, nanoid!(), "affine:paragraph");
+                            block.set(trx, "type", "text");
+                            block.set(trx, "text", text);
+                            block
+                        }
+                        MarkdownBlock::Code { language, text } => {
+                            let block = self.create(trx, nanoid!(), "affine:code");
+                            if let Some(language) = language {
+                                block.set(trx, "language", language);
+                            }
+                            block.set(trx, "text", text);
+                            block
+                        }
+                        MarkdownBlock::Divider => self.create(trx, nanoid!(), "affine:divider"),
+                        MarkdownBlock::Image(source_id) => {
+                            let block = self.create(trx, nanoid!(), "affine:embed");
+                            block.set(trx, "type", "image");
+                            block.set(trx, "sourceId", source_id);
+                            block
+                        }
+                        MarkdownBlock::List { .. } => unreachable!(),
+                    };
+
+                    (frame.clone(), child)
+                }
+            };
+
+            parent.push_children(trx, &child);
+        }
+
+        page
+    }
 }
 
 impl Serialize for Space {
@@ -336,4 +501,92 @@ mod test {
         let space = Space::new(&mut trx, doc.clone(), "space", "test");
         assert_eq!(space.client_id(), 123);
     }
+
+    #[test]
+    fn html() {
+        let doc = Doc::new();
+        let space = {
+            let mut trx = doc.transact_mut();
+            Space::new(&mut trx, doc.clone(), "workspace", "space")
+        };
+
+        space.with_trx(|mut t| {
+            space.from_markdown(
+                &mut t.trx,
+                "# <Title>\n## Heading\n\ntext\n\n- one\n- two\n\n[x] done\n\n``` rust\nlet a = 1 < 2;\n```\n\n---\n\n![](/api/workspace/workspace/blob/hash)",
+            );
+
+            assert_eq!(
+                space.to_html(&t.trx).unwrap(),
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>&lt;Title&gt;</title>\n</head>\n<body>\n<h1>&lt;Title&gt;</h1>\n<h2>Heading</h2>\n<p>text</p>\n<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n<ul class=\"todo\">\n<li><input type=\"checkbox\" disabled checked> done</li>\n</ul>\n<pre><code class=\"language-rust\">let a = 1 &lt; 2;</code></pre>\n<hr>\n<img src=\"/api/workspace/workspace/blob/hash\">\n</body>\n</html>\n"
+            );
+        });
+    }
+
+    #[test]
+    fn markdown_round_trip() {
+        let markdown = "# Title\n## Heading\n\n###### Small heading\n\ntext\n\n> quote\n\n1. one\n\n2. two\n\n- bullet\n\n[x] done\n\n``` rust\nfn main() {}\n```\n\n---\n\n![](/api/workspace/workspace/blob/hash)\n\n";
+
+        let doc = Doc::new();
+        let space = {
+            let mut trx = doc.transact_mut();
+            Space::new(&mut trx, doc.clone(), "workspace", "space")
+        };
+
+        space.with_trx(|mut t| {
+            let page = space.from_markdown(&mut t.trx, markdown);
+
+            assert_eq!(page.flavor(&t.trx), "affine:page");
+            assert_eq!(space.to_markdown(&t.trx).unwrap(), markdown);
+        });
+
+        space.with_trx(|mut t| {
+            space.from_markdown(
+                &mut t.trx,
+                "- parent\n  - child\n    1. grandchild\n- sibling",
+            );
+
+            let page = space.get_blocks_by_flavour(&t.trx, "affine:page").remove(0);
+            let frame = space.get(&t.trx, &page.children(&t.trx)[0]).unwrap();
+            let children = frame.children(&t.trx);
+            let (parent, sibling) = (
+                space.get(&t.trx, &children[children.len() - 2]).unwrap(),
+                space.get(&t.trx, &children[children.len() - 1]).unwrap(),
+            );
+            assert_eq!(parent.get(&t.trx, "text").unwrap().to_string(), "parent");
+            assert_eq!(sibling.get(&t.trx, "text").unwrap().to_string(), "sibling");
+
+            let child = space.get(&t.trx, &parent.children(&t.trx)[0]).unwrap();
+            assert_eq!(child.get(&t.trx, "text").unwrap().to_string(), "child");
+            assert_eq!(child.parent(&t.trx), Some(parent.block_id()));
+
+            let grandchild = space.get(&t.trx, &child.children(&t.trx)[0]).unwrap();
+            assert_eq!(
+                grandchild.get(&t.trx, "type").unwrap().to_string(),
+                "numbered"
+            );
+        });
+    }
+
+    #[test]
+    fn markdown_into_existing_frame() {
+        let doc = Doc::new();
+        let space = {
+            let mut trx = doc.transact_mut();
+            Space::new(&mut trx, doc.clone(), "workspace", "space")
+        };
+
+        space.with_trx(|mut t| {
+            let page = space.create(&mut t.trx, "page", "affine:page");
+            let surface = space.create(&mut t.trx, "surface", "affine:surface");
+            let frame = space.create(&mut t.trx, "frame", "affine:frame");
+            page.push_children(&mut t.trx, &surface);
+            page.push_children(&mut t.trx, &frame);
+
+            space.from_markdown(&mut t.trx, "text");
+
+            assert_eq!(page.children(&t.trx), vec!["surface", "frame"]);
+            assert_eq!(frame.children(&t.trx).len(), 1);
+        });
+    }
 }
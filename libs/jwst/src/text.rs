@@ -0,0 +1,255 @@
+use super::utils::escape_html;
+use lib0::any::Any;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub type TextAttributes = HashMap<String, Any>;
+
+/// A single operation of a rich text delta, compatible with the quill delta format.
+///
+/// The index and length are counted in the offset kind of the doc,
+/// which is utf-8 bytes for docs created by jwst.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TextDelta {
+    Insert {
+        insert: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attributes: Option<TextAttributes>,
+    },
+    Retain {
+        retain: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attributes: Option<TextAttributes>,
+    },
+    Delete {
+        delete: u32,
+    },
+}
+
+impl TextDelta {
+    pub fn insert<S: Into<String>>(text: S) -> Self {
+        Self::Insert {
+            insert: text.into(),
+            attributes: None,
+        }
+    }
+
+    pub fn insert_with_attributes<S: Into<String>>(text: S, attributes: TextAttributes) -> Self {
+        Self::Insert {
+            insert: text.into(),
+            attributes: (!attributes.is_empty()).then_some(attributes),
+        }
+    }
+}
+
+fn is_enabled(attributes: &TextAttributes, key: &str) -> bool {
+    !matches!(
+        attributes.get(key),
+        None | Some(Any::Null | Any::Undefined | Any::Bool(false))
+    )
+}
+
+fn link(attributes: &TextAttributes) -> Option<String> {
+    match attributes.get("link") {
+        Some(Any::String(link)) => Some(link.to_string()),
+        _ => None,
+    }
+}
+
+/// Whether a link can be rendered as a clickable href, only http, https,
+/// mailto and relative urls are allowed so `javascript:` or `data:` links
+/// can't run in the rendered page.
+fn is_safe_link(link: &str) -> bool {
+    let link = link.trim();
+    let Some(colon) = link.find(':') else {
+        return true;
+    };
+    let scheme = &link[..colon];
+    if scheme.contains(['/', '?', '#']) {
+        // the colon is part of the path, query or fragment of a relative url
+        return true;
+    }
+    // browsers drop tabs and newlines inside the scheme
+    let scheme = scheme
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    matches!(scheme.as_str(), "http" | "https" | "mailto")
+}
+
+/// Wrap the text with markers, whitespaces at the edge are kept outside
+/// because `** bold**` is not valid markdown.
+fn wrap(text: &str, start: &str, end: &str) -> String {
+    let content = text.trim();
+    if content.is_empty() {
+        return text.into();
+    }
+    let leading = &text[..text.len() - text.trim_start().len()];
+    let trailing = &text[text.trim_end().len()..];
+
+    format!("{leading}{start}{content}{end}{trailing}")
+}
+
+/// Collect the plain text of delta inserts.
+pub(crate) fn delta_to_string(delta: &[TextDelta]) -> String {
+    delta
+        .iter()
+        .filter_map(|op| match op {
+            TextDelta::Insert { insert, .. } => Some(insert.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Collect the link targets of delta inserts.
+pub(crate) fn delta_links(delta: &[TextDelta]) -> Vec<String> {
+    delta
+        .iter()
+        .filter_map(|op| match op {
+            TextDelta::Insert {
+                attributes: Some(attributes),
+                ..
+            } => link(attributes),
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn delta_to_markdown(delta: &[TextDelta]) -> String {
+    let mut markdown = String::new();
+
+    for op in delta {
+        if let TextDelta::Insert { insert, attributes } = op {
+            let Some(attributes) = attributes else {
+                markdown.push_str(insert);
+                continue;
+            };
+
+            let mut text = insert.clone();
+            if is_enabled(attributes, "code") {
+                text = wrap(&text, "`", "`");
+            } else {
+                if is_enabled(attributes, "strike") {
+                    text = wrap(&text, "~~", "~~");
+                }
+                if is_enabled(attributes, "italic") {
+                    text = wrap(&text, "_", "_");
+                }
+                if is_enabled(attributes, "bold") {
+                    text = wrap(&text, "**", "**");
+                }
+            }
+            if let Some(link) = link(attributes) {
+                text = wrap(&text, "[", &format!("]({link})"));
+            }
+
+            markdown.push_str(&text);
+        }
+    }
+
+    markdown
+}
+
+pub(crate) fn delta_to_html(delta: &[TextDelta]) -> String {
+    let mut html = String::new();
+
+    for op in delta {
+        if let TextDelta::Insert { insert, attributes } = op {
+            let mut text = escape_html(insert);
+
+            if let Some(attributes) = attributes {
+                for (key, tag) in [
+                    ("code", "code"),
+                    ("strike", "s"),
+                    ("underline", "u"),
+                    ("italic", "em"),
+                    ("bold", "strong"),
+                ] {
+                    if is_enabled(attributes, key) {
+                        text = format!("<{tag}>{text}</{tag}>");
+                    }
+                }
+                if let Some(link) = link(attributes).filter(|link| is_safe_link(link)) {
+                    text = format!("<a href=\"{}\">{text}</a>", escape_html(&link));
+                }
+            }
+
+            html.push_str(&text);
+        }
+    }
+
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_delta() {
+        let delta = vec![
+            TextDelta::insert("plain "),
+            TextDelta::insert_with_attributes(
+                "bold ",
+                HashMap::from([("bold".into(), Any::Bool(true))]),
+            ),
+            TextDelta::insert_with_attributes(
+                "link",
+                HashMap::from([
+                    ("italic".into(), Any::Bool(true)),
+                    ("link".into(), Any::String("https://affine.pro".into())),
+                ]),
+            ),
+            TextDelta::insert(" <code>"),
+            TextDelta::insert_with_attributes(
+                "a < b",
+                HashMap::from([("code".into(), Any::Bool(true))]),
+            ),
+        ];
+
+        assert_eq!(
+            delta_to_markdown(&delta),
+            "plain **bold** [_link_](https://affine.pro) <code>`a < b`"
+        );
+        assert_eq!(
+            delta_to_html(&delta),
+            "plain <strong>bold </strong><a href=\"https://affine.pro\"><em>link</em></a> &lt;code&gt;<code>a &lt; b</code>"
+        );
+        assert_eq!(delta_to_string(&delta), "plain bold link <code>a < b");
+        assert_eq!(delta_links(&delta), vec!["https://affine.pro".to_owned()]);
+    }
+
+    #[test]
+    fn render_unsafe_link_as_text() {
+        let link = |text: &str, link: &str| {
+            TextDelta::insert_with_attributes(
+                text,
+                HashMap::from([("link".into(), Any::String(link.into()))]),
+            )
+        };
+
+        assert_eq!(
+            delta_to_html(&[link("click", "javascript:alert(document.cookie)")]),
+            "click"
+        );
+        assert_eq!(
+            delta_to_html(&[link("click", " Java\tScript:alert(1)")]),
+            "click"
+        );
+        assert_eq!(
+            delta_to_html(&[link("image", "data:text/html;base64,PHNjcmlwdD4=")]),
+            "image"
+        );
+        assert_eq!(
+            delta_to_html(&[link("mail", "mailto:hi@affine.pro")]),
+            "<a href=\"mailto:hi@affine.pro\">mail</a>"
+        );
+        assert_eq!(
+            delta_to_html(&[link("page", "/workspace/page?at=10:00#top")]),
+            "<a href=\"/workspace/page?at=10:00#top\">page</a>"
+        );
+    }
+}
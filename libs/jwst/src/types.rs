@@ -1,9 +1,16 @@
 use super::Workspace;
 use async_trait::async_trait;
 use bytes::Bytes;
-use chrono::NaiveDateTime;
-use futures::Stream;
+use chrono::{DateTime, NaiveDateTime};
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Debug, Error)]
 pub enum JwstError {
@@ -19,6 +26,12 @@ pub enum JwstError {
     WorkspaceNotInitialized(String),
     #[error("workspace {0} not found")]
     WorkspaceNotFound(String),
+    #[error("value at {0} cannot be stored: {1}")]
+    InvalidValue(String, String),
+    #[error("snapshot {0} is invalid: {1}")]
+    InvalidSnapshot(String, String),
+    #[error("block {1} already exists in space {0}")]
+    BlockExists(String, String),
 }
 
 pub type JwstResult<T> = Result<T, JwstError>;
@@ -37,6 +50,240 @@ pub trait DocStorage {
 pub struct BlobMetadata {
     pub size: u64,
     pub last_modified: NaiveDateTime,
+    /// Detected from the content on upload, or as declared by the upload
+    pub content_type: String,
+}
+
+impl BlobMetadata {
+    /// The `Content-Disposition` of the blob, see [blob_content_disposition].
+    pub fn content_disposition(&self, id: &str) -> String {
+        blob_content_disposition(&self.content_type, id)
+    }
+}
+
+/// Browsers only display the blobs in place whose content type cannot run scripts,
+/// others such as html or svg are downloaded, so that they cannot act in our origin.
+pub fn blob_content_disposition(content_type: &str, id: &str) -> String {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let inline = match essence.split_once('/') {
+        Some(("image", "svg+xml")) => false,
+        Some(("image" | "video" | "audio", _)) => true,
+        _ => matches!(essence.as_str(), "application/pdf" | "text/plain"),
+    };
+
+    format!(
+        "{}; filename=\"{id}\"",
+        if inline { "inline" } else { "attachment" }
+    )
+}
+
+/// The byte ranges of a blob of `size` bytes requested by a `Range` header, see RFC 9110.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlobRanges {
+    /// The header is invalid or asks for too many ranges or bytes, the whole blob is served
+    Full,
+    /// No range overlaps the blob
+    Unsatisfiable,
+    /// The ranges clamped to the size of the blob, sorted and with the overlapping
+    /// or adjacent ones merged
+    Partial(Vec<Range<u64>>),
+}
+
+impl BlobRanges {
+    /// Clients need only a few ranges, more would make the server do many small reads
+    const MAX_RANGES: usize = 32;
+
+    pub fn parse(header: &str, size: u64) -> Self {
+        let Some((unit, specs)) = header.split_once('=') else {
+            return Self::Full;
+        };
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return Self::Full;
+        }
+
+        let mut ranges = vec![];
+        for (i, spec) in specs.split(',').enumerate() {
+            let Some((first, last)) = spec.trim().split_once('-') else {
+                return Self::Full;
+            };
+            if i >= Self::MAX_RANGES {
+                return Self::Full;
+            }
+            let range = match (first.parse::<u64>(), last.parse::<u64>()) {
+                (Ok(first), Ok(last)) if first <= last => first..last.saturating_add(1).min(size),
+                (Ok(first), Err(_)) if last.is_empty() => first..size,
+                // the last `suffix` bytes
+                (Err(_), Ok(suffix)) if first.is_empty() => size.saturating_sub(suffix)..size,
+                _ => return Self::Full,
+            };
+            if range.start < range.end {
+                ranges.push(range);
+            }
+        }
+
+        // overlapping ranges would make the response larger than the blob
+        let requested = ranges.iter().fold(0u64, |sum, range| {
+            sum.saturating_add(range.end - range.start)
+        });
+        if requested > size {
+            return Self::Full;
+        }
+
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        if merged.is_empty() {
+            Self::Unsatisfiable
+        } else {
+            Self::Partial(merged)
+        }
+    }
+
+    /// The ranges requested by the `Range` header. They are only served if the blob is
+    /// the one the client already has part of, identified by the `If-Range` header with
+    /// either its etag `id` or its modification time.
+    pub fn from_headers(
+        range: Option<&str>,
+        if_range: Option<&str>,
+        id: &str,
+        meta: &BlobMetadata,
+    ) -> Self {
+        let Some(range) = range else {
+            return Self::Full;
+        };
+        let matches = if_range.map_or(true, |if_range| {
+            if_range == id
+                || if_range.strip_prefix('"').and_then(|s| s.strip_suffix('"')) == Some(id)
+                // http dates have no fractions of seconds
+                || DateTime::parse_from_rfc2822(if_range)
+                    .map_or(false, |date| date.timestamp() == meta.last_modified.timestamp())
+        });
+
+        if matches {
+            Self::parse(range, meta.size)
+        } else {
+            Self::Full
+        }
+    }
+
+    /// The `Content-Range` header of a `416 Range Not Satisfiable` response.
+    pub fn unsatisfiable_content_range(size: u64) -> String {
+        format!("bytes */{size}")
+    }
+}
+
+/// The body of a `206 Partial Content` response, see [read_blob_ranges].
+pub struct BlobRangesBody {
+    /// The `Content-Type` of a `multipart/byteranges` body,
+    /// a single range keeps the content type of the blob
+    pub content_type: Option<String>,
+    /// The `Content-Range` of a single range, the parts of a multipart body have their own
+    pub content_range: Option<String>,
+    pub content_length: u64,
+    pub body: BoxStream<'static, std::io::Result<Bytes>>,
+}
+
+/// Read the ranges of the blob, a single range is served as is
+/// and several ranges as a `multipart/byteranges` body.
+pub async fn read_blob_ranges<S>(
+    storage: &S,
+    workspace: Option<String>,
+    id: String,
+    meta: &BlobMetadata,
+    ranges: Vec<Range<u64>>,
+) -> JwstResult<BlobRangesBody>
+where
+    S: BlobStorage + Sync,
+    S::Read: Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+{
+    let content_range =
+        |range: &Range<u64>| format!("bytes {}-{}/{}", range.start, range.end - 1, meta.size);
+
+    if let [range] = &ranges[..] {
+        let file = storage.get_blob_range(workspace, id, range.clone()).await?;
+        return Ok(BlobRangesBody {
+            content_type: None,
+            content_range: Some(content_range(range)),
+            content_length: range.end - range.start,
+            body: file.boxed(),
+        });
+    }
+
+    let boundary = nanoid::nanoid!();
+    let mut length = 0;
+    let mut parts = Vec::with_capacity(ranges.len() + 1);
+    for range in &ranges {
+        let file = storage
+            .get_blob_range(workspace.clone(), id.clone(), range.clone())
+            .await?;
+        let head = format!(
+            "--{boundary}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            meta.content_type,
+            content_range(range)
+        );
+        length += head.len() as u64 + (range.end - range.start) + 2;
+        parts.push(
+            stream::once(future::ready(Ok(Bytes::from(head))))
+                .chain(file)
+                .chain(stream::once(future::ready(Ok(Bytes::from_static(b"\r\n")))))
+                .boxed(),
+        );
+    }
+    let tail = format!("--{boundary}--\r\n");
+    length += tail.len() as u64;
+    parts.push(stream::once(future::ready(Ok(Bytes::from(tail)))).boxed());
+
+    Ok(BlobRangesBody {
+        content_type: Some(format!("multipart/byteranges; boundary={boundary}")),
+        content_range: None,
+        content_length: length,
+        body: stream::iter(parts).flatten().boxed(),
+    })
+}
+
+/// A stored blob without its content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BlobInfo {
+    pub id: String,
+    pub size: u64,
+    #[schema(value_type = String)]
+    pub last_modified: NaiveDateTime,
+}
+
+/// Storage used by the blobs of a workspace
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BlobUsage {
+    /// Number of blobs
+    pub count: u64,
+    /// Total size of the blobs in bytes
+    pub size: u64,
+}
+
+/// Result of collecting the unreferenced blobs of a workspace
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BlobGcReport {
+    pub workspace: String,
+    /// The blobs were only reported, nothing was changed
+    pub dry_run: bool,
+    /// Number of blobs still referred to by the workspace or its snapshots
+    pub referenced: usize,
+    /// Unreferenced blobs within the grace period, they are kept for now
+    pub pending: Vec<String>,
+    /// Unreferenced blobs past the grace period, they are deleted unless it is a dry run
+    pub deleted: Vec<String>,
+    /// Total size of the deleted blobs in bytes
+    pub freed: u64,
 }
 
 #[async_trait]
@@ -45,13 +292,83 @@ pub trait BlobStorage {
 
     async fn check_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool>;
     async fn get_blob(&self, workspace: Option<String>, id: String) -> JwstResult<Self::Read>;
+    /// Read the bytes `range` of the blob, the range must be within the blob.
+    async fn get_blob_range(
+        &self,
+        workspace: Option<String>,
+        id: String,
+        range: Range<u64>,
+    ) -> JwstResult<Self::Read>;
     async fn get_metadata(&self, workspace: Option<String>, id: String)
         -> JwstResult<BlobMetadata>;
+    /// Store the blob and return its hash, the content type is detected from the
+    /// content and `content_type` as declared by the upload is the fallback.
     async fn put_blob(
         &self,
         workspace: Option<String>,
         stream: impl Stream<Item = Bytes> + Send,
+        content_type: Option<String>,
     ) -> JwstResult<String>;
     async fn delete_blob(&self, workspace: Option<String>, id: String) -> JwstResult<bool>;
     async fn delete_workspace(&self, workspace_id: String) -> JwstResult<()>;
+    /// List the blobs of the workspace, the most recently uploaded first
+    async fn list_blobs(
+        &self,
+        workspace: Option<String>,
+        offset: u64,
+        limit: u64,
+    ) -> JwstResult<Vec<BlobInfo>>;
+    async fn get_usage(&self, workspace: Option<String>) -> JwstResult<BlobUsage>;
+    /// A url the client can download the blob from directly instead of reading it
+    /// via [BlobStorage::get_blob], only some storages provide one.
+    async fn get_blob_url(
+        &self,
+        _workspace: Option<String>,
+        _id: String,
+    ) -> JwstResult<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_blob_ranges() {
+        let parse = |header: &str| BlobRanges::parse(header, 100);
+
+        assert_eq!(parse("bytes=0-9"), BlobRanges::Partial(vec![0..10]));
+        assert_eq!(parse("bytes=90-"), BlobRanges::Partial(vec![90..100]));
+        assert_eq!(parse("bytes=-10"), BlobRanges::Partial(vec![90..100]));
+        assert_eq!(parse("bytes=-200"), BlobRanges::Partial(vec![0..100]));
+        assert_eq!(parse("bytes=95-200"), BlobRanges::Partial(vec![95..100]));
+        assert_eq!(
+            parse("bytes=50-59, 0-9"),
+            BlobRanges::Partial(vec![0..10, 50..60])
+        );
+        // overlapping and adjacent ranges are merged
+        assert_eq!(
+            parse("bytes=20-29,0-4,25-34,5-9"),
+            BlobRanges::Partial(vec![0..10, 20..35])
+        );
+        assert_eq!(parse("bytes=0-9,5-14"), BlobRanges::Partial(vec![0..15]));
+        // asking for more bytes than the blob has serves the whole blob
+        assert_eq!(parse("bytes=0-59,40-99"), BlobRanges::Full);
+        assert_eq!(parse("bytes=0-,0-"), BlobRanges::Full);
+        // ranges outside the blob are dropped
+        assert_eq!(parse("bytes=0-0,100-"), BlobRanges::Partial(vec![0..1]));
+        assert_eq!(parse("bytes=100-"), BlobRanges::Unsatisfiable);
+        assert_eq!(parse("bytes=-0"), BlobRanges::Unsatisfiable);
+        assert_eq!(BlobRanges::parse("bytes=0-", 0), BlobRanges::Unsatisfiable);
+        // invalid headers are ignored
+        assert_eq!(parse("bytes=9-0"), BlobRanges::Full);
+        assert_eq!(parse("bytes=a-b"), BlobRanges::Full);
+        assert_eq!(parse("items=0-9"), BlobRanges::Full);
+        assert_eq!(parse("bytes"), BlobRanges::Full);
+        assert_eq!(
+            parse(&format!("bytes={}", vec!["0-0"; 33].join(","))),
+            BlobRanges::Full
+        );
+    }
 }
@@ -0,0 +1,284 @@
+use super::{Block, JwstError, JwstResult, Space, Workspace};
+use yrs::{
+    undo::{EventKind, Options, UndoEventSubscription},
+    Doc, MapRef,
+};
+
+/// Options of [UndoManager].
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoOptions {
+    /// Changes made within this time after the last change are merged into one undo step
+    pub capture_timeout_millis: u64,
+    /// Only track the transactions with these origins, see [Workspace::with_trx_origin].
+    ///
+    /// Transactions without origin are tracked if it is empty.
+    pub origins: Vec<String>,
+}
+
+impl Default for UndoOptions {
+    fn default() -> Self {
+        Self {
+            capture_timeout_millis: 500,
+            origins: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoStack {
+    Undo,
+    Redo,
+}
+
+impl From<EventKind> for UndoStack {
+    fn from(kind: EventKind) -> Self {
+        match kind {
+            EventKind::Undo => Self::Undo,
+            EventKind::Redo => Self::Redo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoStackEvent {
+    /// A step was pushed to the stack, changes are pushed to the redo stack when undoing
+    Added(UndoStack),
+    /// A step was popped from the stack by undo or redo
+    Popped(UndoStack),
+}
+
+/// Track the changes of a [Space] or a set of [Block]s and revert them step by step.
+///
+/// The manager subscribes to the doc when it is created, and undo and redo are applied
+/// in their own transactions, so none of them can be done while a transaction of the
+/// workspace is alive.
+pub struct UndoManager {
+    manager: yrs::UndoManager,
+    subscriptions: Vec<UndoEventSubscription>,
+}
+
+impl UndoManager {
+    pub(crate) fn new(doc: &Doc, scope: &MapRef, options: UndoOptions) -> Self {
+        let mut manager = yrs::UndoManager::with_options(
+            doc,
+            scope,
+            Options {
+                capture_timeout_millis: options.capture_timeout_millis,
+                ..Default::default()
+            },
+        );
+        for origin in options.origins {
+            manager.include_origin(origin.as_str());
+        }
+
+        Self {
+            manager,
+            subscriptions: vec![],
+        }
+    }
+
+    /// Track the changes of another block, the props and children of the block are tracked.
+    pub fn track_block(&mut self, block: &Block) {
+        self.manager.expand_scope(block.map_ref());
+    }
+
+    /// Revert the last step, return false if there is nothing to undo.
+    pub fn undo(&mut self) -> JwstResult<bool> {
+        self.manager
+            .undo()
+            .map_err(|e| JwstError::BoxedError(anyhow::anyhow!("failed to undo: {e}")))
+    }
+
+    /// Reapply the last undone step, return false if there is nothing to redo.
+    pub fn redo(&mut self) -> JwstResult<bool> {
+        self.manager
+            .redo()
+            .map_err(|e| JwstError::BoxedError(anyhow::anyhow!("failed to redo: {e}")))
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.manager.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.manager.can_redo()
+    }
+
+    /// Stop merging changes into the current step, the next change starts a new step.
+    pub fn stop_capturing(&mut self) {
+        self.manager.reset();
+    }
+
+    /// Drop both stacks.
+    pub fn clear(&mut self) -> JwstResult<()> {
+        self.manager
+            .clear()
+            .map_err(|e| JwstError::BoxedError(anyhow::anyhow!("failed to clear undo: {e}")))
+    }
+
+    /// Subscribe to the changes of the undo and redo stacks.
+    ///
+    /// The callback is called inside the transaction that changes the stack,
+    /// [UndoManager::can_undo] and [UndoManager::can_redo] should be checked after it returns.
+    pub fn observe_stack_changed(&mut self, f: impl Fn(UndoStackEvent) + 'static) {
+        let f = std::rc::Rc::new(f);
+
+        let added = f.clone();
+        self.subscriptions.push(
+            self.manager.observe_item_added(move |_, event| {
+                added(UndoStackEvent::Added(event.kind().into()))
+            }),
+        );
+        self.subscriptions.push(
+            self.manager.observe_item_popped(move |_, event| {
+                f(UndoStackEvent::Popped(event.kind().into()))
+            }),
+        );
+    }
+}
+
+impl Space {
+    /// Create a [UndoManager] that tracks all blocks of the space.
+    pub fn undo_manager(&self, options: UndoOptions) -> UndoManager {
+        UndoManager::new(&self.doc(), &self.blocks, options)
+    }
+}
+
+impl Block {
+    /// Create a [UndoManager] that tracks the block, more blocks can be added by
+    /// [UndoManager::track_block].
+    pub fn undo_manager(&self, options: UndoOptions) -> UndoManager {
+        UndoManager::new(&self.doc(), self.map_ref(), options)
+    }
+}
+
+impl Workspace {
+    /// Create a [UndoManager] that tracks all blocks of a space,
+    /// the space will be created if not exists.
+    ///
+    /// There is no such method on [WorkspaceTransaction](crate::WorkspaceTransaction):
+    /// the manager subscribes to the doc, which is not possible while a transaction
+    /// holds the doc, so create it before or after the transaction.
+    pub fn undo_manager<S: AsRef<str>>(&self, space_id: S, options: UndoOptions) -> UndoManager {
+        self.with_trx(|mut t| t.get_space(space_id))
+            .undo_manager(options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn undo_redo() {
+        let workspace = Workspace::new("test");
+        let mut undo = workspace.undo_manager(
+            "space",
+            UndoOptions {
+                capture_timeout_millis: 0,
+                ..Default::default()
+            },
+        );
+
+        let events = Rc::new(RefCell::new(vec![]));
+        {
+            let events = events.clone();
+            undo.observe_stack_changed(move |event| events.borrow_mut().push(event));
+        }
+
+        workspace.with_trx(|mut t| {
+            let block = t.get_space("space").create(&mut t.trx, "block", "text");
+            block.set(&mut t.trx, "text", "first");
+        });
+        undo.stop_capturing();
+        workspace.with_trx(|mut t| {
+            let block = t.get_space("space").get(&t.trx, "block").unwrap();
+            block.set(&mut t.trx, "text", "second");
+        });
+
+        let text = || {
+            workspace.with_trx(|mut t| {
+                t.get_space("space")
+                    .get(&t.trx, "block")
+                    .and_then(|block| block.get(&t.trx, "text"))
+                    .map(|text| text.to_string())
+            })
+        };
+
+        assert!(undo.can_undo());
+        assert!(!undo.can_redo());
+
+        assert!(undo.undo().unwrap());
+        assert_eq!(text(), Some("first".into()));
+        assert!(undo.can_redo());
+
+        assert!(undo.undo().unwrap());
+        assert_eq!(text(), None);
+        assert!(!undo.undo().unwrap());
+
+        assert!(undo.redo().unwrap());
+        assert!(undo.redo().unwrap());
+        assert_eq!(text(), Some("second".into()));
+
+        assert_eq!(
+            events.borrow().first(),
+            Some(&UndoStackEvent::Added(UndoStack::Undo))
+        );
+        assert!(events
+            .borrow()
+            .contains(&UndoStackEvent::Popped(UndoStack::Redo)));
+    }
+
+    #[test]
+    fn undo_origin() {
+        let workspace = Workspace::new("test");
+        let space = workspace.with_trx(|mut t| t.get_space("space"));
+        let mut undo = space.undo_manager(UndoOptions {
+            origins: vec!["local".into()],
+            ..Default::default()
+        });
+
+        // changes from other origins are not tracked
+        workspace.with_trx(|mut t| {
+            t.get_space("space").create(&mut t.trx, "remote", "text");
+        });
+        assert!(!undo.can_undo());
+
+        workspace.with_trx_origin("local", |mut t| {
+            t.get_space("space").create(&mut t.trx, "local", "text");
+        });
+        assert!(undo.can_undo());
+        assert!(undo.undo().unwrap());
+
+        workspace.with_trx(|t| {
+            let space = t.get_exists_space("space").unwrap();
+            assert!(space.exists(&t.trx, "remote"));
+            assert!(!space.exists(&t.trx, "local"));
+        });
+    }
+
+    #[test]
+    fn undo_local_changes() {
+        let workspace = Workspace::new("test");
+        let mut undo = workspace.undo_manager(
+            "space",
+            UndoOptions {
+                origins: vec![workspace.local_origin()],
+                ..Default::default()
+            },
+        );
+
+        workspace.with_trx(|mut t| {
+            t.get_space("space").create(&mut t.trx, "synced", "text");
+        });
+        assert!(!undo.can_undo());
+
+        workspace
+            .try_with_local_trx(|mut t| {
+                t.get_space("space").create(&mut t.trx, "local", "text");
+            })
+            .unwrap();
+        assert!(undo.can_undo());
+    }
+}
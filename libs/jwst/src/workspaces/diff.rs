@@ -0,0 +1,359 @@
+use super::*;
+use crate::{constants::sys, JwstResult};
+use lib0::any::Any;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use utoipa::ToSchema;
+use yrs::{types::ToJson, Doc, Map, MapRef, ReadTxn, Transact};
+
+/// Changes of the blocks between two states of a workspace, grouped by space.
+#[derive(Serialize, ToSchema, Debug, Clone, Default, PartialEq)]
+pub struct WorkspaceDiff {
+    /// Only the spaces that have changes, sorted by space id
+    pub spaces: Vec<SpaceDiff>,
+}
+
+impl WorkspaceDiff {
+    pub fn is_empty(&self) -> bool {
+        self.spaces.is_empty()
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, Default, PartialEq)]
+pub struct SpaceDiff {
+    pub space_id: String,
+    pub added: Vec<BlockSummary>,
+    pub removed: Vec<BlockSummary>,
+    pub changed: Vec<BlockDiff>,
+}
+
+impl SpaceDiff {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct BlockSummary {
+    pub block_id: String,
+    pub flavour: String,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct BlockDiff {
+    pub block_id: String,
+    /// Flavour in the new state
+    pub flavour: String,
+    /// Changed props without the `prop:` prefix, sorted by key
+    pub props: Vec<PropChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<ChildrenChange>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct PropChange {
+    pub key: String,
+    /// None if the prop is added
+    #[schema(value_type = Option<Object>)]
+    pub old: Option<Any>,
+    /// None if the prop is removed
+    #[schema(value_type = Option<Object>)]
+    pub new: Option<Any>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct ChildrenChange {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// The children that exist in both states are in a different order
+    pub reordered: bool,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl ChildrenChange {
+    pub(super) fn new(old: Vec<String>, new: Vec<String>) -> Option<Self> {
+        if old == new {
+            return None;
+        }
+
+        let old_set = old.iter().collect::<HashSet<_>>();
+        let new_set = new.iter().collect::<HashSet<_>>();
+        let added = new
+            .iter()
+            .filter(|id| !old_set.contains(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        let removed = old
+            .iter()
+            .filter(|id| !new_set.contains(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        let reordered = !old
+            .iter()
+            .filter(|id| new_set.contains(id))
+            .eq(new.iter().filter(|id| old_set.contains(id)));
+
+        Some(Self {
+            added,
+            removed,
+            reordered,
+            old,
+            new,
+        })
+    }
+}
+
+/// The content of a block that takes part in the diff.
+struct BlockState {
+    flavour: String,
+    props: HashMap<String, Any>,
+    children: Vec<String>,
+}
+
+/// Block states keyed by space id and block id.
+type SpaceStates = BTreeMap<String, BTreeMap<String, BlockState>>;
+
+impl BlockState {
+    fn diff(&self, block_id: &str, new: &BlockState) -> Option<BlockDiff> {
+        let keys = self
+            .props
+            .keys()
+            .chain(new.props.keys())
+            .collect::<BTreeSet<_>>();
+        let props = keys
+            .into_iter()
+            .filter_map(|key| {
+                let old = self.props.get(key);
+                let new = new.props.get(key);
+                (old != new).then(|| PropChange {
+                    key: key.clone(),
+                    old: old.cloned(),
+                    new: new.cloned(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let children = ChildrenChange::new(self.children.clone(), new.children.clone());
+
+        if props.is_empty() && children.is_none() && self.flavour == new.flavour {
+            None
+        } else {
+            Some(BlockDiff {
+                block_id: block_id.to_owned(),
+                flavour: new.flavour.clone(),
+                props,
+                children,
+            })
+        }
+    }
+}
+
+/// The blocks map of every space in the doc, keyed by space id.
+pub(super) fn space_maps<T: ReadTxn>(trx: &T) -> Vec<(String, MapRef)> {
+    trx.store()
+        .root_keys()
+        .into_iter()
+        .filter(|key| key != constants::space::UPDATED && key != constants::space::META)
+        .filter_map(|key| {
+            let space_id = key.strip_prefix("space:")?.to_owned();
+            Some((space_id, trx.get_map(&key)?))
+        })
+        .collect()
+}
+
+pub(super) fn block_flavour<T: ReadTxn>(trx: &T, block: &MapRef) -> String {
+    block
+        .get(trx, sys::FLAVOR)
+        .or_else(|| block.get(trx, sys::FLAVOUR))
+        .unwrap_or_default()
+        .to_string(trx)
+}
+
+pub(super) fn block_children<T: ReadTxn>(trx: &T, block: &MapRef) -> Vec<String> {
+    block
+        .get(trx, sys::CHILDREN)
+        .and_then(|children| children.to_yarray())
+        .map(|children| children.iter(trx).map(|id| id.to_string(trx)).collect())
+        .unwrap_or_default()
+}
+
+/// Read the blocks of every space in the doc.
+fn read_spaces<T: ReadTxn>(trx: &T) -> SpaceStates {
+    space_maps(trx)
+        .into_iter()
+        .map(|(space_id, space)| {
+            let blocks = space
+                .iter(trx)
+                .filter_map(|(block_id, block)| {
+                    let block = block.to_ymap()?;
+                    let props = block
+                        .iter(trx)
+                        .filter_map(|(key, value)| {
+                            key.strip_prefix("prop:")
+                                .map(|key| (key.to_owned(), value.to_json(trx)))
+                        })
+                        .collect();
+
+                    Some((
+                        block_id.to_owned(),
+                        BlockState {
+                            flavour: block_flavour(trx, &block),
+                            props,
+                            children: block_children(trx, &block),
+                        },
+                    ))
+                })
+                .collect();
+
+            (space_id, blocks)
+        })
+        .collect()
+}
+
+/// Compare the blocks of two docs.
+fn diff_docs(old: &Doc, new: &Doc) -> WorkspaceDiff {
+    diff_states(&read_spaces(&old.transact()), &read_spaces(&new.transact()))
+}
+
+fn diff_states(old: &SpaceStates, new: &SpaceStates) -> WorkspaceDiff {
+    let empty = BTreeMap::new();
+
+    let mut space_ids = old.keys().chain(new.keys()).cloned().collect::<Vec<_>>();
+    space_ids.sort();
+    space_ids.dedup();
+
+    let spaces = space_ids
+        .into_iter()
+        .filter_map(|space_id| {
+            let old_blocks = old.get(&space_id).unwrap_or(&empty);
+            let new_blocks = new.get(&space_id).unwrap_or(&empty);
+
+            let summary = |(block_id, block): (&String, &BlockState)| BlockSummary {
+                block_id: block_id.clone(),
+                flavour: block.flavour.clone(),
+            };
+
+            let diff = SpaceDiff {
+                added: new_blocks
+                    .iter()
+                    .filter(|(block_id, _)| !old_blocks.contains_key(*block_id))
+                    .map(summary)
+                    .collect(),
+                removed: old_blocks
+                    .iter()
+                    .filter(|(block_id, _)| !new_blocks.contains_key(*block_id))
+                    .map(summary)
+                    .collect(),
+                changed: old_blocks
+                    .iter()
+                    .filter_map(|(block_id, old)| {
+                        new_blocks
+                            .get(block_id)
+                            .and_then(|new| old.diff(block_id, new))
+                    })
+                    .collect(),
+                space_id,
+            };
+
+            (!diff.is_empty()).then_some(diff)
+        })
+        .collect();
+
+    WorkspaceDiff { spaces }
+}
+
+impl WorkspaceSnapshot {
+    /// Compare the blocks of this snapshot with a later snapshot.
+    pub fn diff(&self, to: &WorkspaceSnapshot) -> JwstResult<WorkspaceDiff> {
+        Ok(diff_docs(&self.load_doc()?, &to.load_doc()?))
+    }
+}
+
+impl Workspace {
+    /// Compare the blocks of the snapshot with the current state of the workspace.
+    pub fn diff_blocks(&self, snapshot: &WorkspaceSnapshot) -> JwstResult<WorkspaceDiff> {
+        Ok(diff_docs(&snapshot.load_doc()?, &self.doc()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_blocks() {
+        let workspace = Workspace::new("test");
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("page");
+            let root = space.create(&mut t.trx, "root", "affine:page");
+            for id in ["a", "b", "c"] {
+                let block = space.create(&mut t.trx, id, "affine:paragraph");
+                block.set(&mut t.trx, "text", id);
+                root.push_children(&mut t.trx, &block);
+            }
+        });
+        let snapshot = workspace.snapshot("yesterday");
+        assert!(workspace.diff_blocks(&snapshot).unwrap().is_empty());
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("page");
+            let root = space.get(&t.trx, "root").unwrap();
+            let a = space.get(&t.trx, "a").unwrap();
+            a.set(&mut t.trx, "text", "changed");
+            a.set(&mut t.trx, "type", "h1");
+            root.push_children(&mut t.trx, &a);
+            space.remove(&mut t.trx, "b");
+            root.remove_children(&mut t.trx, &space.get(&t.trx, "c").unwrap());
+            let d = space.create(&mut t.trx, "d", "affine:divider");
+            root.insert_children_at(&mut t.trx, &d, 0);
+        });
+
+        let diff = workspace.diff_blocks(&snapshot).unwrap();
+        assert_eq!(diff.spaces.len(), 1);
+        let space = &diff.spaces[0];
+        assert_eq!(space.space_id, "page");
+        assert_eq!(
+            space.added,
+            vec![BlockSummary {
+                block_id: "d".into(),
+                flavour: "affine:divider".into()
+            }]
+        );
+        assert_eq!(
+            space
+                .removed
+                .iter()
+                .map(|b| b.block_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b"]
+        );
+
+        let a = space.changed.iter().find(|b| b.block_id == "a").unwrap();
+        assert_eq!(
+            a.props,
+            vec![
+                PropChange {
+                    key: "text".into(),
+                    old: Some(Any::String("a".into())),
+                    new: Some(Any::String("changed".into())),
+                },
+                PropChange {
+                    key: "type".into(),
+                    old: None,
+                    new: Some(Any::String("h1".into())),
+                }
+            ]
+        );
+        assert_eq!(a.children, None);
+
+        let root = space.changed.iter().find(|b| b.block_id == "root").unwrap();
+        let children = root.children.as_ref().unwrap();
+        assert_eq!(children.added, vec!["d"]);
+        assert_eq!(children.removed, vec!["c"]);
+        assert!(children.reordered);
+        assert_eq!(children.new, vec!["d", "b", "a"]);
+
+        let later = workspace.snapshot("today");
+        assert_eq!(snapshot.diff(&later).unwrap(), diff);
+    }
+}
@@ -0,0 +1,440 @@
+use super::{
+    diff::{block_children, block_flavour, space_maps},
+    *,
+};
+use crate::{constants::sys, ChildrenChange, JwstError, JwstResult};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    rc::Rc,
+};
+use yrs::{
+    types::{DeepEventsSubscription, EntryChange, Event, PathSegment},
+    DeepObservable, Map, MapRef, Transact, TransactionMut, UpdateSubscription,
+};
+
+/// A change of a block made by a transaction, local or remote.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockEvent {
+    Created {
+        space_id: String,
+        block_id: String,
+        flavour: String,
+    },
+    /// Props are added, changed or removed, the keys are without the `prop:` prefix
+    PropsChanged {
+        space_id: String,
+        block_id: String,
+        keys: Vec<String>,
+    },
+    Deleted {
+        space_id: String,
+        block_id: String,
+    },
+    ChildrenChanged {
+        space_id: String,
+        block_id: String,
+        children: ChildrenChange,
+    },
+    FlavourChanged {
+        space_id: String,
+        block_id: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl BlockEvent {
+    pub fn space_id(&self) -> &str {
+        match self {
+            Self::Created { space_id, .. }
+            | Self::PropsChanged { space_id, .. }
+            | Self::Deleted { space_id, .. }
+            | Self::ChildrenChanged { space_id, .. }
+            | Self::FlavourChanged { space_id, .. } => space_id,
+        }
+    }
+
+    pub fn block_id(&self) -> &str {
+        match self {
+            Self::Created { block_id, .. }
+            | Self::PropsChanged { block_id, .. }
+            | Self::Deleted { block_id, .. }
+            | Self::ChildrenChanged { block_id, .. }
+            | Self::FlavourChanged { block_id, .. } => block_id,
+        }
+    }
+}
+
+/// The changes of a block made by a transaction, collected from its events.
+#[derive(Default)]
+struct BlockChanges {
+    flavour: Option<(String, String)>,
+    props: BTreeSet<String>,
+    children: bool,
+}
+
+/// The changes of a space made by a transaction, keyed by block id.
+#[derive(Default)]
+struct SpaceChanges {
+    inserted: BTreeSet<String>,
+    removed: BTreeSet<String>,
+    changed: BTreeMap<String, BlockChanges>,
+}
+
+impl SpaceChanges {
+    fn block(&mut self, block_id: &str) -> &mut BlockChanges {
+        self.changed.entry(block_id.to_owned()).or_default()
+    }
+
+    /// Record a deep event of the blocks map, the first segment of the path is the block id.
+    fn record(&mut self, trx: &TransactionMut, event: &Event) {
+        let mut path = event.path();
+        match (path.pop_front(), path.pop_front(), event) {
+            // blocks are created, removed or replaced
+            (None, _, Event::Map(event)) => {
+                for (block_id, change) in event.keys(trx) {
+                    if !matches!(change, EntryChange::Removed(_)) {
+                        self.inserted.insert(block_id.to_string());
+                    }
+                    if !matches!(change, EntryChange::Inserted(_)) {
+                        self.removed.insert(block_id.to_string());
+                    }
+                }
+            }
+            // keys of a block are changed
+            (Some(PathSegment::Key(block_id)), None, Event::Map(event)) => {
+                let block = self.block(&block_id);
+                for (key, change) in event.keys(trx) {
+                    if key.as_ref() == sys::FLAVOR || key.as_ref() == sys::FLAVOUR {
+                        if let EntryChange::Updated(old, new) = change {
+                            block.flavour = Some((old.to_string(trx), new.to_string(trx)));
+                        }
+                    } else {
+                        block.record_key(key);
+                    }
+                }
+            }
+            // the content of the children or of a prop is changed, e.g. rich text
+            (Some(PathSegment::Key(block_id)), Some(PathSegment::Key(key)), _) => {
+                self.block(&block_id).record_key(&key);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl BlockChanges {
+    fn record_key(&mut self, key: &str) {
+        if key == sys::CHILDREN {
+            self.children = true;
+        } else if let Some(key) = key.strip_prefix("prop:") {
+            self.props.insert(key.to_owned());
+        }
+    }
+}
+
+/// A space whose blocks are observed, the children of the blocks are kept to tell
+/// how they changed, the removed children can't be read from the events.
+struct ObservedSpace {
+    blocks: MapRef,
+    children: HashMap<String, Vec<String>>,
+    _sub: DeepEventsSubscription,
+}
+
+impl ObservedSpace {
+    fn new(
+        mut blocks: MapRef,
+        space_id: String,
+        children: HashMap<String, Vec<String>>,
+        pending: Rc<RefCell<BTreeMap<String, SpaceChanges>>>,
+    ) -> Self {
+        let sub = blocks.observe_deep(move |trx, events| {
+            let mut pending = pending.borrow_mut();
+            let changes = pending.entry(space_id.clone()).or_default();
+            for event in events.iter() {
+                changes.record(trx, event);
+            }
+        });
+
+        Self {
+            blocks,
+            children,
+            _sub: sub,
+        }
+    }
+
+    /// Turn the changes into events, grouped by block. Only the changed blocks are read.
+    fn events(
+        &mut self,
+        trx: &TransactionMut,
+        space_id: &str,
+        changes: SpaceChanges,
+    ) -> Vec<BlockEvent> {
+        let mut events = vec![];
+        let block = |block_id: &str| self.blocks.get(trx, block_id).and_then(|b| b.to_ymap());
+
+        for block_id in &changes.inserted {
+            if changes.removed.contains(block_id) {
+                events.push(BlockEvent::Deleted {
+                    space_id: space_id.to_owned(),
+                    block_id: block_id.clone(),
+                });
+            }
+            if let Some(block) = block(block_id) {
+                events.push(BlockEvent::Created {
+                    space_id: space_id.to_owned(),
+                    block_id: block_id.clone(),
+                    flavour: block_flavour(trx, &block),
+                });
+                self.children
+                    .insert(block_id.clone(), block_children(trx, &block));
+            }
+        }
+
+        for (block_id, block_changes) in changes.changed {
+            if changes.inserted.contains(&block_id) || changes.removed.contains(&block_id) {
+                continue;
+            }
+            if let Some((old, new)) = block_changes.flavour.filter(|(old, new)| old != new) {
+                events.push(BlockEvent::FlavourChanged {
+                    space_id: space_id.to_owned(),
+                    block_id: block_id.clone(),
+                    old,
+                    new,
+                });
+            }
+            if !block_changes.props.is_empty() {
+                events.push(BlockEvent::PropsChanged {
+                    space_id: space_id.to_owned(),
+                    block_id: block_id.clone(),
+                    keys: block_changes.props.into_iter().collect(),
+                });
+            }
+            if block_changes.children {
+                let new = block(&block_id)
+                    .map(|block| block_children(trx, &block))
+                    .unwrap_or_default();
+                let old = self.children.insert(block_id.clone(), new.clone());
+                if let Some(children) = ChildrenChange::new(old.unwrap_or_default(), new) {
+                    events.push(BlockEvent::ChildrenChanged {
+                        space_id: space_id.to_owned(),
+                        block_id,
+                        children,
+                    });
+                }
+            }
+        }
+
+        for block_id in changes.removed {
+            if !changes.inserted.contains(&block_id) {
+                self.children.remove(&block_id);
+                events.push(BlockEvent::Deleted {
+                    space_id: space_id.to_owned(),
+                    block_id,
+                });
+            }
+        }
+
+        events
+    }
+}
+
+impl Workspace {
+    /// Subscribe to the block changes of every space, the callback is called once for
+    /// each transaction that changes any block.
+    ///
+    /// The events are built from the changes observed in each space, so only the blocks
+    /// touched by the transaction are read. Fails if a transaction of the workspace is alive.
+    pub fn observe_blocks(
+        &mut self,
+        f: impl Fn(&[BlockEvent]) + 'static,
+    ) -> JwstResult<UpdateSubscription> {
+        let pending = Rc::new(RefCell::new(BTreeMap::new()));
+        let spaces = {
+            let trx = self.doc().try_transact().map_err(|e| {
+                JwstError::BoxedError(anyhow::anyhow!("failed to observe blocks: {e:?}"))
+            })?;
+            space_maps(&trx)
+                .into_iter()
+                .map(|(space_id, blocks)| {
+                    let children = blocks
+                        .iter(&trx)
+                        .filter_map(|(block_id, block)| {
+                            let block = block.to_ymap()?;
+                            Some((block_id.to_owned(), block_children(&trx, &block)))
+                        })
+                        .collect();
+                    (space_id, blocks, children)
+                })
+                .collect::<Vec<_>>()
+        };
+        let spaces = spaces
+            .into_iter()
+            .map(|(space_id, blocks, children)| {
+                let space = ObservedSpace::new(blocks, space_id.clone(), children, pending.clone());
+                (space_id, space)
+            })
+            .collect::<HashMap<_, _>>();
+        let spaces = Rc::new(RefCell::new(spaces));
+        let f = Rc::new(f);
+
+        self.observe(move |trx, _| {
+            let mut spaces = spaces.borrow_mut();
+
+            // a space created by this transaction is not observed yet, all its blocks are new
+            for (space_id, blocks) in space_maps(trx) {
+                if !spaces.contains_key(&space_id) {
+                    let changes = SpaceChanges {
+                        inserted: blocks.keys(trx).map(|id| id.to_owned()).collect(),
+                        ..Default::default()
+                    };
+                    pending.borrow_mut().insert(space_id.clone(), changes);
+                    let space = ObservedSpace::new(
+                        blocks,
+                        space_id.clone(),
+                        HashMap::new(),
+                        pending.clone(),
+                    );
+                    spaces.insert(space_id, space);
+                }
+            }
+
+            let changes = std::mem::take(&mut *pending.borrow_mut());
+            let events = changes
+                .into_iter()
+                .filter_map(|(space_id, changes)| {
+                    let space = spaces.get_mut(&space_id)?;
+                    Some(space.events(trx, &space_id, changes))
+                })
+                .flatten()
+                .collect::<Vec<_>>();
+
+            if !events.is_empty() {
+                f(&events);
+            }
+        })
+        .ok_or_else(|| JwstError::BoxedError(anyhow::anyhow!("failed to observe blocks")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn observe_blocks() {
+        let mut workspace = Workspace::new("test");
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+            space.create(&mut t.trx, "root", "affine:page");
+        });
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let _sub = {
+            let events = events.clone();
+            workspace
+                .observe_blocks(move |e| events.borrow_mut().push(e.to_vec()))
+                .unwrap()
+        };
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+            let root = space.get(&t.trx, "root").unwrap();
+            let block = space.create(&mut t.trx, "block", "affine:paragraph");
+            block.set(&mut t.trx, "text", "hello");
+            root.push_children(&mut t.trx, &block);
+        });
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+            let block = space.get(&t.trx, "block").unwrap();
+            block.set(&mut t.trx, "text", "world");
+            block.set(&mut t.trx, "type", "h1");
+            block
+                .map_ref()
+                .insert(&mut t.trx, sys::FLAVOR, "affine:list");
+        });
+        workspace.with_trx(|mut t| {
+            t.get_space("space").remove(&mut t.trx, "block");
+        });
+        // a space created after subscribing is observed as well
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space2");
+            space.create(&mut t.trx, "block", "affine:page");
+        });
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space2");
+            let block = space.get(&t.trx, "block").unwrap();
+            block.set(&mut t.trx, "title", "page");
+        });
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 5);
+        assert_eq!(
+            events[0],
+            vec![
+                BlockEvent::Created {
+                    space_id: "space".into(),
+                    block_id: "block".into(),
+                    flavour: "affine:paragraph".into(),
+                },
+                BlockEvent::ChildrenChanged {
+                    space_id: "space".into(),
+                    block_id: "root".into(),
+                    children: ChildrenChange {
+                        added: vec!["block".into()],
+                        removed: vec![],
+                        reordered: false,
+                        old: vec![],
+                        new: vec!["block".into()],
+                    },
+                },
+            ]
+        );
+        assert_eq!(
+            events[1],
+            vec![
+                BlockEvent::FlavourChanged {
+                    space_id: "space".into(),
+                    block_id: "block".into(),
+                    old: "affine:paragraph".into(),
+                    new: "affine:list".into(),
+                },
+                BlockEvent::PropsChanged {
+                    space_id: "space".into(),
+                    block_id: "block".into(),
+                    keys: vec!["text".into(), "type".into()],
+                },
+            ]
+        );
+        assert_eq!(
+            events[2],
+            vec![BlockEvent::Deleted {
+                space_id: "space".into(),
+                block_id: "block".into(),
+            }]
+        );
+        assert_eq!(events[2][0].block_id(), "block");
+        assert_eq!(
+            events[3],
+            vec![BlockEvent::Created {
+                space_id: "space2".into(),
+                block_id: "block".into(),
+                flavour: "affine:page".into(),
+            }]
+        );
+        assert_eq!(
+            events[4],
+            vec![BlockEvent::PropsChanged {
+                space_id: "space2".into(),
+                block_id: "block".into(),
+                keys: vec!["title".into()],
+            }]
+        );
+
+        // the blocks can't be read while a transaction is alive
+        let doc = workspace.doc();
+        let _trx = doc.transact_mut();
+        assert!(workspace.observe_blocks(|_| {}).is_err());
+    }
+}
@@ -3,15 +3,82 @@ use std::collections::HashMap;
 use lib0::any::Any;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use yrs::{Map, MapRef, Transaction};
 
 pub const SEARCH_INDEX: &str = "search_index";
+pub const SEARCH_ANALYZER: &str = "search_analyzer";
+
+/// How the text of the blocks is split into terms when it is indexed and searched
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, JsonSchema, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchAnalyzer {
+    /// Overlapping sequences of up to 10 characters, works for any language
+    /// but makes the index large and ranks whole words poorly
+    #[default]
+    Ngram,
+    /// Dictionary based word segmentation for Chinese, other text is split by words
+    Cjk,
+    // words are reduced to their stems in the following languages
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+}
+
+impl SearchAnalyzer {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ngram => "ngram",
+            Self::Cjk => "cjk",
+            Self::Danish => "danish",
+            Self::Dutch => "dutch",
+            Self::English => "english",
+            Self::Finnish => "finnish",
+            Self::French => "french",
+            Self::German => "german",
+            Self::Greek => "greek",
+            Self::Hungarian => "hungarian",
+            Self::Italian => "italian",
+            Self::Norwegian => "norwegian",
+            Self::Portuguese => "portuguese",
+            Self::Romanian => "romanian",
+            Self::Russian => "russian",
+            Self::Spanish => "spanish",
+            Self::Swedish => "swedish",
+        }
+    }
+}
+
+impl std::str::FromStr for SearchAnalyzer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_owned()))
+            .map_err(|_| format!("unknown search analyzer: {s}"))
+    }
+}
 
 #[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
 pub struct WorkspaceMetadata {
     pub name: Option<String>,
     // pub avatar: Option<String>,
     pub search_index: Vec<String>,
+    #[serde(default)]
+    pub search_analyzer: SearchAnalyzer,
 }
 
 impl From<(&'_ Transaction<'_>, MapRef)> for WorkspaceMetadata {
@@ -23,6 +90,10 @@ impl From<(&'_ Transaction<'_>, MapRef)> for WorkspaceMetadata {
                 Some(value) => serde_json::from_str::<Vec<String>>(&value.to_string(trx)).unwrap(),
                 None => vec!["title".to_string(), "text".to_string()],
             },
+            search_analyzer: map
+                .get(trx, SEARCH_ANALYZER)
+                .and_then(|s| s.to_string(trx).parse().ok())
+                .unwrap_or_default(),
         }
     }
 }
@@ -37,6 +108,10 @@ impl From<WorkspaceMetadata> for Any {
         //     map.insert("avatar".to_owned(), avatar.into());
         // }
         map.insert(SEARCH_INDEX.to_owned(), val.search_index.into());
+        map.insert(
+            SEARCH_ANALYZER.to_owned(),
+            val.search_analyzer.as_str().into(),
+        );
         Any::Map(map.into())
     }
 }
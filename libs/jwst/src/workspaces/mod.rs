@@ -1,13 +1,22 @@
+mod diff;
+mod events;
 mod metadata;
 mod plugins;
+mod snapshot;
 mod transaction;
 mod workspace;
 
 use super::{constants, error, info, trace, warn, Space};
 use plugins::PluginMap;
 
-pub use metadata::WorkspaceMetadata;
+pub use diff::{BlockDiff, BlockSummary, ChildrenChange, PropChange, SpaceDiff, WorkspaceDiff};
+pub use events::BlockEvent;
+pub use metadata::{SearchAnalyzer, WorkspaceMetadata};
+pub use plugins::{PluginImpl, PluginRegister, Reference, ReferenceKind};
 #[cfg(feature = "workspace-search")]
-pub use plugins::{SearchResult, SearchResults};
+pub use plugins::{
+    SearchMode, SearchOptions, SearchOrder, SearchResult, SearchResults, Suggestion,
+};
+pub use snapshot::WorkspaceSnapshot;
 pub use transaction::WorkspaceTransaction;
 pub use workspace::{MapSubscription, Workspace};
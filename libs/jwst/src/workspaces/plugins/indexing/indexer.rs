@@ -1,45 +1,242 @@
-use super::{PluginImpl, Workspace};
-use lib0::any::Any;
+use super::{
+    register::IndexingStorageKind,
+    search,
+    tracker::{observe_space, DirtyBlocks},
+    warn, PluginImpl, SearchAnalyzer, Workspace,
+};
+use crate::{Block, Space};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::ops::Bound;
 use std::rc::Rc;
-use std::sync::{atomic::AtomicU32, Arc};
-use tantivy::{collector::TopDocs, query::QueryParser, schema::*, Index, ReloadPolicy};
+use std::sync::{Arc, Mutex};
+use tantivy::{
+    collector::{Count, TopDocs},
+    query::{
+        AllQuery, BooleanQuery, BoostQuery, EmptyQuery, FuzzyTermQuery, Occur, Query, QueryClone,
+        QueryParser, RangeQuery, TermQuery,
+    },
+    schema::*,
+    DocId, Index, ReloadPolicy, Score, SegmentReader, SnippetGenerator,
+};
 use utoipa::ToSchema;
+use yrs::{types::DeepEventsSubscription, updates::encoder::Encode, ReadTxn, TransactionMut};
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SearchResult {
+    /// `space_id:block_id` of the matched block
     pub block_id: String,
+    #[serde(default)]
+    pub space_id: String,
     pub score: f32,
+    /// Highlighted fragments of the matched fields keyed by field name,
+    /// the matched terms are wrapped in `<b>` and the rest is html escaped.
+    #[serde(default)]
+    pub snippets: HashMap<String, String>,
 }
 
 /// Returned from [`Workspace::search`]
 ///
 /// [`Workspace::search`]: crate::Workspace::search
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchResults {
+    /// Number of all matched blocks, not only the returned ones
+    pub total: usize,
+    pub offset: usize,
+    pub items: Vec<SearchResult>,
+}
+
+/// How the results of [`Workspace::search_with_options`] are sorted
+///
+/// [`Workspace::search_with_options`]: crate::Workspace::search_with_options
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchOrder {
+    /// Best matched first
+    #[default]
+    Relevance,
+    /// Last updated first, blocks updated at the same time are sorted by relevance
+    Recency,
+}
+
+/// How the query text of [`Workspace::search_with_options`] is read
+///
+/// [`Workspace::search_with_options`]: crate::Workspace::search_with_options
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// The query syntax of tantivy, e.g. `title:foo AND -bar`, invalid syntax is an error
+    #[default]
+    Syntax,
+    /// Plain words without syntax, typos are tolerated and the last word
+    /// also matches as a prefix, since it may not be typed completely
+    Forgiving,
+}
+
+/// A block title that starts with the typed text, returned from [`Workspace::suggest`]
+///
+/// [`Workspace::suggest`]: crate::Workspace::suggest
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct SearchResults(Vec<SearchResult>);
+pub struct Suggestion {
+    /// `space_id:block_id` of the block
+    pub block_id: String,
+    pub space_id: String,
+    pub title: String,
+    pub score: f32,
+}
+
+/// Pagination and filters of [`Workspace::search_with_options`]
+///
+/// The timestamps are unix timestamps in milliseconds, both ends of a range are included.
+///
+/// [`Workspace::search_with_options`]: crate::Workspace::search_with_options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub offset: usize,
+    pub limit: usize,
+    /// Only match the blocks of this flavour
+    pub flavour: Option<String>,
+    /// Only match the blocks in this space
+    pub space_id: Option<String>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+    pub updated_after: Option<u64>,
+    pub updated_before: Option<u64>,
+    pub order: SearchOrder,
+    pub mode: SearchMode,
+}
+
+impl SearchOptions {
+    /// Most results returned by a search, a larger limit is clamped.
+    pub const MAX_LIMIT: usize = 100;
+    /// Most results skipped by a search, a larger offset is clamped.
+    pub const MAX_OFFSET: usize = 10_000;
+
+    /// Whether the limit and offset are within [Self::MAX_LIMIT] and [Self::MAX_OFFSET].
+    pub fn in_bounds(&self) -> bool {
+        self.limit <= Self::MAX_LIMIT && self.offset <= Self::MAX_OFFSET
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: 10,
+            flavour: None,
+            space_id: None,
+            created_after: None,
+            created_before: None,
+            updated_after: None,
+            updated_before: None,
+            order: SearchOrder::Relevance,
+            mode: SearchMode::Syntax,
+        }
+    }
+}
+
+/// The content of a block written to the index.
+pub(super) struct IndexedBlock {
+    pub(super) space_id: String,
+    pub(super) block_id: String,
+    pub(super) flavour: String,
+    pub(super) created: u64,
+    pub(super) updated: u64,
+    /// Text of the fields in `search_index`, in the same order
+    pub(super) fields: Vec<Option<String>>,
+}
 
 pub struct IndexingPluginImpl {
-    // /// `true` if the text search has not yet populated the Tantivy index
-    // /// `false` if there should only be incremental changes necessary to the blocks.
-    // first_index: bool,
-    pub(super) queue_reindex: Arc<AtomicU32>,
+    pub(super) storage_kind: IndexingStorageKind,
     pub(super) schema: Schema,
     pub(super) index: Rc<Index>,
     pub(super) query_parser: QueryParser,
-    // need to keep so it gets dropped with this plugin
-    pub(super) _update_sub: Option<yrs::UpdateSubscription>,
     pub(super) search_index: Vec<String>,
+    pub(super) analyzer: SearchAnalyzer,
+    /// Blocks to reindex on the next update
+    pub(super) dirty: Arc<Mutex<DirtyBlocks>>,
+    /// Observers of the spaces keyed by space id,
+    /// need to keep so they get dropped with this plugin
+    pub(super) observers: HashMap<String, DeepEventsSubscription>,
+}
+
+/// The field of the block titles that are suggested
+const TITLE: &str = "title";
+
+fn range_query(field: Field, after: Option<u64>, before: Option<u64>) -> Option<Box<dyn Query>> {
+    if after.is_none() && before.is_none() {
+        return None;
+    }
+    let bound = |value: Option<u64>| value.map_or(Bound::Unbounded, Bound::Included);
+
+    Some(Box::new(RangeQuery::new_u64_bounds(
+        field,
+        bound(after),
+        bound(before),
+    )))
 }
 
 impl IndexingPluginImpl {
+    fn field(&self, name: &str) -> Field {
+        self.schema.get_field(name).unwrap()
+    }
+
+    /// Combine the text query with the filters, an empty text matches all blocks.
+    fn build_query(&self, text_query: &dyn Query, options: &SearchOptions) -> Box<dyn Query> {
+        let term = |name: &str, value: &str| -> Box<dyn Query> {
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.field(name), value),
+                IndexRecordOption::Basic,
+            ))
+        };
+
+        let mut clauses = vec![(Occur::Must, text_query.box_clone())];
+        if let Some(flavour) = &options.flavour {
+            clauses.push((Occur::Must, term(search::FLAVOUR, flavour)));
+        }
+        if let Some(space_id) = &options.space_id {
+            clauses.push((Occur::Must, term(search::SPACE_ID, space_id)));
+        }
+        let ranges = [
+            range_query(
+                self.field(search::CREATED),
+                options.created_after,
+                options.created_before,
+            ),
+            range_query(
+                self.field(search::UPDATED),
+                options.updated_after,
+                options.updated_before,
+            ),
+        ];
+        clauses.extend(
+            ranges
+                .into_iter()
+                .flatten()
+                .map(|query| (Occur::Must, query)),
+        );
+
+        if clauses.len() == 1 {
+            text_query.box_clone()
+        } else {
+            Box::new(BooleanQuery::new(clauses))
+        }
+    }
+
     pub fn search<S: AsRef<str>>(
         &self,
         query: S,
+        options: &SearchOptions,
     ) -> Result<SearchResults, Box<dyn std::error::Error>> {
-        let mut items = Vec::new();
+        let offset = options.offset.min(SearchOptions::MAX_OFFSET);
+        let limit = options.limit.min(SearchOptions::MAX_LIMIT);
+        let mut results = SearchResults {
+            offset,
+            ..Default::default()
+        };
         if self.search_index.is_empty() {
-            return Ok(SearchResults(items));
+            return Ok(results);
         }
 
         let reader = self
@@ -48,20 +245,87 @@ impl IndexingPluginImpl {
             .reload_policy(ReloadPolicy::OnCommit)
             .try_into()?;
         let searcher = reader.searcher();
-        let query = self.query_parser.parse_query(query.as_ref())?;
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(10))?;
-        // The actual documents still need to be retrieved from Tantivy’s store.
-        // Since the body field was not configured as stored, the document returned will only contain a title.
+        let text_query: Box<dyn Query> = match (query.as_ref().trim(), options.mode) {
+            ("", _) => Box::new(AllQuery),
+            (query, SearchMode::Syntax) => self.query_parser.parse_query(query)?,
+            (query, SearchMode::Forgiving) => {
+                let fields = self
+                    .search_index
+                    .iter()
+                    .map(|name| self.field(name))
+                    .collect::<Vec<_>>();
+                self.forgiving_query(query, &fields)?
+            }
+        };
+        let query = self.build_query(&*text_query, options);
+        if limit == 0 {
+            // TopDocs panics on zero limit
+            results.total = searcher.search(&query, &Count)?;
+            return Ok(results);
+        }
+        let collector = TopDocs::with_limit(limit).and_offset(offset);
+        let top_docs = match options.order {
+            SearchOrder::Relevance => {
+                let (top_docs, total) = searcher.search(&query, &(collector, Count))?;
+                results.total = total;
+                top_docs
+            }
+            SearchOrder::Recency => {
+                let updated = self.field(search::UPDATED);
+                let collector = collector.tweak_score(move |segment: &SegmentReader| {
+                    let updated = segment
+                        .fast_fields()
+                        .u64(updated)
+                        .expect("updated is a fast field");
+                    move |doc: DocId, score: Score| (updated.get_val(doc.into()), score)
+                });
+                let (top_docs, total) = searcher.search(&query, &(collector, Count))?;
+                results.total = total;
+                top_docs
+                    .into_iter()
+                    .map(|((_, score), doc_address)| (score, doc_address))
+                    .collect()
+            }
+        };
 
         if !top_docs.is_empty() {
-            let block_id_field = self.schema.get_field("block_id").unwrap();
+            let block_id_field = self.field(search::BLOCK_ID);
+            let space_id_field = self.field(search::SPACE_ID);
+            let generators = self
+                .search_index
+                .iter()
+                .filter_map(|name| {
+                    let field = self.schema.get_field(name)?;
+                    match SnippetGenerator::create(&searcher, &*text_query, field) {
+                        Ok(generator) => Some((name, generator)),
+                        Err(e) => {
+                            warn!("failed to create snippet generator for {name}: {e}");
+                            None
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
 
             for (score, doc_address) in top_docs {
                 let retrieved_doc = searcher.doc(doc_address)?;
                 if let Some(Value::Str(id)) = retrieved_doc.get_first(block_id_field) {
-                    items.push(SearchResult {
+                    let snippets = generators
+                        .iter()
+                        .filter_map(|(name, generator)| {
+                            let snippet = generator.snippet_from_doc(&retrieved_doc);
+                            (!snippet.highlighted().is_empty())
+                                .then(|| (name.to_string(), snippet.to_html()))
+                        })
+                        .collect();
+                    let space_id = retrieved_doc
+                        .get_first(space_id_field)
+                        .and_then(|value| value.as_text())
+                        .unwrap_or_default();
+                    results.items.push(SearchResult {
                         block_id: id.to_string(),
+                        space_id: space_id.to_string(),
                         score,
+                        snippets,
                     });
                 } else {
                     let to_json = self.schema.to_json(&retrieved_doc);
@@ -70,90 +334,274 @@ impl IndexingPluginImpl {
             }
         }
 
-        Ok(SearchResults(items))
+        Ok(results)
+    }
+}
+
+impl IndexingPluginImpl {
+    /// Match the words of the text in the fields, the text is split by the analyzer
+    /// of the workspace instead of being parsed, so any input is valid.
+    ///
+    /// Ngram tokens already match parts of words, so typos and prefixes only need
+    /// to be handled for the analyzers that split the text into whole words.
+    fn forgiving_query(
+        &self,
+        text: &str,
+        fields: &[Field],
+    ) -> Result<Box<dyn Query>, Box<dyn std::error::Error>> {
+        let first = match fields.first() {
+            Some(field) => *field,
+            None => return Ok(Box::new(EmptyQuery)),
+        };
+        let mut words = vec![];
+        self.index
+            .tokenizer_for_field(first)?
+            .token_stream(text)
+            .process(&mut |token| {
+                if !words.contains(&token.text) {
+                    words.push(token.text.clone());
+                }
+            });
+        if words.is_empty() {
+            return Ok(Box::new(EmptyQuery));
+        }
+
+        let whole_words = self.analyzer != SearchAnalyzer::Ngram;
+        let last = words.len() - 1;
+        let clauses = words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| {
+                let mut variants: Vec<(Occur, Box<dyn Query>)> = vec![];
+                for field in fields {
+                    let term = Term::from_field_text(*field, word);
+                    // exact matches are ranked above the typos
+                    variants.push((
+                        Occur::Should,
+                        Box::new(BoostQuery::new(
+                            Box::new(TermQuery::new(term.clone(), IndexRecordOption::WithFreqs)),
+                            2.0,
+                        )),
+                    ));
+                    if !whole_words {
+                        continue;
+                    }
+                    let distance = match word.chars().count() {
+                        0..=3 => 0,
+                        4..=7 => 1,
+                        _ => 2,
+                    };
+                    if distance > 0 {
+                        variants.push((
+                            Occur::Should,
+                            Box::new(FuzzyTermQuery::new(term.clone(), distance, true)),
+                        ));
+                    }
+                    if index == last {
+                        variants.push((
+                            Occur::Should,
+                            Box::new(FuzzyTermQuery::new_prefix(term, 0, true)),
+                        ));
+                    }
+                }
+                let occur = if whole_words {
+                    Occur::Must
+                } else {
+                    Occur::Should
+                };
+                (
+                    occur,
+                    Box::new(BooleanQuery::new(variants)) as Box<dyn Query>,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+
+    /// Titles of the blocks that match the typed text, the last word is matched as a prefix.
+    pub fn suggest<S: AsRef<str>>(
+        &self,
+        text: S,
+        limit: usize,
+    ) -> Result<Vec<Suggestion>, Box<dyn std::error::Error>> {
+        let title_field = match self.search_index.iter().find(|name| name.as_str() == TITLE) {
+            Some(name) => self.field(name),
+            None => return Ok(vec![]),
+        };
+        if limit == 0 || text.as_ref().trim().is_empty() {
+            return Ok(vec![]);
+        }
+        let limit = limit.min(SearchOptions::MAX_LIMIT);
+
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let query = self.forgiving_query(text.as_ref(), &[title_field])?;
+
+        let block_id_field = self.field(search::BLOCK_ID);
+        let space_id_field = self.field(search::SPACE_ID);
+        let mut suggestions = vec![];
+        for (score, doc_address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let doc = searcher.doc(doc_address)?;
+            let stored = |field| {
+                doc.get_first(field)
+                    .and_then(|value| value.as_text())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            suggestions.push(Suggestion {
+                block_id: stored(block_id_field),
+                space_id: stored(space_id_field),
+                title: stored(title_field),
+                score,
+            });
+        }
+
+        Ok(suggestions)
     }
 }
 
 impl PluginImpl for IndexingPluginImpl {
     fn on_update(&mut self, ws: &Workspace) -> Result<(), Box<dyn std::error::Error>> {
-        let curr = self.queue_reindex.load(std::sync::atomic::Ordering::SeqCst);
-        if curr > 0 {
-            // TODO: reindex
-
-            let re_index_list = ws.with_trx(|t| {
-                t.spaces(|spaces| {
-                    spaces
-                        .flat_map(|space| {
+        self.observe_spaces(ws, true);
+
+        let dirty = std::mem::take(&mut *self.dirty.lock().unwrap());
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        let (blocks, state_vector) = ws.with_trx(|t| {
+            let blocks = t.spaces(|spaces| {
+                spaces
+                    .flat_map(|space| {
+                        let space_id = space.space_id();
+                        if dirty.spaces.contains(&space_id) {
                             space.blocks(&t.trx, |blocks| {
                                 blocks
-                                    .map(|block| {
-                                        (
-                                            format!("{}:{}", space.space_id(), block.block_id()),
-                                            self.search_index
-                                                .iter()
-                                                .map(|field| {
-                                                    block
-                                                        .content(&t.trx)
-                                                        .get(field)
-                                                        .map(ToOwned::to_owned)
-                                                        .and_then(|a| match a {
-                                                            Any::String(str) => {
-                                                                Some(str.to_string())
-                                                            }
-                                                            _ => None,
-                                                        })
-                                                })
-                                                .collect(),
-                                        )
-                                    })
+                                    .map(|block| self.indexed_block(&t.trx, &space, &block))
                                     .collect::<Vec<_>>()
                             })
-                        })
-                        .collect::<HashMap<_, _>>()
-                })
+                        } else {
+                            // removed blocks are not found and only deleted from the index
+                            dirty
+                                .blocks
+                                .get(&space_id)
+                                .into_iter()
+                                .flatten()
+                                .filter_map(|block_id| space.get(&t.trx, block_id))
+                                .map(|block| self.indexed_block(&t.trx, &space, &block))
+                                .collect()
+                        }
+                    })
+                    .collect::<Vec<_>>()
             });
+            (blocks, t.trx.state_vector().encode_v1())
+        });
 
-            // dbg!((txn, upd));
-            // println!("got update event: {items}");
-            // just re-index stupidly
-            self.re_index_content(re_index_list)
-                .map_err(|err| format!("Error during reindex: {err:?}"))?;
-        }
-
-        // reset back down now that the update was applied
-        self.queue_reindex
-            .fetch_sub(curr, std::sync::atomic::Ordering::SeqCst);
+        self.re_index_content(&dirty, blocks, &state_vector)
+            .map_err(|err| format!("Error during reindex: {err:?}"))?;
 
         Ok(())
     }
 }
 
 impl IndexingPluginImpl {
-    fn re_index_content<BlockIdTitleAndTextIter>(
+    /// Observe the spaces that are not observed yet, new spaces are reindexed
+    /// as a whole since their blocks may be changed before they are observed.
+    pub(super) fn observe_spaces(&mut self, ws: &Workspace, mark_dirty: bool) {
+        let spaces = ws.with_trx(|t| {
+            t.spaces(|spaces| {
+                spaces
+                    .filter(|space| !self.observers.contains_key(&space.space_id()))
+                    .map(|space| (space.space_id(), space.blocks.clone()))
+                    .collect::<Vec<_>>()
+            })
+        });
+
+        for (space_id, blocks) in spaces {
+            if mark_dirty {
+                let mut dirty = self.dirty.lock().unwrap();
+                dirty.blocks.remove(&space_id);
+                dirty.spaces.insert(space_id.clone());
+            }
+            let sub = observe_space(blocks, space_id.clone(), self.dirty.clone());
+            self.observers.insert(space_id, sub);
+        }
+    }
+
+    fn indexed_block(&self, trx: &TransactionMut, space: &Space, block: &Block) -> IndexedBlock {
+        IndexedBlock {
+            space_id: space.space_id(),
+            block_id: block.block_id(),
+            flavour: block.flavor(trx),
+            created: block.created(trx),
+            updated: block.updated(trx),
+            fields: self
+                .search_index
+                .iter()
+                .map(|field| block.get_search_text(trx, field))
+                .collect(),
+        }
+    }
+
+    /// Replace the documents of the dirty blocks with the current content of the blocks,
+    /// the state vector of the doc is committed with them.
+    fn re_index_content<I>(
         &mut self,
-        blocks: BlockIdTitleAndTextIter,
+        dirty: &DirtyBlocks,
+        blocks: I,
+        state_vector: &[u8],
     ) -> Result<(), Box<dyn std::error::Error>>
     where
-        // TODO: use a structure with better names than tuples?
-        BlockIdTitleAndTextIter: IntoIterator<Item = (String, Vec<Option<String>>)>,
+        I: IntoIterator<Item = IndexedBlock>,
     {
-        let block_id_field = self.schema.get_field("block_id").unwrap();
+        let block_id_field = self.field(search::BLOCK_ID);
+        let space_id_field = self.field(search::SPACE_ID);
+        let flavour_field = self.field(search::FLAVOUR);
+        let created_field = self.field(search::CREATED);
+        let updated_field = self.field(search::UPDATED);
 
         let mut writer = self
             .index
             .writer(50_000_000)
             .map_err(|err| format!("Error creating writer: {err:?}"))?;
 
+        if dirty.reset {
+            writer.delete_all_documents()?;
+        }
+        for space_id in &dirty.spaces {
+            writer.delete_term(Term::from_field_text(space_id_field, space_id));
+        }
+        for (space_id, block_ids) in &dirty.blocks {
+            for block_id in block_ids {
+                writer.delete_term(Term::from_field_text(
+                    block_id_field,
+                    &format!("{space_id}:{block_id}"),
+                ));
+            }
+        }
+
         let search_index = self
             .search_index
             .iter()
             .map(|filed| self.schema.get_field(filed).unwrap())
             .collect::<Vec<_>>();
 
-        for (block_id, fields) in blocks {
+        for block in blocks {
             let mut block_doc = Document::new();
-            block_doc.add_text(block_id_field, block_id);
-            fields.iter().enumerate().for_each(|(index, field)| {
+            block_doc.add_text(
+                block_id_field,
+                format!("{}:{}", block.space_id, block.block_id),
+            );
+            block_doc.add_text(space_id_field, &block.space_id);
+            block_doc.add_text(flavour_field, &block.flavour);
+            block_doc.add_u64(created_field, block.created);
+            block_doc.add_u64(updated_field, block.updated);
+            block.fields.iter().enumerate().for_each(|(index, field)| {
                 if let Some(field_text) = field {
                     let index_field = search_index.get(index).unwrap().to_owned();
                     block_doc.add_text(index_field, field_text);
@@ -164,7 +612,9 @@ impl IndexingPluginImpl {
 
         // If .commit() returns correctly, then all of the documents that have been added
         // are guaranteed to be persistently indexed.
-        writer.commit()?;
+        let mut commit = writer.prepare_commit()?;
+        commit.set_payload(&STANDARD.encode(state_vector));
+        commit.commit()?;
 
         Ok(())
     }
@@ -181,7 +631,7 @@ mod test {
     macro_rules! expect_result_ids {
         ($search_results:ident, $id_str_array:expr) => {
             let mut sorted_ids = $search_results
-                .0
+                .items
                 .iter()
                 .map(|i| &i.block_id)
                 .collect::<Vec<_>>();
@@ -195,7 +645,7 @@ mod test {
     macro_rules! expect_search_gives_ids {
         ($search_plugin:ident, $query_text:expr, $id_str_array:expr) => {
             let search_result = $search_plugin
-                .search($query_text)
+                .search($query_text, &SearchOptions::default())
                 .expect("no error searching");
 
             let line = line!();
@@ -358,7 +808,325 @@ mod test {
 
                 expect_search_gives_ids!(search_plugin, "核聚变反应", &["space1:f", "space2:f1"]);
                 expect_search_gives_ids!(search_plugin, "镭射能量", &["space1:f", "space2:f1"]);
+
+                let options = SearchOptions {
+                    offset: 4,
+                    limit: 4,
+                    ..Default::default()
+                };
+                let results = search_plugin.search("content", &options).unwrap();
+                assert_eq!(results.total, 6);
+                assert_eq!(results.offset, 4);
+                assert_eq!(results.items.len(), 2);
+
+                // the page is clamped instead of overflowing the collector
+                let options = SearchOptions {
+                    offset: usize::MAX,
+                    limit: usize::MAX,
+                    ..Default::default()
+                };
+                assert!(!options.in_bounds());
+                let results = search_plugin.search("content", &options).unwrap();
+                assert_eq!(results.total, 6);
+                assert_eq!(results.offset, SearchOptions::MAX_OFFSET);
+                assert!(results.items.is_empty());
+
+                let options = SearchOptions {
+                    limit: 0,
+                    ..Default::default()
+                };
+                let results = search_plugin.search("bbb", &options).unwrap();
+                assert_eq!(results.total, 2);
+                assert!(results.items.is_empty());
+
+                let results = search_plugin
+                    .search("bbb", &SearchOptions::default())
+                    .unwrap();
+                let snippet = results.items[0].snippets.get("text").unwrap();
+                assert!(snippet.starts_with("Text B content "));
+                assert!(snippet.contains("<b>"));
+                assert_eq!(results.items[0].snippets.get("title"), None);
             })
             .is_some());
     }
+
+    #[test]
+    fn filter_search_test() {
+        let workspace = {
+            let workspace = Workspace::from_doc(Default::default(), "wk-filter");
+            super::super::super::insert_plugin(workspace, IndexingPluginRegister::ram())
+                .expect("failed to insert plugin")
+        };
+
+        workspace.with_trx(|mut t| {
+            let page1 = t.get_space("page1");
+            let code = page1.create(&mut t.trx, "code", "affine:code");
+            code.set(&mut t.trx, "text", "fn foo() {}");
+            let paragraph = page1.create(&mut t.trx, "paragraph", "affine:paragraph");
+            paragraph.set(&mut t.trx, "text", "foo bar");
+
+            let page2 = t.get_space("page2");
+            let code = page2.create(&mut t.trx, "code", "affine:code");
+            code.set(&mut t.trx, "text", "let foo = 1;");
+        });
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let before_update = chrono::Utc::now().timestamp_millis() as u64;
+        workspace.with_trx(|mut t| {
+            let page1 = t.get_space("page1");
+            let paragraph = page1.get(&t.trx, "paragraph").unwrap();
+            paragraph.set(&mut t.trx, "text", "foo bar baz");
+        });
+
+        workspace
+            .update_plugin::<IndexingPluginImpl>()
+            .expect("update text search plugin");
+
+        assert!(workspace
+            .with_plugin::<IndexingPluginImpl, ()>(|search_plugin| {
+                let search = |options: SearchOptions| {
+                    let results = search_plugin.search("foo", &options).unwrap();
+                    results
+                        .items
+                        .into_iter()
+                        .map(|item| item.block_id)
+                        .collect::<Vec<_>>()
+                };
+
+                let mut ids = search(SearchOptions {
+                    flavour: Some("affine:code".into()),
+                    ..Default::default()
+                });
+                ids.sort();
+                assert_eq!(ids, vec!["page1:code", "page2:code"]);
+
+                assert_eq!(
+                    search(SearchOptions {
+                        flavour: Some("affine:code".into()),
+                        space_id: Some("page1".into()),
+                        ..Default::default()
+                    }),
+                    vec!["page1:code"]
+                );
+
+                assert_eq!(
+                    search(SearchOptions {
+                        updated_after: Some(before_update),
+                        ..Default::default()
+                    }),
+                    vec!["page1:paragraph"]
+                );
+                assert!(search(SearchOptions {
+                    created_after: Some(before_update),
+                    ..Default::default()
+                })
+                .is_empty());
+
+                assert_eq!(
+                    search(SearchOptions {
+                        order: SearchOrder::Recency,
+                        ..Default::default()
+                    })
+                    .first()
+                    .map(String::as_str),
+                    Some("page1:paragraph")
+                );
+
+                // empty query matches all blocks that pass the filters
+                let results = search_plugin
+                    .search(
+                        "",
+                        &SearchOptions {
+                            space_id: Some("page2".into()),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                assert_eq!(results.total, 1);
+            })
+            .is_some());
+    }
+
+    #[test]
+    fn incremental_search_test() {
+        let workspace = Workspace::from_doc(Default::default(), "wk-incremental");
+        workspace.with_trx(|mut t| {
+            let page = t.get_space("page");
+            for id in ["a", "b", "c"] {
+                let block = page.create(&mut t.trx, id, "affine:paragraph");
+                block.set(&mut t.trx, "text", format!("hello {id}"));
+            }
+        });
+
+        let search = |query: &str| {
+            let mut ids = workspace
+                .search(query)
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|item| item.block_id)
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        assert_eq!(search("hello"), vec!["page:a", "page:b", "page:c"]);
+
+        workspace.with_trx(|mut t| {
+            let page = t.get_space("page");
+            let a = page.get(&t.trx, "a").unwrap();
+            a.set(&mut t.trx, "text", "world a");
+            page.remove(&mut t.trx, "b");
+        });
+        workspace.with_plugin::<IndexingPluginImpl, ()>(|plugin| {
+            let dirty = plugin.dirty.lock().unwrap();
+            assert!(dirty.spaces.is_empty());
+            let mut blocks = dirty.blocks["page"].iter().collect::<Vec<_>>();
+            blocks.sort();
+            assert_eq!(blocks, vec!["a", "b"]);
+        });
+        assert_eq!(search("hello"), vec!["page:c"]);
+        assert_eq!(search("world"), vec!["page:a"]);
+
+        // blocks in new spaces are indexed as well
+        workspace.with_trx(|mut t| {
+            let page = t.get_space("page2");
+            let d = page.create(&mut t.trx, "d", "affine:paragraph");
+            d.set(&mut t.trx, "text", "world d");
+        });
+        assert_eq!(search("world"), vec!["page2:d", "page:a"]);
+
+        // no duplicated documents after reindex
+        assert_eq!(workspace.search("").unwrap().total, 3);
+    }
+
+    #[test]
+    fn analyzer_search_test() {
+        let workspace = Workspace::from_doc(Default::default(), "wk-analyzer");
+        workspace.with_trx(|mut t| {
+            let page = t.get_space("page");
+            let block = page.create(&mut t.trx, "a", "affine:paragraph");
+            block.set(&mut t.trx, "text", "The runners were running");
+            let block = page.create(&mut t.trx, "b", "affine:paragraph");
+            block.set(&mut t.trx, "text", "张华考上了北京大学");
+        });
+        // ngram matches any part of the words
+        assert_eq!(workspace.search("unne").unwrap().total, 1);
+
+        workspace.set_search_analyzer(SearchAnalyzer::English);
+        assert_eq!(
+            workspace.metadata().search_analyzer,
+            SearchAnalyzer::English
+        );
+        assert_eq!(workspace.search("unne").unwrap().total, 0);
+        assert_eq!(workspace.search("runs").unwrap().total, 1);
+
+        workspace.set_search_analyzer(SearchAnalyzer::Cjk);
+        assert_eq!(workspace.search("北京大学").unwrap().total, 1);
+        assert_eq!(workspace.search("RUNNERS").unwrap().total, 1);
+    }
+
+    #[test]
+    fn forgiving_search_test() {
+        let workspace = Workspace::from_doc(Default::default(), "wk-forgiving");
+        workspace.set_search_analyzer(SearchAnalyzer::English);
+        workspace.with_trx(|mut t| {
+            let page = t.get_space("page");
+            let block = page.create(&mut t.trx, "a", "affine:page");
+            block.set(&mut t.trx, "title", "Meeting notes");
+            block.set(&mut t.trx, "text", "share the workspace documents");
+            let block = page.create(&mut t.trx, "b", "affine:page");
+            block.set(&mut t.trx, "title", "Meeting agenda");
+            let block = page.create(&mut t.trx, "c", "affine:page");
+            block.set(&mut t.trx, "title", "Roadmap");
+        });
+
+        let search = |query: &str| {
+            let options = SearchOptions {
+                mode: SearchMode::Forgiving,
+                ..Default::default()
+            };
+            workspace
+                .search_with_options(query, options)
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|item| item.block_id)
+                .collect::<Vec<_>>()
+        };
+        // typo
+        assert_eq!(search("worksapce"), vec!["page:a"]);
+        // incomplete last word
+        assert_eq!(search("workspace docu"), vec!["page:a"]);
+        // no syntax error
+        assert!(workspace.search("meeting (").is_err());
+        assert_eq!(search("meeting (").len(), 2);
+
+        let suggestions = workspace.suggest("meeting no", 10).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].block_id, "page:a");
+        assert_eq!(suggestions[0].space_id, "page");
+        assert_eq!(suggestions[0].title, "Meeting notes");
+
+        let mut titles = workspace
+            .suggest("mee", 10)
+            .unwrap()
+            .into_iter()
+            .map(|suggestion| suggestion.title)
+            .collect::<Vec<_>>();
+        titles.sort();
+        assert_eq!(titles, vec!["Meeting agenda", "Meeting notes"]);
+    }
+
+    #[test]
+    fn persisted_search_test() {
+        use yrs::{updates::decoder::Decode, Doc, StateVector, Transact, Update};
+
+        let dir = std::env::temp_dir().join(format!("jwst-index-{}", nanoid::nanoid!()));
+        let update = {
+            let workspace = Workspace::from_doc(Default::default(), "wk-persisted");
+            workspace.persist_search_index(&dir).unwrap();
+            workspace.with_trx(|mut t| {
+                let page = t.get_space("page");
+                let block = page.create(&mut t.trx, "a", "affine:paragraph");
+                block.set(&mut t.trx, "text", "persisted content");
+            });
+            assert_eq!(workspace.search("persisted").unwrap().total, 1);
+
+            workspace
+                .doc()
+                .transact()
+                .encode_state_as_update_v1(&StateVector::default())
+        };
+
+        let load = || {
+            let doc = Doc::new();
+            doc.transact_mut()
+                .apply_update(Update::decode_v1(&update).unwrap());
+            let workspace = Workspace::from_doc(doc, "wk-persisted");
+            workspace.persist_search_index(&dir).unwrap();
+            workspace
+        };
+
+        // the index is reused without reindexing
+        let workspace = load();
+        workspace.with_plugin::<IndexingPluginImpl, ()>(|plugin| {
+            assert!(plugin.dirty.lock().unwrap().is_empty());
+        });
+        assert_eq!(workspace.search("persisted").unwrap().total, 1);
+
+        // the index is rebuilt if it does not match the loaded workspace
+        workspace.with_trx(|mut t| {
+            let page = t.get_space("page");
+            let block = page.create(&mut t.trx, "b", "affine:paragraph");
+            block.set(&mut t.trx, "text", "unsaved content");
+        });
+        assert_eq!(workspace.search("content").unwrap().total, 2);
+        drop(workspace);
+        let workspace = load();
+        workspace.with_plugin::<IndexingPluginImpl, ()>(|plugin| {
+            assert!(plugin.dirty.lock().unwrap().reset);
+        });
+        assert_eq!(workspace.search("content").unwrap().total, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
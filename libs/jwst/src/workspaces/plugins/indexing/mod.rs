@@ -2,8 +2,13 @@ mod indexer;
 mod register;
 mod tokenizer;
 
-use super::{PluginImpl, PluginRegister, Workspace};
-use tokenizer::{tokenizers_register, GRAM_TOKENIZER};
+use super::{
+    constants::search, tracker, warn, PluginImpl, PluginRegister, SearchAnalyzer, Workspace,
+};
+use tokenizer::{tokenizer_name, tokenizers_register};
 
-pub use indexer::{IndexingPluginImpl, SearchResult, SearchResults};
+pub use indexer::{
+    IndexingPluginImpl, SearchMode, SearchOptions, SearchOrder, SearchResult, SearchResults,
+    Suggestion,
+};
 pub(super) use register::IndexingPluginRegister;
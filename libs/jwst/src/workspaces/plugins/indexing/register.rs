@@ -1,21 +1,27 @@
-use super::*;
+use super::{tracker::DirtyBlocks, *};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use std::{
-    path::PathBuf,
+    collections::HashMap,
+    fs::{create_dir_all, remove_dir_all},
+    path::{Path, PathBuf},
     rc::Rc,
-    sync::{atomic::AtomicU32, Arc},
+    sync::{Arc, Mutex},
 };
 use tantivy::{
+    directory::MmapDirectory,
     query::QueryParser,
-    schema::{IndexRecordOption, Schema, TextFieldIndexing, TextOptions, STORED, STRING},
-    Index,
+    schema::{
+        IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST, INDEXED, STORED, STRING,
+    },
+    Index, TantivyError,
 };
+use yrs::{updates::encoder::Encode, ReadTxn, Transact};
 
-#[derive(Debug)]
-enum IndexingStorageKind {
+#[derive(Debug, Clone)]
+pub(super) enum IndexingStorageKind {
     /// Store index in memory (default)
     Ram,
     /// Store index in a specific directory
-    #[allow(dead_code)]
     PersistedDirectory(PathBuf),
 }
 
@@ -38,7 +44,6 @@ impl IndexingPluginRegister {
         }
     }
 
-    #[allow(dead_code)]
     pub fn persisted_directory(path: PathBuf) -> Self {
         Self {
             storage_kind: IndexingStorageKind::PersistedDirectory(path),
@@ -49,30 +54,46 @@ impl IndexingPluginRegister {
 impl PluginRegister for IndexingPluginRegister {
     type Plugin = IndexingPluginImpl;
     fn setup(self, ws: &mut Workspace) -> Result<IndexingPluginImpl, Box<dyn std::error::Error>> {
-        let search_index = ws.metadata().search_index;
-        let options = TextOptions::default().set_indexing_options(
-            TextFieldIndexing::default()
-                .set_tokenizer(GRAM_TOKENIZER)
-                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
-        );
+        let metadata = ws.metadata();
+        let analyzer = metadata.search_analyzer;
+        let search_index = metadata
+            .search_index
+            .into_iter()
+            .filter(|field| {
+                let reserved = search::RESERVED.contains(&field.as_str());
+                if reserved {
+                    warn!("skip reserved search index field: {}", field);
+                }
+                !reserved
+            })
+            .collect::<Vec<_>>();
+        // stored for generating snippets of search results
+        let options = TextOptions::default()
+            .set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer(&tokenizer_name(analyzer))
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            )
+            .set_stored();
 
         let mut schema_builder = Schema::builder();
-        schema_builder.add_text_field("block_id", STRING | STORED);
+        schema_builder.add_text_field(search::BLOCK_ID, STRING | STORED);
+        // filterable fields
+        schema_builder.add_text_field(search::SPACE_ID, STRING | STORED);
+        schema_builder.add_text_field(search::FLAVOUR, STRING | STORED);
+        schema_builder.add_u64_field(search::CREATED, INDEXED | FAST | STORED);
+        schema_builder.add_u64_field(search::UPDATED, INDEXED | FAST | STORED);
         search_index.iter().for_each(|field_name| {
             schema_builder.add_text_field(field_name.as_str(), options.clone());
         });
         let schema = schema_builder.build();
 
-        let index_dir: Box<dyn tantivy::Directory> = match &self.storage_kind {
-            IndexingStorageKind::Ram => Box::new(tantivy::directory::RamDirectory::create()),
-            IndexingStorageKind::PersistedDirectory(dir) => {
-                Box::new(tantivy::directory::MmapDirectory::open(dir)?)
-            }
-        };
-
         let index = Rc::new({
-            let index = Index::open_or_create(index_dir, schema.clone())?;
-            tokenizers_register(index.tokenizers());
+            let index = match &self.storage_kind {
+                IndexingStorageKind::Ram => Index::create_in_ram(schema.clone()),
+                IndexingStorageKind::PersistedDirectory(dir) => open_persisted(dir, &schema)?,
+            };
+            tokenizers_register(index.tokenizers(), analyzer);
             index
         });
 
@@ -82,34 +103,57 @@ impl PluginRegister for IndexingPluginRegister {
             fields.push(body);
         });
 
-        let queue_reindex = Arc::new(AtomicU32::new(
-            // require an initial re-index by setting the default above 0
-            1,
-        ));
-
-        let queue_reindex_clone = queue_reindex.clone();
-        let sub = ws.observe(move |_txn, _e| {
-            // upd.update
-            // let u = yrs::Update::decode_v1(&e.update).unwrap();
-            // let _items = u
-            //     .as_items()
-            //     .into_iter()
-            //     .map(|i| format!("\n  {i:?}"))
-            //     .collect::<String>();
-            // for item in u.as_items() {
-            //     item.id;
-            // }
-            queue_reindex_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        });
+        // the persisted index records the state vector of the doc at each commit,
+        // if it still matches then the index is up to date and only needs incremental updates
+        let up_to_date = match &self.storage_kind {
+            IndexingStorageKind::Ram => false,
+            IndexingStorageKind::PersistedDirectory(_) => {
+                let state_vector = ws.doc().transact().state_vector().encode_v1();
+                index.load_metas()?.payload.as_deref()
+                    == Some(STANDARD.encode(state_vector).as_str())
+            }
+        };
 
-        Ok(IndexingPluginImpl {
+        let mut plugin = IndexingPluginImpl {
+            storage_kind: self.storage_kind,
             schema,
             query_parser: QueryParser::for_index(&index, fields),
             index,
-            queue_reindex,
-            // needs to drop sub with everything else
-            _update_sub: sub,
             search_index,
-        })
+            analyzer,
+            dirty: Arc::new(Mutex::new(DirtyBlocks {
+                reset: !up_to_date,
+                ..Default::default()
+            })),
+            observers: HashMap::new(),
+        };
+        plugin.observe_spaces(ws, !up_to_date);
+
+        Ok(plugin)
+    }
+}
+
+/// Open the index in the directory, the index is created again if the schema was changed.
+fn open_persisted(dir: &Path, schema: &Schema) -> Result<Index, Box<dyn std::error::Error>> {
+    create_dir_all(dir)?;
+    match Index::open_or_create(MmapDirectory::open(dir)?, schema.clone()) {
+        Ok(index) => Ok(index),
+        Err(TantivyError::SchemaError(err)) => {
+            warn!("recreate search index in {}: {}", dir.display(), err);
+            remove_dir_all(dir)?;
+            create_dir_all(dir)?;
+            Ok(Index::create_in_dir(dir, schema.clone())?)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl IndexingPluginImpl {
+    /// The register to set up the plugin again with the same storage,
+    /// e.g. after the search index fields are changed.
+    pub(crate) fn register(&self) -> IndexingPluginRegister {
+        IndexingPluginRegister {
+            storage_kind: self.storage_kind.clone(),
+        }
     }
 }
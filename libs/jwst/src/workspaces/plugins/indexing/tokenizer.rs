@@ -1,7 +1,65 @@
-use tantivy::tokenizer::{NgramTokenizer, TokenizerManager};
+use super::SearchAnalyzer;
+use cang_jie::{CangJieTokenizer, TokenizerOption};
+use jieba_rs::Jieba;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use tantivy::tokenizer::{
+    Language, LowerCaser, NgramTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer, TextAnalyzer,
+    TokenizerManager,
+};
 
-pub const GRAM_TOKENIZER: &str = "gram";
+const GRAM_TOKENIZER: &str = "gram";
+const CJK_TOKENIZER: &str = "cjk";
 
-pub fn tokenizers_register(tokenizers: &TokenizerManager) {
-    tokenizers.register(GRAM_TOKENIZER, NgramTokenizer::new(1, 10, false));
+/// The dictionary is large, so it is loaded once and shared by all workspaces.
+static JIEBA: Lazy<Arc<Jieba>> = Lazy::new(|| Arc::new(Jieba::new()));
+
+fn stem_language(analyzer: SearchAnalyzer) -> Option<Language> {
+    match analyzer {
+        SearchAnalyzer::Ngram | SearchAnalyzer::Cjk => None,
+        SearchAnalyzer::Danish => Some(Language::Danish),
+        SearchAnalyzer::Dutch => Some(Language::Dutch),
+        SearchAnalyzer::English => Some(Language::English),
+        SearchAnalyzer::Finnish => Some(Language::Finnish),
+        SearchAnalyzer::French => Some(Language::French),
+        SearchAnalyzer::German => Some(Language::German),
+        SearchAnalyzer::Greek => Some(Language::Greek),
+        SearchAnalyzer::Hungarian => Some(Language::Hungarian),
+        SearchAnalyzer::Italian => Some(Language::Italian),
+        SearchAnalyzer::Norwegian => Some(Language::Norwegian),
+        SearchAnalyzer::Portuguese => Some(Language::Portuguese),
+        SearchAnalyzer::Romanian => Some(Language::Romanian),
+        SearchAnalyzer::Russian => Some(Language::Russian),
+        SearchAnalyzer::Spanish => Some(Language::Spanish),
+        SearchAnalyzer::Swedish => Some(Language::Swedish),
+    }
+}
+
+/// The tokenizer name is a part of the schema,
+/// so a persisted index is rebuilt when the analyzer is changed.
+pub fn tokenizer_name(analyzer: SearchAnalyzer) -> String {
+    match analyzer {
+        SearchAnalyzer::Ngram => GRAM_TOKENIZER.to_owned(),
+        SearchAnalyzer::Cjk => CJK_TOKENIZER.to_owned(),
+        language => format!("stem_{}", language.as_str()),
+    }
+}
+
+pub fn tokenizers_register(tokenizers: &TokenizerManager, analyzer: SearchAnalyzer) {
+    let tokenizer = if let Some(language) = stem_language(analyzer) {
+        TextAnalyzer::from(SimpleTokenizer)
+            .filter(RemoveLongFilter::limit(40))
+            .filter(LowerCaser)
+            .filter(Stemmer::new(language))
+    } else if analyzer == SearchAnalyzer::Cjk {
+        TextAnalyzer::from(CangJieTokenizer {
+            worker: JIEBA.clone(),
+            option: TokenizerOption::ForSearch { hmm: false },
+        })
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+    } else {
+        TextAnalyzer::from(NgramTokenizer::new(1, 10, false))
+    };
+    tokenizers.register(&tokenizer_name(analyzer), tokenizer);
 }
@@ -1,24 +1,32 @@
 #[cfg(feature = "workspace-search")]
 mod indexing;
 mod plugin;
+mod references;
+mod tracker;
 
 use super::*;
 
 #[cfg(feature = "workspace-search")]
 pub(super) use indexing::IndexingPluginImpl;
-pub(super) use plugin::{PluginImpl, PluginMap, PluginRegister};
+pub(super) use plugin::PluginMap;
+pub(super) use references::{ReferencesPluginImpl, ReferencesPluginRegister};
+
+pub use plugin::{PluginImpl, PluginRegister};
+pub use references::{Reference, ReferenceKind};
 
 #[cfg(feature = "workspace-search")]
-pub use indexing::{SearchResult, SearchResults};
+pub use indexing::{
+    SearchMode, SearchOptions, SearchOrder, SearchResult, SearchResults, Suggestion,
+};
 
-/// Setup a [WorkspacePlugin] and insert it into the [Workspace].
+/// Setup a [PluginImpl] and insert it into the [Workspace].
 /// See [plugins].
 fn insert_plugin(
     mut workspace: Workspace,
     config: impl PluginRegister,
 ) -> Result<Workspace, Box<dyn std::error::Error>> {
     let plugin = config.setup(&mut workspace)?;
-    workspace.plugins.insert_plugin(plugin)?;
+    workspace.plugins.insert_plugin(&workspace, plugin)?;
 
     Ok(workspace)
 }
@@ -27,10 +35,28 @@ fn insert_plugin(
 pub(super) fn setup_plugin(workspace: Workspace) -> Workspace {
     // default plugins
     if cfg!(feature = "workspace-search") {
-        // Set up indexing plugin
-        insert_plugin(workspace, indexing::IndexingPluginRegister::default())
-            .expect("Failed to setup search plugin")
+        // Set up indexing plugin, keep the storage of the index if it was set up before
+        let register = workspace
+            .with_plugin::<IndexingPluginImpl, _>(|plugin| plugin.register())
+            .unwrap_or_default();
+        insert_plugin(workspace.clone(), register).unwrap_or_else(|err| {
+            warn!("failed to setup search plugin, fallback to memory index: {err}");
+            insert_plugin(workspace, indexing::IndexingPluginRegister::default())
+                .expect("Failed to setup search plugin")
+        })
     } else {
         workspace
     }
 }
+
+/// Setup plugin: [indexing], the index is persisted in the directory.
+#[cfg(feature = "workspace-search")]
+pub(super) fn setup_persisted_plugin(
+    workspace: Workspace,
+    dir: std::path::PathBuf,
+) -> Result<Workspace, Box<dyn std::error::Error>> {
+    insert_plugin(
+        workspace,
+        indexing::IndexingPluginRegister::persisted_directory(dir),
+    )
+}
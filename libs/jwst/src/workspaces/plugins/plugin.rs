@@ -1,63 +1,290 @@
-//! Plugins are an internal experimental interface for extending the [Workspace].
+//! Plugins extend the [Workspace] with state that follows its blocks, like the search index.
+//!
+//! A plugin is created from a [PluginRegister] and owned by the [Workspace], so the
+//! observers it subscribes are dropped together with the [Workspace].
 
 use super::*;
-use std::sync::{Arc, RwLock};
+use crate::JwstResult;
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
 use type_map::TypeMap;
+use yrs::UpdateSubscription;
 
-/// A configuration from which a [WorkspacePlugin] can be created from.
-pub(crate) trait PluginRegister {
+/// A configuration from which a [PluginImpl] can be created from.
+pub trait PluginRegister: 'static {
     type Plugin: PluginImpl;
-    // Do we need self?
+
+    /// Create the plugin, it has access to the whole [Workspace] to read the initial
+    /// state and to add listeners to changes of the [Workspace].
     fn setup(self, ws: &mut Workspace) -> Result<Self::Plugin, Box<dyn std::error::Error>>;
-    // Do we need a clean-up thing?
-    // -> Box<dyn FnMut(&mut Workspace)>;
 }
 
-/// A workspace plugin which comes from a corresponding [WorkspacePluginConfig::setup].
-/// In that setup call, the plugin will have initial access to the whole [Workspace],
-/// and will be able to add listeners to changes to blocks in the [Workspace].
-pub(crate) trait PluginImpl: 'static {
-    /// IDEA 1/10:
-    /// This update is called sometime between when we know changes have been made to the workspace
-    /// and the time when we will get the plugin to query its data (e.g. search())
+/// A workspace plugin which comes from a corresponding [PluginRegister::setup].
+pub trait PluginImpl: 'static {
+    /// Called before the plugin is queried, e.g. before searching, so that the plugin
+    /// can apply the changes it flagged via observers.
     fn on_update(&mut self, _ws: &Workspace) -> Result<(), Box<dyn std::error::Error>> {
         // Default implementation for a WorkspacePlugin update does nothing.
         Ok(())
     }
+
+    /// Called with the block changes of each transaction after the plugin is set up.
+    ///
+    /// The events are only observed once a plugin is added by [Workspace::register_plugin]
+    /// or [Workspace::install_plugin]. The workspace must not be changed in this callback,
+    /// events of transactions made while the plugins are in use are delivered later.
+    fn on_block_event(&mut self, _events: &[BlockEvent]) {}
+
+    /// Called when the plugin is removed from the [Workspace] or replaced by a new one.
+    fn teardown(&mut self, _ws: &Workspace) {}
 }
 
-#[derive(Default, Clone)]
-pub(crate) struct PluginMap {
+type PendingSetup = Box<dyn FnOnce(&mut Workspace) -> Result<(), Box<dyn std::error::Error>>>;
+type BlockEventHandler = fn(&mut TypeMap, &[BlockEvent]);
+
+#[derive(Default)]
+struct Plugins {
     /// We store plugins into the TypeMap, so that their ownership is tied to [Workspace].
     /// This enables us to properly manage lifetimes of observers which will subscribe
     /// into events that the [Workspace] experiences, like block updates.
-    map: Arc<RwLock<TypeMap>>,
+    map: TypeMap,
+    /// Deliver the block events to the plugin of the type
+    handlers: HashMap<TypeId, BlockEventHandler>,
+}
+
+impl Plugins {
+    fn flush(&mut self, queued: &Mutex<Vec<BlockEvent>>) {
+        let events = std::mem::take(&mut *queued.lock().unwrap());
+        if !events.is_empty() {
+            for handler in self.handlers.values() {
+                handler(&mut self.map, &events);
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub(crate) struct PluginMap {
+    plugins: Arc<RwLock<Plugins>>,
+    /// Registered plugins that are set up on first use, keyed by the type of plugin
+    pending: Arc<Mutex<HashMap<TypeId, PendingSetup>>>,
+    /// Block events that are not delivered yet because the plugins were in use
+    queued: Arc<Mutex<Vec<BlockEvent>>>,
+    block_events: Arc<Mutex<Option<UpdateSubscription>>>,
 }
 
 impl PluginMap {
     pub(crate) fn insert_plugin<P: PluginImpl>(
         &self,
+        ws: &Workspace,
         plugin: P,
     ) -> Result<&Self, Box<dyn std::error::Error>> {
-        self.map.write().unwrap().insert(plugin);
+        let replaced = {
+            let mut plugins = self.plugins.write().unwrap();
+            plugins.handlers.insert(TypeId::of::<P>(), |map, events| {
+                if let Some(plugin) = map.get_mut::<P>() {
+                    plugin.on_block_event(events);
+                }
+            });
+            plugins.map.insert(plugin)
+        };
+        if let Some(mut replaced) = replaced {
+            replaced.teardown(ws);
+        }
         Ok(self)
     }
 
-    pub(crate) fn with_plugin<P: PluginImpl, T>(&self, cb: impl Fn(&P) -> T) -> Option<T> {
-        let map = self.map.read().unwrap();
-        let plugin = map.get::<P>();
-        plugin.map(cb)
+    pub(crate) fn register<R: PluginRegister>(&self, register: R) {
+        let setup: PendingSetup = Box::new(move |ws: &mut Workspace| {
+            let plugin = register.setup(ws)?;
+            ws.plugins.clone().insert_plugin(ws, plugin)?;
+            Ok(())
+        });
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<R::Plugin>(), setup);
+    }
+
+    /// Set up the plugin if it is registered but not used yet.
+    fn setup_pending<P: PluginImpl>(
+        &self,
+        ws: &Workspace,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let setup = self.pending.lock().unwrap().remove(&TypeId::of::<P>());
+        match setup {
+            Some(setup) => setup(&mut ws.clone()),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn with_plugin<P: PluginImpl, T>(
+        &self,
+        ws: &Workspace,
+        cb: impl FnOnce(&P) -> T,
+    ) -> Option<T> {
+        if let Err(e) = self.setup_pending::<P>(ws) {
+            error!("failed to setup plugin: {}", e);
+        }
+        let plugins = self.plugins.read().unwrap();
+        plugins.map.get::<P>().map(cb)
     }
 
     pub(crate) fn update_plugin<P: PluginImpl>(
         &self,
         ws: &Workspace,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut map = self.map.write().unwrap();
-        let plugin = map.get_mut::<P>().ok_or("Plugin not found")?;
+        self.setup_pending::<P>(ws)?;
+
+        let mut plugins = self.plugins.write().unwrap();
+        plugins.flush(&self.queued);
+        let plugin = plugins.map.get_mut::<P>().ok_or("Plugin not found")?;
 
         plugin.on_update(ws)?;
 
         Ok(())
     }
+
+    pub(crate) fn remove_plugin<P: PluginImpl>(&self, ws: &Workspace) -> bool {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&TypeId::of::<P>())
+            .is_some();
+        let removed = {
+            let mut plugins = self.plugins.write().unwrap();
+            plugins.handlers.remove(&TypeId::of::<P>());
+            plugins.map.remove::<P>()
+        };
+        match removed {
+            Some(mut plugin) => {
+                plugin.teardown(ws);
+                true
+            }
+            None => pending,
+        }
+    }
+
+    /// Start delivering block events to the plugins, only the first call subscribes.
+    pub(crate) fn observe_block_events(&self, ws: &Workspace) -> JwstResult<()> {
+        let mut sub = self.block_events.lock().unwrap();
+        if sub.is_some() {
+            return Ok(());
+        }
+
+        // the observer must not keep the plugins alive, they own the observer
+        let plugins = Arc::downgrade(&self.plugins);
+        let queued = self.queued.clone();
+        *sub = Some(ws.clone().observe_blocks(move |events| {
+            queued.lock().unwrap().extend_from_slice(events);
+            if let Some(plugins) = plugins.upgrade() {
+                // the plugins are in use if the transaction is made by a plugin,
+                // then the events are delivered on the next update
+                if let Ok(mut plugins) = plugins.try_write() {
+                    plugins.flush(&queued);
+                }
+            }
+        })?);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Default)]
+    struct CountingPlugin {
+        created: Vec<String>,
+        updates: usize,
+        log: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl PluginImpl for CountingPlugin {
+        fn on_update(&mut self, _ws: &Workspace) -> Result<(), Box<dyn std::error::Error>> {
+            self.updates += 1;
+            Ok(())
+        }
+
+        fn on_block_event(&mut self, events: &[BlockEvent]) {
+            for event in events {
+                if let BlockEvent::Created { block_id, .. } = event {
+                    self.created.push(block_id.clone());
+                }
+            }
+        }
+
+        fn teardown(&mut self, _ws: &Workspace) {
+            self.log.borrow_mut().push("teardown");
+        }
+    }
+
+    struct CountingRegister(Rc<RefCell<Vec<&'static str>>>);
+
+    impl PluginRegister for CountingRegister {
+        type Plugin = CountingPlugin;
+
+        fn setup(self, _ws: &mut Workspace) -> Result<CountingPlugin, Box<dyn std::error::Error>> {
+            self.0.borrow_mut().push("setup");
+            Ok(CountingPlugin {
+                log: self.0,
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn plugin_lifecycle() {
+        let workspace = Workspace::new("test");
+        let log = Rc::new(RefCell::new(vec![]));
+
+        // the blocks can't be observed while a transaction is alive
+        workspace.with_trx(|_| {
+            assert!(workspace
+                .register_plugin(CountingRegister(log.clone()))
+                .is_err());
+        });
+        assert!(workspace.with_plugin::<CountingPlugin, _>(|_| ()).is_none());
+
+        workspace
+            .register_plugin(CountingRegister(log.clone()))
+            .unwrap();
+        assert!(log.borrow().is_empty());
+
+        workspace.update_plugin::<CountingPlugin>().unwrap();
+        assert_eq!(*log.borrow(), vec!["setup"]);
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+            space.create(&mut t.trx, "block", "affine:paragraph");
+        });
+        workspace.update_plugin::<CountingPlugin>().unwrap();
+        assert_eq!(
+            workspace.with_plugin::<CountingPlugin, _>(|plugin| (
+                plugin.created.clone(),
+                plugin.updates
+            )),
+            Some((vec!["block".to_string()], 2))
+        );
+
+        // replacing a plugin tears down the old one
+        workspace
+            .install_plugin(CountingRegister(log.clone()))
+            .unwrap();
+        assert_eq!(*log.borrow(), vec!["setup", "setup", "teardown"]);
+
+        assert!(workspace.remove_plugin::<CountingPlugin>());
+        assert_eq!(
+            *log.borrow(),
+            vec!["setup", "setup", "teardown", "teardown"]
+        );
+        assert!(workspace.with_plugin::<CountingPlugin, _>(|_| ()).is_none());
+        assert!(!workspace.remove_plugin::<CountingPlugin>());
+    }
 }
@@ -0,0 +1,409 @@
+use super::{
+    constants::reference,
+    tracker::{observe_space, DirtyBlocks},
+    PluginImpl, PluginRegister, Workspace,
+};
+use crate::{Block, TextDelta};
+use lib0::any::Any;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+use utoipa::ToSchema;
+use yrs::{types::DeepEventsSubscription, ReadTxn};
+
+/// How a block refers to the target of a [Reference]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceKind {
+    /// The `sourceId` prop of an embed, usually a blob id
+    Embed,
+    /// A page link inside rich text, the `reference` attribute with a `pageId`
+    Link,
+    /// One of the reference props `pageId`, `reference` or `references`
+    Prop,
+}
+
+/// A block that refers to a block, page or blob, returned from [`Workspace::backlinks`]
+///
+/// [`Workspace::backlinks`]: crate::Workspace::backlinks
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema)]
+pub struct Reference {
+    /// The space of the block holding the reference
+    pub space_id: String,
+    /// The block holding the reference
+    pub block_id: String,
+    pub kind: ReferenceKind,
+    /// The prop holding the reference, without the `prop:` prefix
+    pub prop: String,
+    /// The id of the referenced block, page or blob
+    pub target: String,
+}
+
+/// The ids held by a reference prop, empty ids are skipped.
+fn reference_ids(value: &Any) -> Vec<String> {
+    match value {
+        Any::String(id) if !id.is_empty() => vec![id.to_string()],
+        Any::Array(ids) => ids.iter().flat_map(reference_ids).collect(),
+        _ => vec![],
+    }
+}
+
+/// The page of a page link, from the `reference` text attribute.
+fn linked_page(delta: &TextDelta) -> Option<String> {
+    match delta {
+        TextDelta::Insert {
+            attributes: Some(attributes),
+            ..
+        } => match attributes.get(reference::TEXT_ATTRIBUTE)? {
+            Any::Map(link) => match link.get(reference::PAGE_ID)? {
+                Any::String(page_id) if !page_id.is_empty() => Some(page_id.to_string()),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// All references held by the props of the block, a target referred to
+/// several times by the same prop is only returned once.
+fn block_references<T: ReadTxn>(trx: &T, space_id: &str, block: &Block) -> Vec<Reference> {
+    let block_id = block.block_id();
+    let mut references = BTreeSet::new();
+    let mut push = |kind, prop: &str, target| {
+        references.insert(Reference {
+            space_id: space_id.to_owned(),
+            block_id: block_id.clone(),
+            kind,
+            prop: prop.to_owned(),
+            target,
+        });
+    };
+
+    for (prop, value) in block.content(trx) {
+        let kind = if prop == reference::SOURCE_ID {
+            Some(ReferenceKind::Embed)
+        } else if reference::PROPS.contains(&prop.as_str()) {
+            Some(ReferenceKind::Prop)
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            for target in reference_ids(&value) {
+                push(kind, &prop, target);
+            }
+        }
+
+        // rich text is read as a plain string by `content`
+        if let Any::String(_) = value {
+            for target in block
+                .get_text_delta(trx, &prop)
+                .iter()
+                .flatten()
+                .filter_map(linked_page)
+            {
+                push(ReferenceKind::Link, &prop, target);
+            }
+        }
+    }
+
+    references.into_iter().collect()
+}
+
+#[derive(Default)]
+pub(crate) struct ReferencesPluginRegister;
+
+impl PluginRegister for ReferencesPluginRegister {
+    type Plugin = ReferencesPluginImpl;
+
+    fn setup(self, ws: &mut Workspace) -> Result<Self::Plugin, Box<dyn std::error::Error>> {
+        let mut plugin = ReferencesPluginImpl::default();
+        // all spaces are marked dirty, so the first update reads every block
+        plugin.observe_spaces(ws);
+        Ok(plugin)
+    }
+}
+
+/// Index of the references between blocks, kept current by observing the spaces.
+#[derive(Default)]
+pub struct ReferencesPluginImpl {
+    /// References held by each block, keyed by space id and then block id
+    sources: HashMap<String, HashMap<String, Vec<Reference>>>,
+    /// The space id and block id of the blocks referring to each target
+    targets: HashMap<String, BTreeSet<(String, String)>>,
+    dirty: Arc<Mutex<DirtyBlocks>>,
+    observers: HashMap<String, DeepEventsSubscription>,
+}
+
+impl PluginImpl for ReferencesPluginImpl {
+    fn on_update(&mut self, ws: &Workspace) -> Result<(), Box<dyn std::error::Error>> {
+        self.observe_spaces(ws);
+
+        let dirty = std::mem::take(&mut *self.dirty.lock().unwrap());
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        let blocks = ws.with_trx(|t| {
+            t.spaces(|spaces| {
+                spaces
+                    .flat_map(|space| {
+                        let space_id = space.space_id();
+                        let blocks = if dirty.spaces.contains(&space_id) {
+                            space.blocks(&t.trx, |blocks| blocks.collect::<Vec<_>>())
+                        } else {
+                            // removed blocks are not found and only dropped from the index
+                            dirty
+                                .blocks
+                                .get(&space_id)
+                                .into_iter()
+                                .flatten()
+                                .filter_map(|block_id| space.get(&t.trx, block_id))
+                                .collect()
+                        };
+                        blocks
+                            .into_iter()
+                            .map(|block| {
+                                (
+                                    space_id.clone(),
+                                    block.block_id(),
+                                    block_references(&t.trx, &space_id, &block),
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+
+        for space_id in &dirty.spaces {
+            self.remove_space(space_id);
+        }
+        for (space_id, block_ids) in &dirty.blocks {
+            for block_id in block_ids {
+                self.remove_block(space_id, block_id);
+            }
+        }
+        for (space_id, block_id, references) in blocks {
+            self.insert_block(space_id, block_id, references);
+        }
+
+        Ok(())
+    }
+}
+
+impl ReferencesPluginImpl {
+    /// The blocks referring to the target, ordered by space and block.
+    pub fn backlinks(&self, target: &str) -> Vec<Reference> {
+        self.targets
+            .get(target)
+            .into_iter()
+            .flatten()
+            .filter_map(|(space_id, block_id)| self.sources.get(space_id)?.get(block_id))
+            .flatten()
+            .filter(|reference| reference.target == target)
+            .cloned()
+            .collect()
+    }
+
+    /// The references held by the block.
+    pub fn references(&self, space_id: &str, block_id: &str) -> Vec<Reference> {
+        self.sources
+            .get(space_id)
+            .and_then(|blocks| blocks.get(block_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The ids of all blocks, pages and blobs that are referred to.
+    pub fn targets(&self) -> impl Iterator<Item = &String> {
+        self.targets.keys()
+    }
+
+    /// Observe the spaces that are not observed yet, new spaces are read as a whole
+    /// since their blocks may be changed before they are observed.
+    /// The references of removed spaces are dropped.
+    fn observe_spaces(&mut self, ws: &Workspace) {
+        let spaces = ws.with_trx(|t| {
+            t.spaces(|spaces| {
+                spaces
+                    .map(|space| (space.space_id(), space.blocks.clone()))
+                    .collect::<HashMap<_, _>>()
+            })
+        });
+
+        let removed = self
+            .observers
+            .keys()
+            .filter(|space_id| !spaces.contains_key(*space_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for space_id in removed {
+            self.observers.remove(&space_id);
+            self.dirty.lock().unwrap().blocks.remove(&space_id);
+            self.remove_space(&space_id);
+        }
+
+        for (space_id, blocks) in spaces {
+            if self.observers.contains_key(&space_id) {
+                continue;
+            }
+            {
+                let mut dirty = self.dirty.lock().unwrap();
+                dirty.blocks.remove(&space_id);
+                dirty.spaces.insert(space_id.clone());
+            }
+            let sub = observe_space(blocks, space_id.clone(), self.dirty.clone());
+            self.observers.insert(space_id, sub);
+        }
+    }
+
+    fn insert_block(&mut self, space_id: String, block_id: String, references: Vec<Reference>) {
+        if references.is_empty() {
+            return;
+        }
+        for reference in &references {
+            self.targets
+                .entry(reference.target.clone())
+                .or_default()
+                .insert((space_id.clone(), block_id.clone()));
+        }
+        self.sources
+            .entry(space_id)
+            .or_default()
+            .insert(block_id, references);
+    }
+
+    fn remove_block(&mut self, space_id: &str, block_id: &str) {
+        let references = self
+            .sources
+            .get_mut(space_id)
+            .and_then(|blocks| blocks.remove(block_id));
+        for reference in references.into_iter().flatten() {
+            self.remove_target(&reference.target, space_id, block_id);
+        }
+    }
+
+    fn remove_space(&mut self, space_id: &str) {
+        for (block_id, references) in self.sources.remove(space_id).into_iter().flatten() {
+            for reference in references {
+                self.remove_target(&reference.target, space_id, &block_id);
+            }
+        }
+    }
+
+    fn remove_target(&mut self, target: &str, space_id: &str, block_id: &str) {
+        if let Some(sources) = self.targets.get_mut(target) {
+            sources.remove(&(space_id.to_owned(), block_id.to_owned()));
+            if sources.is_empty() {
+                self.targets.remove(target);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TextAttributes;
+
+    fn page_link(page_id: &str) -> TextDelta {
+        TextDelta::insert_with_attributes(
+            " ",
+            TextAttributes::from([(
+                reference::TEXT_ATTRIBUTE.to_owned(),
+                Any::Map(Box::new(HashMap::from([(
+                    reference::PAGE_ID.to_owned(),
+                    Any::String(page_id.into()),
+                )]))),
+            )]),
+        )
+    }
+
+    fn expected(block_id: &str, kind: ReferenceKind, prop: &str, target: &str) -> Reference {
+        Reference {
+            space_id: "page1".into(),
+            block_id: block_id.into(),
+            kind,
+            prop: prop.into(),
+            target: target.into(),
+        }
+    }
+
+    #[test]
+    fn backlinks_test() {
+        let workspace = Workspace::new("test");
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("page1");
+            let paragraph = space.create(&mut t.trx, "paragraph", "affine:paragraph");
+            paragraph.apply_text_delta(
+                &mut t.trx,
+                "text",
+                &[
+                    TextDelta::insert("see "),
+                    page_link("page2"),
+                    page_link("page2"),
+                ],
+            );
+            let image = space.create(&mut t.trx, "image", "affine:embed");
+            image.set(&mut t.trx, "type", "image");
+            image.set(&mut t.trx, "sourceId", "blob1");
+            let surface = space.create(&mut t.trx, "surface", "affine:surface-ref");
+            surface.set(
+                &mut t.trx,
+                "references",
+                Any::Array(Box::new([
+                    Any::String("paragraph".into()),
+                    Any::String("page2".into()),
+                ])),
+            );
+        });
+
+        assert_eq!(
+            workspace.backlinks("page2"),
+            vec![
+                expected("paragraph", ReferenceKind::Link, "text", "page2"),
+                expected("surface", ReferenceKind::Prop, "references", "page2"),
+            ]
+        );
+        assert_eq!(
+            workspace.backlinks("blob1"),
+            vec![expected("image", ReferenceKind::Embed, "sourceId", "blob1")]
+        );
+        assert_eq!(
+            workspace.references("page1", "surface"),
+            vec![
+                expected("surface", ReferenceKind::Prop, "references", "page2"),
+                expected("surface", ReferenceKind::Prop, "references", "paragraph"),
+            ]
+        );
+
+        // the index follows the changes of blocks and spaces
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("page1");
+            space.remove(&mut t.trx, "surface");
+            let paragraph = space.get(&t.trx, "paragraph").unwrap();
+            paragraph.apply_text_delta(&mut t.trx, "text", &[TextDelta::Delete { delete: 6 }]);
+
+            let space = t.get_space("page3");
+            let link = space.create(&mut t.trx, "link", "affine:embed-linked-doc");
+            link.set(&mut t.trx, "pageId", "page2");
+        });
+
+        assert_eq!(
+            workspace.backlinks("page2"),
+            vec![Reference {
+                space_id: "page3".into(),
+                block_id: "link".into(),
+                kind: ReferenceKind::Prop,
+                prop: "pageId".into(),
+                target: "page2".into(),
+            }]
+        );
+        assert!(workspace.backlinks("paragraph").is_empty());
+    }
+}
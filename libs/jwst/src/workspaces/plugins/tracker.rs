@@ -0,0 +1,59 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use yrs::{
+    types::{DeepEventsSubscription, Event, PathSegment},
+    DeepObservable, MapRef,
+};
+
+/// Blocks changed since the index was last committed.
+#[derive(Debug, Default)]
+pub(super) struct DirtyBlocks {
+    /// The index does not match the workspace, all documents need to be dropped
+    pub(super) reset: bool,
+    /// Spaces whose blocks all need to be reindexed
+    pub(super) spaces: HashSet<String>,
+    /// Block ids keyed by space id
+    pub(super) blocks: HashMap<String, HashSet<String>>,
+}
+
+impl DirtyBlocks {
+    pub(super) fn is_empty(&self) -> bool {
+        !self.reset && self.spaces.is_empty() && self.blocks.is_empty()
+    }
+
+    fn insert(&mut self, space_id: &str, block_id: &str) {
+        if !self.spaces.contains(space_id) {
+            self.blocks
+                .entry(space_id.to_owned())
+                .or_default()
+                .insert(block_id.to_owned());
+        }
+    }
+}
+
+/// Record the blocks touched by each transaction that changes the space.
+///
+/// The first segment of the event path is the block id, an event without path
+/// is the space itself, in which case the changed keys are the created or removed blocks.
+pub(super) fn observe_space(
+    mut blocks: MapRef,
+    space_id: String,
+    dirty: Arc<Mutex<DirtyBlocks>>,
+) -> DeepEventsSubscription {
+    blocks.observe_deep(move |trx, events| {
+        let mut dirty = dirty.lock().unwrap();
+        for event in events.iter() {
+            match event.path().pop_front() {
+                Some(PathSegment::Key(block_id)) => dirty.insert(&space_id, &block_id),
+                Some(PathSegment::Index(_)) => {}
+                None => {
+                    if let Event::Map(event) = event {
+                        for block_id in event.keys(trx).keys() {
+                            dirty.insert(&space_id, block_id);
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
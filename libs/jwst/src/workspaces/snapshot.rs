@@ -0,0 +1,265 @@
+use super::*;
+use crate::{JwstError, JwstResult};
+use lib0::any::Any;
+use std::collections::HashSet;
+use yrs::{
+    types::{text::YChange, ToJson, Value},
+    updates::{decoder::Decode, encoder::Encode},
+    Array, ArrayPrelim, ArrayRef, Doc, Map, MapPrelim, MapRef, ReadTxn, StateVector, Text,
+    TextPrelim, TextRef, Transact, TransactionMut, Update,
+};
+
+/// A named point-in-time state of a [Workspace].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceSnapshot {
+    pub name: String,
+    /// Unix timestamp in milliseconds
+    pub timestamp: i64,
+    /// Encoded state vector, used to diff the snapshot against later states
+    pub state_vector: Vec<u8>,
+    /// Encoded doc, the full state as a v1 update
+    pub doc: Vec<u8>,
+}
+
+impl WorkspaceSnapshot {
+    pub(super) fn load_doc(&self) -> JwstResult<Doc> {
+        let update = Update::decode_v1(&self.doc)
+            .map_err(|e| JwstError::InvalidSnapshot(self.name.clone(), e.to_string()))?;
+
+        let doc = Doc::new();
+        doc.transact_mut().apply_update(update);
+
+        Ok(doc)
+    }
+
+    /// The ids of all blocks, pages and blobs that are referred to in the snapshot of
+    /// the workspace, see [Workspace::referenced_ids].
+    pub fn referenced_ids(&self, workspace_id: &str) -> JwstResult<HashSet<String>> {
+        Workspace::from_doc(self.load_doc()?, workspace_id).referenced_ids()
+    }
+}
+
+impl Workspace {
+    /// Capture the current state of the workspace.
+    pub fn snapshot<S: Into<String>>(&self, name: S) -> WorkspaceSnapshot {
+        let doc = self.doc();
+        let trx = doc.transact();
+
+        WorkspaceSnapshot {
+            name: name.into(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            state_vector: trx.state_vector().encode_v1(),
+            doc: trx.encode_state_as_update_v1(&StateVector::default()),
+        }
+    }
+
+    /// Encode the changes made since the snapshot as a v1 update.
+    pub fn diff_snapshot(&self, snapshot: &WorkspaceSnapshot) -> JwstResult<Vec<u8>> {
+        let state_vector = StateVector::decode_v1(&snapshot.state_vector)
+            .map_err(|e| JwstError::InvalidSnapshot(snapshot.name.clone(), e.to_string()))?;
+
+        Ok(self
+            .doc()
+            .transact()
+            .encode_state_as_update_v1(&state_vector))
+    }
+
+    /// Bring the content of the workspace back to the snapshot.
+    ///
+    /// The history is not rewritten, the content is changed by a new transaction
+    /// so connected clients converge to the restored state. Returns the encoded
+    /// update of that transaction, which should be stored and broadcast by the caller.
+    pub fn restore_snapshot(&self, snapshot: &WorkspaceSnapshot) -> JwstResult<Vec<u8>> {
+        let source = snapshot.load_doc()?;
+        let src_trx = source.transact();
+
+        let doc = self.doc();
+        let mut trx = doc.transact_mut();
+
+        let mut roots = src_trx
+            .store()
+            .root_keys()
+            .into_iter()
+            .chain(trx.store().root_keys())
+            .filter(|key| key.starts_with("space:") && key != constants::space::UPDATED)
+            .collect::<Vec<_>>();
+        roots.sort();
+        roots.dedup();
+
+        for root in roots {
+            let target = doc.get_or_insert_map_with_trx(trx.store_mut(), &root);
+            match src_trx.get_map(&root) {
+                Some(src) => sync_map(&src_trx, &src, &mut trx, &target),
+                None => target.clear(&mut trx),
+            }
+        }
+
+        Ok(trx.encode_update_v1())
+    }
+}
+
+/// Change the target map to match the source map, only the changed entries are written.
+fn sync_map<T: ReadTxn>(src_trx: &T, src: &MapRef, trx: &mut TransactionMut, target: &MapRef) {
+    let keys = src
+        .keys(src_trx)
+        .map(ToOwned::to_owned)
+        .collect::<HashSet<_>>();
+    let removed = target
+        .keys(&*trx)
+        .filter(|key| !keys.contains(*key))
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    for key in removed {
+        target.remove(trx, &key);
+    }
+
+    for (key, value) in src.iter(src_trx) {
+        match (value, target.get(&*trx, key)) {
+            (Value::YMap(src), Some(Value::YMap(target))) => sync_map(src_trx, &src, trx, &target),
+            (value, Some(current))
+                if std::mem::discriminant(&value) == std::mem::discriminant(&current)
+                    && value.to_json(src_trx) == current.to_json(&*trx) => {}
+            (value, _) => copy_to_map(src_trx, value, trx, target, key),
+        }
+    }
+}
+
+fn copy_to_map<T: ReadTxn>(
+    src_trx: &T,
+    value: Value,
+    trx: &mut TransactionMut,
+    map: &MapRef,
+    key: &str,
+) {
+    match value {
+        Value::YMap(src) => {
+            let target = map.insert(trx, key, MapPrelim::<Any>::new());
+            for (key, value) in src.iter(src_trx) {
+                copy_to_map(src_trx, value, trx, &target, key);
+            }
+        }
+        Value::YArray(src) => {
+            let target = map.insert(trx, key, ArrayPrelim::<Vec<Any>, Any>::from(vec![]));
+            copy_array(src_trx, &src, trx, &target);
+        }
+        Value::YText(src) => {
+            let target = map.insert(trx, key, TextPrelim::new(""));
+            copy_text(src_trx, &src, trx, &target);
+        }
+        value => {
+            map.insert(trx, key, value.to_json(src_trx));
+        }
+    }
+}
+
+fn copy_array<T: ReadTxn>(
+    src_trx: &T,
+    src: &ArrayRef,
+    trx: &mut TransactionMut,
+    target: &ArrayRef,
+) {
+    for value in src.iter(src_trx) {
+        match value {
+            Value::YMap(src) => {
+                let map = target.push_back(trx, MapPrelim::<Any>::new());
+                for (key, value) in src.iter(src_trx) {
+                    copy_to_map(src_trx, value, trx, &map, key);
+                }
+            }
+            Value::YArray(src) => {
+                let array = target.push_back(trx, ArrayPrelim::<Vec<Any>, Any>::from(vec![]));
+                copy_array(src_trx, &src, trx, &array);
+            }
+            Value::YText(src) => {
+                let text = target.push_back(trx, TextPrelim::new(""));
+                copy_text(src_trx, &src, trx, &text);
+            }
+            value => {
+                target.push_back(trx, value.to_json(src_trx));
+            }
+        }
+    }
+}
+
+fn copy_text<T: ReadTxn>(src_trx: &T, src: &TextRef, trx: &mut TransactionMut, target: &TextRef) {
+    for diff in src.diff(src_trx, YChange::identity) {
+        let index = target.len(&*trx);
+        match diff.insert {
+            Value::Any(Any::String(insert)) => match diff.attributes {
+                Some(attributes) => target.insert_with_attributes(trx, index, &insert, *attributes),
+                None => target.insert(trx, index, &insert),
+            },
+            insert => warn!("skip unsupported embed in text: {:?}", insert),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn snapshot_restore() {
+        let workspace = Workspace::new("test");
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+            let block = space.create(&mut t.trx, "block", "affine:paragraph");
+            block.set(&mut t.trx, "text", "hello");
+            block.set(
+                &mut t.trx,
+                "tags",
+                Any::Array(Box::new([Any::String("a".into())])),
+            );
+        });
+
+        let snapshot = workspace.snapshot("first");
+        assert_eq!(snapshot.name, "first");
+        assert!(workspace.diff_snapshot(&snapshot).unwrap().len() <= 2);
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+            let block = space.get(&t.trx, "block").unwrap();
+            block.set(&mut t.trx, "text", "world");
+            block.set(&mut t.trx, "color", "red");
+            space.create(&mut t.trx, "other", "affine:paragraph");
+            t.get_space("new_space")
+                .create(&mut t.trx, "block", "affine:paragraph");
+        });
+        assert!(workspace.diff_snapshot(&snapshot).unwrap().len() > 2);
+
+        // a client that is connected before the restore
+        let client = Doc::new();
+        {
+            let update = workspace.sync_migration();
+            client
+                .transact_mut()
+                .apply_update(Update::decode_v1(&update).unwrap());
+        }
+
+        let update = workspace.restore_snapshot(&snapshot).unwrap();
+
+        workspace.with_trx(|mut t| {
+            let space = t.get_space("space");
+            let block = space.get(&t.trx, "block").unwrap();
+            assert_eq!(block.get(&t.trx, "text").unwrap().to_string(), "hello");
+            assert_eq!(block.get(&t.trx, "color"), None);
+            assert_eq!(
+                block.get(&t.trx, "tags"),
+                Some(Any::Array(Box::new([Any::String("a".into())])))
+            );
+            assert!(!space.exists(&t.trx, "other"));
+            assert!(!t.get_space("new_space").exists(&t.trx, "block"));
+        });
+
+        // restore is a forward update, the client converges after applying it
+        client
+            .transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        let space = client.get_or_insert_map("space:space");
+        let server_space = workspace.doc().get_or_insert_map("space:space");
+        assert_eq!(
+            space.to_json(&client.transact()),
+            server_space.to_json(&workspace.doc().transact())
+        );
+    }
+}
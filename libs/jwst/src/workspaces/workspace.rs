@@ -1,6 +1,12 @@
-use super::{metadata::SEARCH_INDEX, plugins::setup_plugin, *};
+use super::{
+    metadata::{SEARCH_ANALYZER, SEARCH_INDEX},
+    plugins::{setup_plugin, ReferencesPluginImpl, ReferencesPluginRegister},
+    *,
+};
+use crate::{JwstError, JwstResult};
 use serde::{ser::SerializeMap, Serialize, Serializer};
 use std::{
+    collections::HashSet,
     panic::{catch_unwind, AssertUnwindSafe},
     sync::Arc,
     thread::sleep,
@@ -17,14 +23,14 @@ use yrs::{
         decoder::{Decode, DecoderV1},
         encoder::{Encode, Encoder, EncoderV1},
     },
-    Doc, MapRef, Observable, ReadTxn, StateVector, Subscription, Transact, TransactionMut, Update,
-    UpdateEvent, UpdateSubscription,
+    Doc, Map, MapRef, Observable, ReadTxn, StateVector, Subscription, Transact, TransactionMut,
+    Update, UpdateEvent, UpdateSubscription,
 };
 
 static PROTOCOL: DefaultProtocol = DefaultProtocol;
 
 use super::PluginMap;
-use plugins::PluginImpl;
+use plugins::{PluginImpl, PluginRegister};
 
 pub type MapSubscription = Subscription<Arc<dyn Fn(&TransactionMut, &MapEvent)>>;
 
@@ -38,8 +44,7 @@ pub struct Workspace {
     /// This enables us to properly manage lifetimes of observers which will subscribe
     /// into events that the [Workspace] experiences, like block updates.
     ///
-    /// Public just for the crate as we experiment with the plugins interface.
-    /// See [plugins].
+    /// See [PluginImpl].
     pub(super) plugins: PluginMap,
 }
 
@@ -56,14 +61,18 @@ impl Workspace {
         let updated = doc.get_or_insert_map("space:updated");
         let metadata = doc.get_or_insert_map("space:meta");
 
-        setup_plugin(Self {
+        let workspace = Self {
             id: id.as_ref().to_string(),
             awareness: Arc::new(RwLock::new(Awareness::new(doc.clone()))),
             doc,
             updated,
             metadata,
             plugins: Default::default(),
-        })
+        };
+        // the references are read on first use
+        workspace.plugins.register(ReferencesPluginRegister);
+
+        setup_plugin(workspace)
     }
 
     fn from_raw<S: AsRef<str>>(
@@ -84,21 +93,61 @@ impl Workspace {
         }
     }
 
+    /// Register a plugin, it is set up the first time it is used by
+    /// [Workspace::with_plugin] or [Workspace::update_plugin].
+    /// A plugin of the same type that was set up before is replaced at that point.
+    ///
+    /// Fails if the block events can't be observed, e.g. while a transaction is alive.
+    pub fn register_plugin<R: PluginRegister>(
+        &self,
+        register: R,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.plugins.observe_block_events(self)?;
+        self.plugins.register(register);
+        Ok(())
+    }
+
+    /// Set up a plugin right away, a plugin of the same type is torn down and replaced.
+    pub fn install_plugin<R: PluginRegister>(
+        &self,
+        register: R,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.plugins.observe_block_events(self)?;
+        let plugin = register.setup(&mut self.clone())?;
+        self.plugins.insert_plugin(self, plugin)?;
+        Ok(())
+    }
+
+    /// Tear down and remove the plugin, returns false if there is no such plugin.
+    pub fn remove_plugin<P: PluginImpl>(&self) -> bool {
+        self.plugins.remove_plugin::<P>(self)
+    }
+
     /// Allow the plugin to run any necessary updates it could have flagged via observers.
-    /// See [plugins].
-    pub(super) fn update_plugin<P: PluginImpl>(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// See [PluginImpl::on_update].
+    pub fn update_plugin<P: PluginImpl>(&self) -> Result<(), Box<dyn std::error::Error>> {
         self.plugins.update_plugin::<P>(self)
     }
 
-    /// See [plugins].
-    pub(super) fn with_plugin<P: PluginImpl, T>(&self, cb: impl Fn(&P) -> T) -> Option<T> {
-        self.plugins.with_plugin::<P, T>(cb)
+    /// Read the plugin of the type, returns None if there is no such plugin.
+    pub fn with_plugin<P: PluginImpl, T>(&self, cb: impl FnOnce(&P) -> T) -> Option<T> {
+        self.plugins.with_plugin::<P, T>(self, cb)
     }
 
+    /// Search the blocks with default options, the first 10 results are returned.
     #[cfg(feature = "workspace-search")]
     pub fn search<S: AsRef<str>>(
         &self,
         query: S,
+    ) -> Result<SearchResults, Box<dyn std::error::Error>> {
+        self.search_with_options(query, SearchOptions::default())
+    }
+
+    #[cfg(feature = "workspace-search")]
+    pub fn search_with_options<S: AsRef<str>>(
+        &self,
+        query: S,
+        options: SearchOptions,
     ) -> Result<SearchResults, Box<dyn std::error::Error>> {
         use plugins::IndexingPluginImpl;
 
@@ -108,22 +157,110 @@ impl Workspace {
         let query = query.as_ref();
 
         self.with_plugin::<IndexingPluginImpl, Result<SearchResults, Box<dyn std::error::Error>>>(
-            |search_plugin| search_plugin.search(query),
+            |search_plugin| search_plugin.search(query, &options),
         )
         .expect("text search was set up by default")
     }
 
+    /// Titles of the blocks that match the typed text, for autocomplete.
+    /// Nothing is suggested if `title` is not in the search index fields.
+    #[cfg(feature = "workspace-search")]
+    pub fn suggest<S: AsRef<str>>(
+        &self,
+        text: S,
+        limit: usize,
+    ) -> Result<Vec<Suggestion>, Box<dyn std::error::Error>> {
+        use plugins::IndexingPluginImpl;
+
+        self.update_plugin::<IndexingPluginImpl>()?;
+
+        let text = text.as_ref();
+
+        self.with_plugin::<IndexingPluginImpl, Result<Vec<Suggestion>, Box<dyn std::error::Error>>>(
+            |search_plugin| search_plugin.suggest(text, limit),
+        )
+        .expect("text search was set up by default")
+    }
+
+    /// The blocks that refer to the target, which is a block id, a page id or a blob id.
+    /// See [Reference] for what counts as a reference.
+    pub fn backlinks<S: AsRef<str>>(&self, target: S) -> Vec<Reference> {
+        if let Err(e) = self.update_plugin::<ReferencesPluginImpl>() {
+            error!("failed to update references: {}", e);
+        }
+        self.with_plugin::<ReferencesPluginImpl, _>(|plugin| plugin.backlinks(target.as_ref()))
+            .unwrap_or_default()
+    }
+
+    /// The blocks, pages or blobs that the block refers to.
+    pub fn references<S, B>(&self, space_id: S, block_id: B) -> Vec<Reference>
+    where
+        S: AsRef<str>,
+        B: AsRef<str>,
+    {
+        if let Err(e) = self.update_plugin::<ReferencesPluginImpl>() {
+            error!("failed to update references: {}", e);
+        }
+        self.with_plugin::<ReferencesPluginImpl, _>(|plugin| {
+            plugin.references(space_id.as_ref(), block_id.as_ref())
+        })
+        .unwrap_or_default()
+    }
+
+    /// The ids of all blocks, pages and blobs that are referred to by any block,
+    /// and of the blobs referred to by the workspace metadata like the avatar.
+    ///
+    /// Fails if the references can't be read, an empty set would mean that nothing is referred to.
+    pub fn referenced_ids(&self) -> JwstResult<HashSet<String>> {
+        self.update_plugin::<ReferencesPluginImpl>().map_err(|e| {
+            JwstError::BoxedError(anyhow::anyhow!("failed to update references: {e}"))
+        })?;
+        let mut referenced = self
+            .with_plugin::<ReferencesPluginImpl, _>(|plugin| {
+                plugin.targets().cloned().collect::<HashSet<_>>()
+            })
+            .ok_or_else(|| JwstError::BoxedError(anyhow::anyhow!("references are not set up")))?;
+
+        let trx = self.doc.transact();
+        referenced.extend(
+            constants::reference::METADATA_BLOBS
+                .iter()
+                .filter_map(|key| self.metadata.get(&trx, key))
+                .map(|value| value.to_string(&trx))
+                .filter(|id| !id.is_empty()),
+        );
+
+        Ok(referenced)
+    }
+
+    /// Keep the search index in the directory instead of memory.
+    ///
+    /// The index remembers the state of the workspace it was built from, so it is
+    /// reused across restarts as long as the workspace was not changed in between,
+    /// otherwise it is rebuilt on the next search.
+    #[cfg(feature = "workspace-search")]
+    pub fn persist_search_index<P: Into<std::path::PathBuf>>(
+        &self,
+        dir: P,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        plugins::setup_persisted_plugin(self.clone(), dir.into())?;
+        Ok(())
+    }
+
+    /// Search the blocks and return the matched items as json array.
     pub fn search_result(&self, query: String) -> String {
         match self.search(query) {
-            Ok(list) => serde_json::to_string(&list).unwrap(),
+            Ok(list) => serde_json::to_string(&list.items).unwrap(),
             Err(_) => "[]".to_string(),
         }
     }
 
     pub fn set_search_index(&self, fields: Vec<String>) -> bool {
-        match fields.iter().find(|&field| field.is_empty()) {
+        match fields.iter().find(|&field| {
+            field.is_empty() || constants::search::RESERVED.contains(&field.as_str())
+        }) {
             Some(field) => {
-                error!("field name cannot be empty: {}", field);
+                error!("field name cannot be empty or reserved: {}", field);
                 false
             }
             None => {
@@ -135,6 +272,14 @@ impl Workspace {
         }
     }
 
+    /// Change how the text is tokenized, the blocks are indexed again with the new analyzer.
+    pub fn set_search_analyzer(&self, analyzer: SearchAnalyzer) {
+        if self.metadata().search_analyzer != analyzer {
+            self.with_trx(|mut trx| trx.set_metadata(SEARCH_ANALYZER, analyzer.as_str()));
+            setup_plugin(self.clone());
+        }
+    }
+
     pub fn with_trx<T>(&self, f: impl FnOnce(WorkspaceTransaction) -> T) -> T {
         let doc = self.doc();
         let trx = WorkspaceTransaction {
@@ -145,6 +290,40 @@ impl Workspace {
         f(trx)
     }
 
+    /// Same as [Workspace::with_trx], the changes are marked with the origin,
+    /// so that they can be told apart by [crate::UndoManager].
+    pub fn with_trx_origin<T>(&self, origin: &str, f: impl FnOnce(WorkspaceTransaction) -> T) -> T {
+        let doc = self.doc();
+        let trx = WorkspaceTransaction {
+            trx: doc.transact_mut_with(origin),
+            ws: self,
+        };
+
+        f(trx)
+    }
+
+    /// Origin of the changes made by this client, the changes synced from other
+    /// clients don't have it. See [Workspace::try_with_local_trx].
+    pub fn local_origin(&self) -> String {
+        format!("local:{}", self.client_id())
+    }
+
+    /// Same as [Workspace::try_with_trx], the changes are marked with
+    /// [Workspace::local_origin].
+    pub fn try_with_local_trx<T>(&self, f: impl FnOnce(WorkspaceTransaction) -> T) -> Option<T> {
+        let origin = self.local_origin();
+        match self.doc().try_transact_mut_with(origin.as_str()) {
+            Ok(trx) => {
+                let trx = WorkspaceTransaction { trx, ws: self };
+                Some(f(trx))
+            }
+            Err(e) => {
+                info!("try_with_local_trx error: {}", e);
+                None
+            }
+        }
+    }
+
     pub fn try_with_trx<T>(&self, f: impl FnOnce(WorkspaceTransaction) -> T) -> Option<T> {
         match self.doc().try_transact_mut() {
             Ok(trx) => {